@@ -10,19 +10,17 @@ fn main() -> Result<(), Error> {
     let mut contents = "".to_string();
 
     for path in paths {
-        let path_name = &path.as_ref().unwrap().path().display().to_string();
-        if path_name.starts_with('_') {
+        let path = path.unwrap();
+        let file_name = path.file_name().to_str().unwrap().to_string();
+        // mail/ holds the mail templates, those are include_str!'d by the mailer
+        if file_name.starts_with('_') || path.file_type()?.is_dir() {
             continue;
         }
+        let path_name = &path.path().display().to_string();
         let content = read_to_string(path_name).expect(path_name);
         contents.push_str(&format!(
             "#[allow(dead_code)]pub static {}: &str = r#\"{}\"#;",
-            path.unwrap()
-                .file_name()
-                .to_str()
-                .unwrap()
-                .replace(".html", "")
-                .to_ascii_uppercase(),
+            file_name.replace(".html", "").to_ascii_uppercase(),
             content
                 .lines()
                 .map(|s| s.trim())
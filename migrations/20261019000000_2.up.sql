@@ -0,0 +1,7 @@
+-- Events too large for a NOTIFY payload, referenced by id instead
+
+CREATE TABLE IF NOT EXISTS "bus_event" (
+    "id"          BIGINT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+    "payload"     TEXT NOT NULL,
+    "created_at"  TIMESTAMP DEFAULT current_timestamp NOT NULL
+);
@@ -0,0 +1,2 @@
+ALTER TABLE users DROP COLUMN IF EXISTS "custom_status";
+ALTER TABLE users DROP COLUMN IF EXISTS "status";
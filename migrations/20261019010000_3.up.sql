@@ -0,0 +1,4 @@
+-- What the user picked, idle and offline are worked out from their sessions
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS "status" TEXT NOT NULL DEFAULT 'online' CHECK (status IN ('online', 'dnd', 'invisible'));
+ALTER TABLE users ADD COLUMN IF NOT EXISTS "custom_status" TEXT CHECK (char_length(custom_status) <= 128);
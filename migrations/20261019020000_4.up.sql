@@ -0,0 +1,10 @@
+-- Single use password reset tokens, only the sha256 of the token is stored
+
+CREATE TABLE IF NOT EXISTS "password_reset" (
+    "id"          BIGINT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+    "user_id"     BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    "token_hash"  TEXT NOT NULL UNIQUE,
+    "created_at"  TIMESTAMP DEFAULT current_timestamp NOT NULL,
+    "expires_at"  TIMESTAMP NOT NULL,
+    "used_at"     TIMESTAMP
+);
@@ -0,0 +1,4 @@
+ALTER TABLE users DROP COLUMN IF EXISTS "verify_token";
+ALTER TABLE users DROP COLUMN IF EXISTS "code_attempts";
+ALTER TABLE users DROP COLUMN IF EXISTS "code_sent_at";
+ALTER TABLE users DROP COLUMN IF EXISTS "code_expires_at";
@@ -0,0 +1,9 @@
+-- Verification codes expire, can only be guessed a few times, and come with a magic link
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS "code_expires_at" TIMESTAMP;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS "code_sent_at" TIMESTAMP;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS "code_attempts" INT NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS "verify_token" TEXT UNIQUE;
+
+-- codes sent before this never expired, give them the rest of the day
+UPDATE users SET code_expires_at = NOW() + interval '1 day', code_sent_at = NOW() WHERE code IS NOT NULL;
@@ -0,0 +1,27 @@
+-- TOTP two-factor auth, its recovery codes, and the tickets handed out between password and code
+
+CREATE TABLE IF NOT EXISTS "user_totp" (
+    "user_id"     BIGINT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    "secret"      TEXT NOT NULL,
+    -- false until the first code is confirmed
+    "enabled"     BOOLEAN NOT NULL DEFAULT false,
+    -- last 30 second step a code was accepted for, so a code can't be replayed
+    "last_step"   BIGINT NOT NULL DEFAULT 0,
+    "created_at"  TIMESTAMP DEFAULT current_timestamp NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS "recovery_code" (
+    "id"          BIGINT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+    "user_id"     BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    "code_hash"   TEXT NOT NULL,
+    "used_at"     TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS recovery_code_user_idx ON recovery_code (user_id);
+
+CREATE TABLE IF NOT EXISTS "mfa_ticket" (
+    "token_hash"  TEXT PRIMARY KEY,
+    "user_id"     BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    "attempts"    INT NOT NULL DEFAULT 0,
+    "expires_at"  TIMESTAMP NOT NULL
+);
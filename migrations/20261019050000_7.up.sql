@@ -0,0 +1,15 @@
+-- WebAuthn passkeys. passkey is the serialized credential, public key and counter included,
+-- sign_count is kept next to it so it can be looked at without deserializing
+
+CREATE TABLE IF NOT EXISTS "user_credential" (
+    "id"             BIGINT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+    "user_id"        BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    "credential_id"  TEXT NOT NULL UNIQUE,
+    "passkey"        TEXT NOT NULL,
+    "sign_count"     BIGINT NOT NULL DEFAULT 0,
+    "name"           TEXT NOT NULL DEFAULT 'Passkey' CHECK (char_length(name) <= 64),
+    "created_at"     TIMESTAMP DEFAULT current_timestamp NOT NULL,
+    "last_used_at"   TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS user_credential_user_idx ON user_credential (user_id);
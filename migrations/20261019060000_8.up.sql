@@ -0,0 +1,14 @@
+-- Accounts at external OpenID Connect providers, linked to local users
+
+CREATE TABLE IF NOT EXISTS "user_identity" (
+    "id"          BIGINT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+    "user_id"     BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- provider name from the config, not the issuer url, so an issuer can move
+    "provider"    TEXT NOT NULL,
+    "subject"     TEXT NOT NULL,
+    "email"       TEXT,
+    "created_at"  TIMESTAMP DEFAULT current_timestamp NOT NULL,
+    UNIQUE (provider, subject)
+);
+
+CREATE INDEX IF NOT EXISTS user_identity_user_idx ON user_identity (user_id);
@@ -0,0 +1,7 @@
+-- Bearer tokens, each one bound to a user_sessions row and gone with it
+
+CREATE TABLE IF NOT EXISTS "session_token" (
+    "token_hash"    TEXT PRIMARY KEY,
+    "session_id"    uuid NOT NULL UNIQUE REFERENCES user_sessions(session_id) ON DELETE CASCADE,
+    "created_at"    TIMESTAMP DEFAULT current_timestamp NOT NULL
+);
@@ -0,0 +1,3 @@
+DELETE FROM users WHERE is_bot;
+DROP TABLE IF EXISTS "application";
+ALTER TABLE users DROP COLUMN IF EXISTS "is_bot";
@@ -0,0 +1,14 @@
+-- Applications owned by users, each with a bot user that logs in with a token
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS "is_bot" BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS "application" (
+    "id"           BIGINT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+    "owner_id"     BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    "bot_id"       BIGINT NOT NULL UNIQUE REFERENCES users(id) ON DELETE CASCADE,
+    "name"         TEXT NOT NULL CHECK (char_length(name) <= 64),
+    "description"  TEXT CHECK (char_length(description) <= 255),
+    "created_at"   TIMESTAMP DEFAULT current_timestamp NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS application_owner_idx ON application (owner_id);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS "login_alert";
+DROP TABLE IF EXISTS "known_device";
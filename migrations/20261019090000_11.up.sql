@@ -0,0 +1,19 @@
+-- Devices each account has logged in from, and the "this wasn't me" links sent for new ones
+
+CREATE TABLE IF NOT EXISTS "known_device" (
+    "user_id"      BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- os, browser and device without versions
+    "fingerprint"  TEXT NOT NULL,
+    "first_seen"   TIMESTAMP DEFAULT current_timestamp NOT NULL,
+    "last_seen"    TIMESTAMP DEFAULT current_timestamp NOT NULL,
+    PRIMARY KEY ("user_id", "fingerprint")
+);
+
+CREATE TABLE IF NOT EXISTS "login_alert" (
+    "token_hash"   TEXT PRIMARY KEY,
+    "user_id"      BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- no foreign key, the link still works after the session is gone
+    "session_id"   uuid NOT NULL,
+    "created_at"   TIMESTAMP DEFAULT current_timestamp NOT NULL,
+    "expires_at"   TIMESTAMP NOT NULL
+);
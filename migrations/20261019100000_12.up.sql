@@ -0,0 +1,20 @@
+-- Failed login counters, and a trail of security relevant events
+
+CREATE TABLE IF NOT EXISTS "login_throttle" (
+    -- email:<address> or ip:<address>
+    "key"           TEXT PRIMARY KEY,
+    "failures"      INT NOT NULL DEFAULT 0,
+    "last_failure"  TIMESTAMP DEFAULT current_timestamp NOT NULL,
+    "locked_until"  TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS "audit_log" (
+    "id"          BIGINT PRIMARY KEY GENERATED ALWAYS AS IDENTITY,
+    "user_id"     BIGINT REFERENCES users(id) ON DELETE SET NULL,
+    "event"       TEXT NOT NULL,
+    "ip"          TEXT,
+    "detail"      TEXT NOT NULL DEFAULT '',
+    "created_at"  TIMESTAMP DEFAULT current_timestamp NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_user_idx ON audit_log (user_id, created_at);
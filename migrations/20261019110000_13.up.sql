@@ -0,0 +1,7 @@
+-- Outstanding captcha challenges, kept here so each can only be answered once
+
+CREATE TABLE IF NOT EXISTS "captcha" (
+    "id"          uuid PRIMARY KEY DEFAULT gen_random_uuid (),
+    "expected"    TEXT NOT NULL,
+    "expires_at"  TIMESTAMP NOT NULL
+);
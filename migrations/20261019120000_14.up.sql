@@ -0,0 +1,5 @@
+-- Deleted accounts' messages are handed to this user instead of being deleted with them
+
+INSERT INTO users (username, email, password, allow_login)
+VALUES ('[deleted]', 'deleted@users.invalid', '', FALSE)
+ON CONFLICT DO NOTHING;
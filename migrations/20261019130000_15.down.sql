@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS "email_change";
+ALTER TABLE users DROP COLUMN IF EXISTS "username_changed_at";
@@ -0,0 +1,10 @@
+-- Profile edits: when the username last changed, and email changes waiting on the new address
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS "username_changed_at" TIMESTAMP;
+
+CREATE TABLE IF NOT EXISTS "email_change" (
+    "user_id"     BIGINT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    "new_email"   TEXT NOT NULL CHECK (char_length(new_email) <= 191),
+    "token_hash"  TEXT NOT NULL UNIQUE,
+    "expires_at"  TIMESTAMP NOT NULL
+);
@@ -0,0 +1,5 @@
+-- Suspensions: allow_login = FALSE is a suspended account, until suspended_until
+-- if there is one and forever otherwise
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS "suspended_until" TIMESTAMP;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS "suspension_reason" TEXT CHECK (char_length(suspension_reason) <= 255);
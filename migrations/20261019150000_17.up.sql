@@ -0,0 +1,11 @@
+-- Guild bans: who can't (re)join a guild, until expires_at if there is one and forever otherwise
+
+CREATE TABLE IF NOT EXISTS "guild_ban" (
+    "guild_id"      uuid NOT NULL REFERENCES guild(id) ON DELETE CASCADE,
+    "user_id"       BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    "moderator_id"  BIGINT REFERENCES users(id) ON DELETE SET NULL,
+    "reason"        TEXT CHECK (char_length(reason) <= 255),
+    "created_at"    TIMESTAMP NOT NULL DEFAULT NOW(),
+    "expires_at"    TIMESTAMP,
+    PRIMARY KEY (guild_id, user_id)
+);
@@ -0,0 +1,13 @@
+-- Gateway connections on every node, so presence counts sessions on all of them.
+-- Each node touches its own rows, ones nobody touched for a while belong to a node that died.
+
+CREATE TABLE IF NOT EXISTS "gateway_session" (
+    "node_id"   uuid NOT NULL,
+    "conn_id"   BIGINT NOT NULL,
+    "user_id"   BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    "idle"      BOOLEAN NOT NULL DEFAULT FALSE,
+    "seen_at"   TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (node_id, conn_id)
+);
+
+CREATE INDEX IF NOT EXISTS "gateway_session_user_idx" ON "gateway_session" (user_id);
@@ -0,0 +1,299 @@
+use std::io::{self, BufRead, Write};
+
+use sqlx::{postgres::PgPool, types::Uuid};
+
+use raspberry::config::Config;
+use raspberry::controllers::ws::{CLOSE_SESSION_REVOKED, CLOSE_SUSPENDED};
+use raspberry::db::{self, models::User, signup::create_password};
+use raspberry::messages::{WsChannelCreate, WsGuildCreate};
+use raspberry::server::bus::{Envelope, Event, EventBus, PgEventBus};
+
+const USAGE: &str = "usage: raspberry-admin <command>
+
+  create-user <username> <email> [--staff] [--superuser]   password is read from stdin
+  promote <user> [--staff] [--superuser]                   sets exactly the given roles
+  suspend <user> [--hours N] [--reason text]               no --hours is permanent, also logs them out
+  unsuspend <user>
+  reset-password <user>                                    password is read from stdin
+  revoke-sessions <user>
+  guilds
+  channels <guild id>
+  seed                                                     demo users, a guild and some messages
+
+<user> is an id, email or username. Reads the same config.toml and env as the server.";
+
+fn fail(msg: impl std::fmt::Display) -> ! {
+    eprintln!("{}", msg);
+    std::process::exit(1);
+}
+
+fn read_password() -> String {
+    print!("password: ");
+    io::stdout().flush().unwrap();
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line).unwrap();
+    let password = line.trim_end_matches(['\r', '\n']).to_string();
+    if password.is_empty() {
+        fail("empty password");
+    }
+    password
+}
+
+fn hash(password: String) -> String {
+    create_password(password).unwrap_or_else(|err| fail(err))
+}
+
+async fn user(key: Option<&String>, pool: &PgPool) -> User {
+    let key = key.unwrap_or_else(|| fail(USAGE));
+    match db::admin::find_user(key, pool).await {
+        Ok(Some(user)) => user,
+        Ok(None) => fail(format!("no user {}", key)),
+        Err(err) => fail(err),
+    }
+}
+
+// The servers only hear about it through the postgres bus, with the in-memory one
+// open sockets stay up until they reconnect and find the session gone.
+async fn disconnect(user_id: i64, code: u16, reason: &str, config: &Config, pool: &PgPool) {
+    if config.gateway.event_bus != "postgres" {
+        println!("event bus is in-memory, connected clients keep their socket until it drops");
+        return;
+    }
+    let bus = PgEventBus::connect(pool.clone())
+        .await
+        .unwrap_or_else(|err| fail(err));
+    bus.publish(&Envelope {
+        origin: Uuid::new_v4(),
+        event: Event::Disconnect {
+            user_id: user_id as usize,
+            session_id: None,
+            code,
+            reason: reason.to_string(),
+        },
+    })
+    .await;
+}
+
+async fn revoke(user: &User, code: u16, reason: &str, config: &Config, pool: &PgPool) {
+    match db::password::revoke_sessions(user.id, pool).await {
+        Ok(res) => println!(
+            "revoked {} session(s) of {}",
+            res.rows_affected(),
+            user.username
+        ),
+        Err(err) => fail(err),
+    }
+    disconnect(user.id, code, reason, config, pool).await;
+}
+
+async fn seed(pool: &PgPool) {
+    let password = hash("password".to_string());
+    let mut ids = vec![];
+    for name in ["alice", "bob", "carol"] {
+        match db::admin::create_user(
+            name.to_string(),
+            format!("{}@example.com", name),
+            password.clone(),
+            false,
+            false,
+            pool,
+        )
+        .await
+        {
+            Ok(id) => ids.push(id),
+            Err(err) => fail(format!(
+                "could not create {} (seeded already?): {}",
+                name, err
+            )),
+        }
+    }
+    let guild = db::ws_session::create_guild(
+        ids[0],
+        WsGuildCreate {
+            name: "Demo".to_string(),
+            desc: Some("Made by raspberry-admin seed".to_string()),
+            icon: None,
+        },
+        pool,
+    )
+    .await
+    .unwrap_or_else(|err| fail(err));
+    let mut channels = vec![];
+    for (position, name) in ["general", "random"].into_iter().enumerate() {
+        let channel = db::ws_session::create_channel(
+            WsChannelCreate {
+                name: name.to_string(),
+                desc: None,
+                position: position as i64,
+                guild_id: guild.id,
+                channel_type: 0,
+            },
+            pool,
+        )
+        .await
+        .unwrap_or_else(|err| fail(err));
+        channels.push(channel.id);
+    }
+    for id in &ids[1..] {
+        db::ws_session::join_guild(*id, guild.id, pool)
+            .await
+            .unwrap_or_else(|err| fail(err));
+    }
+    for (author, content) in [
+        (ids[0], "welcome to the demo guild"),
+        (ids[1], "hi!"),
+        (ids[2], "hello everyone"),
+    ] {
+        db::ws_session::create_message(content.to_string(), author, channels[0], pool)
+            .await
+            .unwrap_or_else(|err| fail(err));
+    }
+    println!(
+        "alice, bob and carol (@example.com, password \"password\") in guild {}",
+        guild.id
+    );
+}
+
+#[actix_web::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(command) = args.first() else {
+        fail(USAGE);
+    };
+    let flag = |name: &str| args.iter().any(|a| a == name);
+    let value = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .map(|i| args.get(i + 1).unwrap_or_else(|| fail(USAGE)))
+    };
+
+    let config = Config::load().unwrap_or_else(|err| fail(format!("Bad config: {}", err)));
+    let pool = PgPool::connect(&config.database.url)
+        .await
+        .unwrap_or_else(|err| fail(err));
+    if let Err(err) = raspberry::migrate::check(&pool).await {
+        fail(format!("Schema does not match this build: {}", err));
+    }
+
+    match command.as_str() {
+        "create-user" => {
+            let (Some(username), Some(email)) = (args.get(1), args.get(2)) else {
+                fail(USAGE);
+            };
+            let password = hash(read_password());
+            match db::admin::create_user(
+                username.clone(),
+                email.clone(),
+                password,
+                flag("--staff"),
+                flag("--superuser"),
+                &pool,
+            )
+            .await
+            {
+                Ok(id) => println!("created {} with id {}", username, id),
+                Err(err) => fail(err),
+            }
+        }
+        "promote" => {
+            let user = user(args.get(1), &pool).await;
+            let (staff, superuser) = (flag("--staff"), flag("--superuser"));
+            if let Err(err) = db::admin::set_roles(user.id, staff, superuser, &pool).await {
+                fail(err);
+            }
+            println!(
+                "{}: is_staff {}, is_superuser {}",
+                user.username, staff, superuser
+            );
+        }
+        "suspend" => {
+            let user = user(args.get(1), &pool).await;
+            let minutes = value("--hours").map(|hours| match hours.parse::<i32>() {
+                Ok(hours) if hours > 0 && hours <= i32::MAX / 60 => hours * 60,
+                _ => fail("--hours has to be a positive number"),
+            });
+            let reason = value("--reason").cloned();
+            if let Err(err) = db::suspension::suspend(user.id, minutes, reason, &pool).await {
+                fail(err);
+            }
+            match db::suspension::active(user.id, &pool).await {
+                Ok(Some(suspension)) => println!(
+                    "{}: suspended until {}",
+                    user.username,
+                    suspension
+                        .until
+                        .map(|until| until.format("%Y-%m-%d %H:%M UTC").to_string())
+                        .unwrap_or_else(|| "lifted".to_string())
+                ),
+                _ => println!("{}: suspended", user.username),
+            }
+            revoke(&user, CLOSE_SUSPENDED, "Account suspended", &config, &pool).await;
+        }
+        "unsuspend" => {
+            let user = user(args.get(1), &pool).await;
+            if let Err(err) = db::suspension::lift(user.id, &pool).await {
+                fail(err);
+            }
+            println!("{}: suspension lifted", user.username);
+        }
+        "reset-password" => {
+            let user = user(args.get(1), &pool).await;
+            let password = hash(read_password());
+            if let Err(err) = db::password::set_password(user.id, password, &pool).await {
+                fail(err);
+            }
+            println!("password of {} changed", user.username);
+            revoke(
+                &user,
+                CLOSE_SESSION_REVOKED,
+                "Password changed",
+                &config,
+                &pool,
+            )
+            .await;
+        }
+        "revoke-sessions" => {
+            let user = user(args.get(1), &pool).await;
+            revoke(
+                &user,
+                CLOSE_SESSION_REVOKED,
+                "Sessions revoked",
+                &config,
+                &pool,
+            )
+            .await;
+        }
+        "guilds" => {
+            for g in db::admin::list_guilds(None, i64::MAX, 0, &pool)
+                .await
+                .unwrap_or_else(|err| fail(err))
+            {
+                println!(
+                    "{} {:<50} owner {:<8} {} member(s) {} channel(s)",
+                    g.id, g.name, g.creator_id, g.members, g.channels
+                );
+            }
+        }
+        "channels" => {
+            let guild_id = args
+                .get(1)
+                .and_then(|id| Uuid::parse_str(id).ok())
+                .unwrap_or_else(|| fail(USAGE));
+            let guild = db::ws_session::get_guild_by_id(guild_id, &pool)
+                .await
+                .unwrap_or_else(|_| fail(format!("no guild {}", guild_id)));
+            println!("{} ({})", guild.name, guild.id);
+            for c in db::ws_session::get_channels_by_guild_id(guild_id, &pool)
+                .await
+                .unwrap_or_else(|err| fail(err))
+            {
+                println!(
+                    "  {} #{:<50} type {} position {}",
+                    c.id, c.name, c.channel_type, c.position
+                );
+            }
+        }
+        "seed" => seed(&pool).await,
+        _ => fail(USAGE),
+    }
+}
@@ -0,0 +1,27 @@
+use captcha_rs::CaptchaBuilder;
+
+use super::{CaptchaProvider, Challenge};
+
+// Five wobbly letters as a base64 png, what the frontend has always shown
+pub struct ImageCaptcha;
+
+impl CaptchaProvider for ImageCaptcha {
+    fn challenge(&self) -> Challenge {
+        let captcha = CaptchaBuilder::new()
+            .length(5)
+            .width(130)
+            .height(50)
+            .dark_mode(false)
+            .complexity(5) // min: 1, max: 10
+            .build();
+        Challenge {
+            body: captcha.to_base64(),
+            json: false,
+            expected: captcha.text,
+        }
+    }
+
+    fn check(&self, expected: &str, answer: &str) -> bool {
+        expected == answer
+    }
+}
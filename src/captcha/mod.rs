@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use crate::config::CaptchaConfig;
+
+pub mod image;
+pub mod none;
+pub mod pow;
+
+// seconds a challenge can be answered in
+pub const CAPTCHA_SECONDS: f64 = 300.0;
+
+// What a GET /login or /signup hands out. body goes to the client, expected stays on
+// the server until the answer comes back.
+pub struct Challenge {
+    pub body: String,
+    // json body, otherwise plain text
+    pub json: bool,
+    pub expected: String,
+}
+
+pub trait CaptchaProvider: Send + Sync {
+    fn challenge(&self) -> Challenge;
+
+    fn check(&self, expected: &str, answer: &str) -> bool;
+
+    // false lets every request through without asking for a challenge first
+    fn required(&self) -> bool {
+        true
+    }
+}
+
+// kind is checked by Config::validate, so anything but pow and none is the image
+pub fn from_config(config: &CaptchaConfig) -> Arc<dyn CaptchaProvider> {
+    match config.kind.as_str() {
+        "pow" => Arc::new(pow::PowCaptcha::new(config.pow_bits)),
+        "none" => Arc::new(none::NoCaptcha),
+        _ => Arc::new(image::ImageCaptcha),
+    }
+}
@@ -0,0 +1,22 @@
+use super::{CaptchaProvider, Challenge};
+
+// For tests and deployments that only trusted clients can reach
+pub struct NoCaptcha;
+
+impl CaptchaProvider for NoCaptcha {
+    fn challenge(&self) -> Challenge {
+        Challenge {
+            body: String::new(),
+            json: false,
+            expected: String::new(),
+        }
+    }
+
+    fn check(&self, _expected: &str, _answer: &str) -> bool {
+        true
+    }
+
+    fn required(&self) -> bool {
+        false
+    }
+}
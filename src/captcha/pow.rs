@@ -0,0 +1,57 @@
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use super::{CaptchaProvider, Challenge};
+use crate::token;
+
+// about a million hashes, a second or so in a browser
+pub const DEFAULT_BITS: u32 = 20;
+
+// The client has to find an answer where sha256("<nonce>:<answer>") starts with
+// `bits` zero bits. Cheap to check, costly to do in bulk.
+pub struct PowCaptcha {
+    bits: u32,
+}
+
+impl PowCaptcha {
+    pub fn new(bits: u32) -> Self {
+        Self { bits: bits.min(32) }
+    }
+}
+
+pub fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        if *byte == 0 {
+            bits += 8;
+        } else {
+            return bits + byte.leading_zeros();
+        }
+    }
+    bits
+}
+
+pub fn solves(nonce: &str, answer: &str, bits: u32) -> bool {
+    leading_zero_bits(&Sha256::digest(format!("{}:{}", nonce, answer).as_bytes())) >= bits
+}
+
+impl CaptchaProvider for PowCaptcha {
+    fn challenge(&self) -> Challenge {
+        let nonce = token::generate();
+        Challenge {
+            body: json!({ "kind": "pow", "nonce": nonce, "bits": self.bits }).to_string(),
+            json: true,
+            // the difficulty it was issued with, in case it changes before the answer comes in
+            expected: format!("{}:{}", self.bits, nonce),
+        }
+    }
+
+    fn check(&self, expected: &str, answer: &str) -> bool {
+        match expected.split_once(':') {
+            Some((bits, nonce)) => bits
+                .parse()
+                .is_ok_and(|bits| solves(nonce, answer.trim(), bits)),
+            None => false,
+        }
+    }
+}
@@ -1,74 +1,517 @@
-use std::collections::HashMap;
-
-use actix_web::{http::header::ContentType, web, HttpResponse};
-use regex::Regex;
-
-use crate::db::ws_session;
-use crate::html;
-
-use sqlx::postgres::PgPool;
-
-pub fn format_html(resp: &str, replacements: HashMap<&str, String>) -> String {
-    let re: Regex = Regex::new(r"\{\{(.*?)\}\}").unwrap();
-    let v: Vec<&str> = re.find_iter(resp).map(|s| s.as_str()).collect();
-    v.iter()
-        .fold(resp.to_string(), |resp, needle| {
-            resp.replace(
-                needle,
-                &replacements[needle
-                    .strip_prefix("{{")
-                    .unwrap()
-                    .strip_suffix("}}")
-                    .unwrap()],
-            )
+use std::future::Future;
+use std::pin::Pin;
+
+use actix_http::Payload;
+use actix_web::{
+    error::{ErrorForbidden, ErrorInternalServerError},
+    http::{header::ContentType, StatusCode},
+    web, FromRequest, HttpRequest, HttpResponse,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{types::Uuid, PgPool};
+
+use super::auth::Auth;
+use super::login::client_ip;
+use crate::controllers::ws::{CLOSE_SESSION_REVOKED, CLOSE_SUSPENDED};
+use crate::db;
+use crate::messages::{ChannelDeleteType, MessageDeleteType, MessageTypes};
+use crate::server;
+use utoipa;
+
+// rows per page when the query doesn't say, and the most it can ask for
+const PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+// A logged in is_staff or is_superuser user. Everything under /admin/api takes one.
+#[derive(Clone, Debug)]
+pub struct Staff {
+    pub user_id: i64,
+    pub is_superuser: bool,
+}
+
+impl FromRequest for Staff {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            let auth = Auth::extract(&req).await?;
+            let pool = req
+                .app_data::<web::Data<PgPool>>()
+                .ok_or_else(|| ErrorInternalServerError("No database"))?;
+            match db::admin::get_user(auth.user_id, pool).await {
+                Ok(Some(user)) if user.is_staff || user.is_superuser => Ok(Staff {
+                    user_id: user.id,
+                    is_superuser: user.is_superuser,
+                }),
+                Ok(_) => Err(ErrorForbidden("Staff only")),
+                Err(err) => Err(ErrorInternalServerError(err)),
+            }
         })
-        .replace('\n', "<br />")
-}
-
-pub async fn get(pool: web::Data<PgPool>) -> HttpResponse {
-    let replacements: HashMap<&str, String> = HashMap::from_iter([
-        (
-            "users_data",
-            ws_session::get_all(pool.as_ref())
-                .await
-                .unwrap()
-                .into_iter()
-                .map(|s| format!("{:?}", s))
-                .collect::<Vec<String>>()
-                .join("\n"),
-        ),
-        (
-            "user_sessions_data",
-            ws_session::get_all_sessions(pool.as_ref())
-                .await
-                .unwrap()
-                .into_iter()
-                .map(|s| format!("{:?}", s))
-                .collect::<Vec<String>>()
-                .join("\n"),
-        ),
-        (
-            "guilds_data",
-            ws_session::get_all_guilds(pool.as_ref())
-                .await
-                .unwrap()
-                .into_iter()
-                .map(|s| format!("{:?}", s))
-                .collect::<Vec<String>>()
-                .join("\n"),
-        ),
-        (
-            "member_data",
-            ws_session::get_all_members(pool.as_ref())
-                .await
-                .unwrap()
-                .into_iter()
-                .map(|s| format!("{:?}", s))
-                .collect::<Vec<String>>()
-                .join("\n"),
-        ),
-    ]);
-    HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(format_html(html::ADMIN, replacements))
+    }
+}
+
+fn bad_request(body: &'static str) -> HttpResponse {
+    HttpResponse::build(StatusCode::BAD_REQUEST)
+        .content_type(ContentType::plaintext())
+        .body(body)
+}
+
+fn forbidden(body: &'static str) -> HttpResponse {
+    HttpResponse::build(StatusCode::FORBIDDEN)
+        .content_type(ContentType::plaintext())
+        .body(body)
+}
+
+fn bounds(limit: Option<i64>, offset: Option<i64>) -> (i64, i64) {
+    (
+        limit.unwrap_or(PAGE_SIZE).clamp(1, MAX_PAGE_SIZE),
+        offset.unwrap_or(0).max(0),
+    )
+}
+
+// The listings fetch one row more than the page, that's how we know there's a next one
+fn page<T: Serialize>(rows: sqlx::Result<Vec<T>>, limit: i64, offset: i64) -> HttpResponse {
+    match rows {
+        Ok(mut items) => {
+            let next_offset = (items.len() as i64 > limit).then_some(offset + limit);
+            items.truncate(limit as usize);
+            HttpResponse::Ok().json(json!({ "items": items, "next_offset": next_offset }))
+        }
+        Err(err) => {
+            log::error!("{}", err);
+            bad_request("Bad request (database errored, you are unlucky)")
+        }
+    }
+}
+
+async fn audit(staff: &Staff, event: &str, detail: String, req: &HttpRequest, pool: &PgPool) {
+    if let Err(err) =
+        db::audit::record(Some(staff.user_id), event, Some(client_ip(req)), detail, pool).await
+    {
+        log::error!("{}", err);
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UserQuery {
+    q: Option<String>,
+    // staff or superuser
+    staff: Option<bool>,
+    suspended: Option<bool>,
+    bot: Option<bool>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+pub async fn users(
+    _staff: Staff,
+    query: web::Query<UserQuery>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let query = query.into_inner();
+    let (limit, offset) = bounds(query.limit, query.offset);
+    let rows = db::admin::list_users(
+        query.q,
+        query.staff,
+        query.suspended,
+        query.bot,
+        limit + 1,
+        offset,
+        pool.as_ref(),
+    )
+    .await;
+    page(rows, limit, offset)
+}
+
+pub async fn user(_staff: Staff, path: web::Path<i64>, pool: web::Data<PgPool>) -> HttpResponse {
+    match db::admin::get_user(path.into_inner(), pool.as_ref()).await {
+        Ok(Some(user)) => HttpResponse::Ok().json(user),
+        Ok(None) => bad_request("No such user"),
+        Err(_) => bad_request("Bad request (database errored, you are unlucky)"),
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path = "/admin/api/users/{id}",
+    responses(
+        (status = 200, description = "The updated user", body = String),
+        (status = 400, description = "No such user, or it's your own account", body = String),
+        (status = 403, description = "Roles and superusers can only be changed by a superuser", body = String)
+    ),
+    request_body(content = AdminUserUpdateEvent, description = "is_staff, is_superuser or both", content_type = "application/json")
+)]
+pub async fn update_user(
+    staff: Staff,
+    path: web::Path<i64>,
+    body: web::Json<server::AdminUserUpdateEvent>,
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let user_id = path.into_inner();
+    let pl = body.into_inner();
+    if user_id == staff.user_id {
+        return bad_request("You can't change your own account here");
+    }
+    let target = match db::admin::get_user(user_id, pool.as_ref()).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return bad_request("No such user"),
+        Err(_) => return bad_request("Bad request (database errored, you are unlucky)"),
+    };
+    let changes_roles = pl.is_staff.is_some() || pl.is_superuser.is_some();
+    if !staff.is_superuser && (changes_roles || target.is_superuser) {
+        return forbidden("Only a superuser can do that");
+    }
+    if changes_roles {
+        if let Err(err) = db::admin::set_roles(
+            user_id,
+            pl.is_staff.unwrap_or(target.is_staff),
+            pl.is_superuser.unwrap_or(target.is_superuser),
+            pool.as_ref(),
+        )
+        .await
+        {
+            log::error!("{}", err);
+            return bad_request("DB failed to update the user");
+        }
+    }
+    audit(
+        &staff,
+        "admin_user_update",
+        json!({ "user_id": user_id, "update": pl }).to_string(),
+        &req,
+        pool.as_ref(),
+    )
+    .await;
+    match db::admin::get_user(user_id, pool.as_ref()).await {
+        Ok(Some(user)) => HttpResponse::Ok().json(user),
+        _ => HttpResponse::Ok().finish(),
+    }
+}
+
+// Staff can't suspend themselves, and only a superuser can suspend a superuser
+async fn suspendable(staff: &Staff, user_id: i64, pool: &PgPool) -> Result<(), HttpResponse> {
+    if user_id == staff.user_id {
+        return Err(bad_request("You can't suspend your own account"));
+    }
+    match db::admin::get_user(user_id, pool).await {
+        Ok(Some(target)) if target.is_superuser && !staff.is_superuser => {
+            Err(forbidden("Only a superuser can do that"))
+        }
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(bad_request("No such user")),
+        Err(_) => Err(bad_request(
+            "Bad request (database errored, you are unlucky)",
+        )),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/api/users/{id}/suspension",
+    responses(
+        (status = 200, description = "The suspended user, logged out everywhere", body = String),
+        (status = 400, description = "No such user, it's your own account, or hours isn't positive", body = String),
+        (status = 403, description = "Superusers can only be suspended by a superuser", body = String)
+    ),
+    request_body(content = AdminSuspendEvent, description = "reason and hours, both optional", content_type = "application/json")
+)]
+pub async fn suspend(
+    staff: Staff,
+    path: web::Path<i64>,
+    body: web::Json<server::AdminSuspendEvent>,
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    srv: web::Data<server::Chat>,
+) -> HttpResponse {
+    let user_id = path.into_inner();
+    let pl = body.into_inner();
+    if let Err(res) = suspendable(&staff, user_id, pool.as_ref()).await {
+        return res;
+    }
+    let minutes = match pl.hours {
+        Some(hours) if hours <= 0 || hours > i32::MAX / 60 => {
+            return bad_request("hours has to be positive")
+        }
+        hours => hours.map(|hours| hours * 60),
+    };
+    if pl.reason.as_ref().is_some_and(|reason| reason.len() > 255) {
+        return bad_request("reason is longer than 255 characters");
+    }
+    if let Err(err) =
+        db::suspension::suspend(user_id, minutes, pl.reason.clone(), pool.as_ref()).await
+    {
+        log::error!("{}", err);
+        return bad_request("DB failed to suspend the user");
+    }
+    if let Err(err) = db::password::revoke_sessions(user_id, pool.as_ref()).await {
+        log::error!("{}", err);
+    }
+    srv.disconnect_user(user_id as usize, None, CLOSE_SUSPENDED, "Account suspended")
+        .await;
+    audit(
+        &staff,
+        "admin_suspend",
+        json!({ "user_id": user_id, "suspension": pl }).to_string(),
+        &req,
+        pool.as_ref(),
+    )
+    .await;
+    match db::admin::get_user(user_id, pool.as_ref()).await {
+        Ok(Some(user)) => HttpResponse::Ok().json(user),
+        _ => HttpResponse::Ok().finish(),
+    }
+}
+
+pub async fn unsuspend(
+    staff: Staff,
+    path: web::Path<i64>,
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let user_id = path.into_inner();
+    if let Err(res) = suspendable(&staff, user_id, pool.as_ref()).await {
+        return res;
+    }
+    if let Err(err) = db::suspension::lift(user_id, pool.as_ref()).await {
+        log::error!("{}", err);
+        return bad_request("DB failed to lift the suspension");
+    }
+    audit(
+        &staff,
+        "admin_unsuspend",
+        json!({ "user_id": user_id }).to_string(),
+        &req,
+        pool.as_ref(),
+    )
+    .await;
+    match db::admin::get_user(user_id, pool.as_ref()).await {
+        Ok(Some(user)) => HttpResponse::Ok().json(user),
+        _ => HttpResponse::Ok().finish(),
+    }
+}
+
+// Logs the user out everywhere
+pub async fn revoke_sessions(
+    staff: Staff,
+    path: web::Path<i64>,
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    srv: web::Data<server::Chat>,
+) -> HttpResponse {
+    let user_id = path.into_inner();
+    match db::admin::get_user(user_id, pool.as_ref()).await {
+        Ok(Some(target)) if target.is_superuser && !staff.is_superuser => {
+            return forbidden("Only a superuser can do that")
+        }
+        Ok(Some(_)) => {}
+        Ok(None) => return bad_request("No such user"),
+        Err(_) => return bad_request("Bad request (database errored, you are unlucky)"),
+    }
+    if let Err(err) = db::password::revoke_sessions(user_id, pool.as_ref()).await {
+        log::error!("{}", err);
+        return bad_request("DB failed to revoke sessions");
+    }
+    srv.disconnect_user(
+        user_id as usize,
+        None,
+        CLOSE_SESSION_REVOKED,
+        "Sessions revoked",
+    )
+    .await;
+    audit(
+        &staff,
+        "admin_revoke_sessions",
+        json!({ "user_id": user_id }).to_string(),
+        &req,
+        pool.as_ref(),
+    )
+    .await;
+    HttpResponse::Ok().finish()
+}
+
+#[derive(Deserialize)]
+pub struct SessionQuery {
+    user_id: Option<i64>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+pub async fn sessions(
+    _staff: Staff,
+    query: web::Query<SessionQuery>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let (limit, offset) = bounds(query.limit, query.offset);
+    let rows = db::admin::list_sessions(query.user_id, limit + 1, offset, pool.as_ref()).await;
+    page(rows, limit, offset)
+}
+
+pub async fn delete_session(
+    staff: Staff,
+    path: web::Path<Uuid>,
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    srv: web::Data<server::Chat>,
+) -> HttpResponse {
+    let session_id = path.into_inner();
+    match db::admin::delete_session(session_id, pool.as_ref()).await {
+        Ok(Some(user_id)) => {
+            srv.disconnect_user(
+                user_id as usize,
+                Some(session_id.to_string()),
+                CLOSE_SESSION_REVOKED,
+                "Session revoked",
+            )
+            .await;
+            audit(
+                &staff,
+                "admin_delete_session",
+                json!({ "user_id": user_id, "session_id": session_id }).to_string(),
+                &req,
+                pool.as_ref(),
+            )
+            .await;
+            HttpResponse::Ok().finish()
+        }
+        Ok(None) => bad_request("No such session"),
+        Err(_) => bad_request("Bad request (database errored, you are unlucky)"),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct GuildQuery {
+    q: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+pub async fn guilds(
+    _staff: Staff,
+    query: web::Query<GuildQuery>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let query = query.into_inner();
+    let (limit, offset) = bounds(query.limit, query.offset);
+    let rows = db::admin::list_guilds(query.q, limit + 1, offset, pool.as_ref()).await;
+    page(rows, limit, offset)
+}
+
+#[derive(Deserialize)]
+pub struct ChannelQuery {
+    guild_id: Option<Uuid>,
+    q: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+pub async fn channels(
+    _staff: Staff,
+    query: web::Query<ChannelQuery>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let query = query.into_inner();
+    let (limit, offset) = bounds(query.limit, query.offset);
+    let rows =
+        db::admin::list_channels(query.guild_id, query.q, limit + 1, offset, pool.as_ref()).await;
+    page(rows, limit, offset)
+}
+
+// Guild channels only, same as deleting it from the gateway
+pub async fn delete_channel(
+    staff: Staff,
+    path: web::Path<Uuid>,
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    srv: web::Data<server::Chat>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    match db::ws_session::delete_channel(id, pool.as_ref()).await {
+        Ok(guild_id) => {
+            if let Some(guild_id) = guild_id {
+                srv.send_guild_message(
+                    &guild_id.to_string(),
+                    MessageTypes::ChannelDelete(ChannelDeleteType { id }),
+                )
+                .await;
+            }
+            audit(
+                &staff,
+                "admin_delete_channel",
+                json!({ "channel_id": id, "guild_id": guild_id }).to_string(),
+                &req,
+                pool.as_ref(),
+            )
+            .await;
+            HttpResponse::Ok().finish()
+        }
+        Err(sqlx::Error::RowNotFound) => bad_request("No such guild channel"),
+        Err(_) => bad_request("Bad request (database errored, you are unlucky)"),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MessageQuery {
+    channel_id: Option<Uuid>,
+    author_id: Option<i64>,
+    q: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+pub async fn messages(
+    _staff: Staff,
+    query: web::Query<MessageQuery>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let query = query.into_inner();
+    let (limit, offset) = bounds(query.limit, query.offset);
+    let rows = db::admin::list_messages(
+        query.channel_id,
+        query.author_id,
+        query.q,
+        limit + 1,
+        offset,
+        pool.as_ref(),
+    )
+    .await;
+    page(rows, limit, offset)
+}
+
+pub async fn delete_message(
+    staff: Staff,
+    path: web::Path<Uuid>,
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    srv: web::Data<server::Chat>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let info = match db::ws_session::delete_message(id, pool.as_ref()).await {
+        Ok(info) => info,
+        Err(sqlx::Error::RowNotFound) => return bad_request("No such message"),
+        Err(_) => return bad_request("Bad request (database errored, you are unlucky)"),
+    };
+    let event = MessageTypes::MessageDelete(MessageDeleteType {
+        id,
+        channel_id: info.channel_id,
+    });
+    match (info.guild_id, info.user1, info.user2) {
+        (Some(guild_id), _, _) => srv.send_guild_message(&guild_id.to_string(), event).await,
+        (None, Some(user1), Some(user2)) => {
+            srv.send_dm(user1 as usize, user2 as usize, event).await
+        }
+        _ => {}
+    }
+    audit(
+        &staff,
+        "admin_delete_message",
+        json!({ "message_id": id, "channel_id": info.channel_id }).to_string(),
+        &req,
+        pool.as_ref(),
+    )
+    .await;
+    HttpResponse::Ok().finish()
 }
@@ -0,0 +1,160 @@
+use actix_web::{
+    http::{header::ContentType, StatusCode},
+    web, HttpRequest, HttpResponse,
+};
+use chrono::Utc;
+use sqlx::{types::Uuid, PgPool};
+
+use super::password::RESET_MINUTES;
+use crate::config::Config;
+use crate::controllers::ws::CLOSE_SESSION_REVOKED;
+use crate::db::{self, signup::UserAgent};
+use crate::mailer::{templates, MailQueue};
+use crate::messages::{MessageTypes, NotificationType};
+use crate::{html, server, token};
+
+// how long the "this wasn't me" link works
+const ALERT_DAYS: i32 = 7;
+
+// "Chrome 118 0" and "Chrome 119 0" are the same browser
+fn family(part: &Option<String>) -> String {
+    part.as_deref()
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter(|w| !w.chars().all(|c| c.is_ascii_digit()))
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+pub fn fingerprint(uag: &UserAgent) -> String {
+    format!(
+        "{}|{}|{}",
+        family(&uag.os),
+        family(&uag.browser),
+        family(&uag.device)
+    )
+}
+
+// Called right after a login created session_id. A device the account hasn't used before
+// gets an email with a "this wasn't me" link and a notification on the sessions already open.
+pub async fn check_device(
+    user_id: i64,
+    session_id: Uuid,
+    uag: &UserAgent,
+    req: &HttpRequest,
+    pool: &PgPool,
+) {
+    match db::alerts::see_device(user_id, &fingerprint(uag), pool).await {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(err) => {
+            log::error!("{}", err);
+            return;
+        }
+    }
+    let email = match db::alerts::get_email(user_id, pool).await {
+        Ok(email) => email,
+        Err(err) => {
+            log::error!("{}", err);
+            return;
+        }
+    };
+    let alert = token::generate();
+    if let Err(err) = db::alerts::create_alert(
+        user_id,
+        session_id,
+        token::hash(&alert),
+        ALERT_DAYS * 24 * 60,
+        pool,
+    )
+    .await
+    {
+        log::error!("{}", err);
+        return;
+    }
+
+    let unknown = || "Unknown".to_string();
+    let browser = uag.browser.clone().unwrap_or_else(unknown);
+    let os = uag.os.clone().unwrap_or_else(unknown);
+    let device = uag.device.clone().unwrap_or_else(unknown);
+    let time = Utc::now().format("%Y-%m-%d %H:%M UTC").to_string();
+    let link = match req.app_data::<web::Data<Config>>() {
+        Some(config) => format!("{}/login/alerts/{}", config.public_url(), alert),
+        None => return,
+    };
+    if let Some(mail) = req.app_data::<web::Data<MailQueue>>() {
+        mail.send(templates::new_device(
+            &email, &browser, &os, &device, &time, &link, ALERT_DAYS,
+        ));
+    }
+    if let Some(srv) = req.app_data::<web::Data<server::Chat>>() {
+        srv.send_to_id(
+            user_id as usize,
+            MessageTypes::Notification(NotificationType {
+                title: "New login".to_string(),
+                body: format!(
+                    "Your account was logged in to from {} on {} ({}) at {}. If it wasn't you, check your email.",
+                    browser, os, device, time
+                ),
+                created_at: Utc::now().naive_utc(),
+            }),
+        )
+        .await;
+    }
+}
+
+fn invalid_link() -> HttpResponse {
+    HttpResponse::build(StatusCode::BAD_REQUEST)
+        .content_type(ContentType::plaintext())
+        .body("Invalid or expired link")
+}
+
+// The "this wasn't me" link from the email. Mail scanners and link previews open links
+// on their own, so this only asks, the form on the page POSTs back to do it.
+pub async fn confirm_not_me(path: web::Path<String>, pool: web::Data<PgPool>) -> HttpResponse {
+    match db::alerts::alert_exists(token::hash(&path.into_inner()), pool.as_ref()).await {
+        Ok(true) => HttpResponse::Ok()
+            .content_type(ContentType::html())
+            .body(html::NOT_ME),
+        _ => invalid_link(),
+    }
+}
+
+// Whoever logged in might know the password, so every session goes, the password
+// stops working and a reset code is sent to the owner.
+pub async fn not_me(
+    path: web::Path<String>,
+    pool: web::Data<PgPool>,
+    srv: web::Data<server::Chat>,
+    mail: web::Data<MailQueue>,
+) -> HttpResponse {
+    let (user_id, email) =
+        match db::alerts::use_alert(token::hash(&path.into_inner()), pool.as_ref()).await {
+            Ok(Some(found)) => found,
+            _ => return invalid_link(),
+        };
+    // an empty hash never verifies, only a reset gets them back in with a password
+    if let Err(err) = db::password::set_password(user_id, String::new(), pool.as_ref()).await {
+        log::error!("{}", err);
+    }
+    if let Err(err) = db::password::revoke_sessions(user_id, pool.as_ref()).await {
+        log::error!("{}", err);
+    }
+    srv.disconnect_user(
+        user_id as usize,
+        None,
+        CLOSE_SESSION_REVOKED,
+        "Password reset required",
+    )
+    .await;
+    let reset = token::generate();
+    match db::password::create_reset(user_id, token::hash(&reset), RESET_MINUTES, pool.as_ref())
+        .await
+    {
+        Ok(_) => mail.send(templates::password_reset(&email, &reset, RESET_MINUTES)),
+        Err(err) => log::error!("{}", err),
+    }
+    HttpResponse::Ok()
+        .content_type(ContentType::plaintext())
+        .body("Every session was logged out, check your email to set a new password")
+}
@@ -0,0 +1,201 @@
+use actix_web::{
+    http::{header::ContentType, StatusCode},
+    web, HttpResponse,
+};
+use serde_json::json;
+use sqlx::{types::Uuid, PgPool};
+
+use super::auth::Auth;
+use super::login::suspended;
+use crate::controllers::ws::CLOSE_SESSION_REVOKED;
+use crate::db::{self, models::Application, signup::UserAgent};
+use crate::{server, token};
+use utoipa;
+
+fn bad_request(body: &'static str) -> HttpResponse {
+    HttpResponse::build(StatusCode::BAD_REQUEST)
+        .content_type(ContentType::plaintext())
+        .body(body)
+}
+
+// bots can't make more bots
+async fn is_bot(auth: &Auth, pool: &PgPool) -> bool {
+    match db::ws_session::get_user_by_id(auth.user_id, pool).await {
+        Ok(user) => user.is_bot,
+        Err(_) => true,
+    }
+}
+
+// the application if auth owns it
+async fn owned(id: i64, auth: &Auth, pool: &PgPool) -> Option<Application> {
+    match db::applications::get_application(id, pool).await {
+        Ok(Some(app)) if app.owner_id == auth.user_id => Some(app),
+        _ => None,
+    }
+}
+
+// A bot token is a session of the bot user, same as `/auth/token` hands out
+async fn new_token(bot_id: i64, pool: &PgPool) -> sqlx::Result<String> {
+    let uag = UserAgent {
+        os: None,
+        device: None,
+        browser: None,
+        original: "bot".to_string(),
+    };
+    let session_id = db::login::create_session(bot_id, pool, uag).await?;
+    let token = token::generate();
+    db::auth::create_token(session_id, token::hash(&token), pool).await?;
+    Ok(token)
+}
+
+#[utoipa::path(
+    post,
+    path = "/applications",
+    responses(
+        (status = 200, description = "{\"application\": ..., \"token\": ...}, the token is only shown this once", body = String),
+        (status = 400, description = "Name taken or too long", body = String)
+    ),
+    request_body(content = ApplicationCreateEvent, description = "application name (also the bot's username), description", content_type = "application/json")
+)]
+pub async fn create(
+    body: web::Json<server::ApplicationCreateEvent>,
+    auth: Auth,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    if is_bot(&auth, pool.as_ref()).await {
+        return bad_request("Bots can't own applications");
+    }
+    let pl = body.into_inner();
+    let name = pl.name.trim().to_string();
+    if name.is_empty() || name.len() > 64 {
+        return bad_request("Name must be 1 to 64 characters");
+    }
+    if pl.description.as_ref().is_some_and(|d| d.len() > 255) {
+        return bad_request("Description is too long");
+    }
+    match db::oidc::username_taken(&name, pool.as_ref()).await {
+        Ok(false) => {}
+        Ok(true) => return bad_request("Name taken"),
+        Err(_) => return bad_request("Bad request (database errored, you are unlucky)"),
+    }
+    let app = match db::applications::create_application(
+        auth.user_id,
+        name,
+        pl.description,
+        pool.as_ref(),
+    )
+    .await
+    {
+        Ok(app) => app,
+        Err(err) => {
+            log::error!("{}", err);
+            return bad_request("DB failed to create application");
+        }
+    };
+    match new_token(app.bot_id, pool.as_ref()).await {
+        Ok(token) => HttpResponse::Ok().json(json!({ "application": app, "token": token })),
+        Err(err) => {
+            log::error!("{}", err);
+            bad_request("DB failed to create token")
+        }
+    }
+}
+
+pub async fn list(auth: Auth, pool: web::Data<PgPool>) -> HttpResponse {
+    match db::applications::list_applications(auth.user_id, pool.as_ref()).await {
+        Ok(apps) => HttpResponse::Ok().json(apps),
+        Err(_) => bad_request("Bad request (database errored, you are unlucky)"),
+    }
+}
+
+// Logs the bot out everywhere and hands out a new token
+pub async fn reset_token(
+    path: web::Path<i64>,
+    auth: Auth,
+    pool: web::Data<PgPool>,
+    srv: web::Data<server::Chat>,
+) -> HttpResponse {
+    let Some(app) = owned(path.into_inner(), &auth, pool.as_ref()).await else {
+        return bad_request("No such application");
+    };
+    // a suspended bot stays without a token until staff lift it
+    if let Some(res) = suspended(app.bot_id, pool.as_ref()).await {
+        return res;
+    }
+    if let Err(err) = db::password::revoke_sessions(app.bot_id, pool.as_ref()).await {
+        log::error!("{}", err);
+        return bad_request("DB failed to reset token");
+    }
+    srv.disconnect_user(
+        app.bot_id as usize,
+        None,
+        CLOSE_SESSION_REVOKED,
+        "Token reset",
+    )
+    .await;
+    match new_token(app.bot_id, pool.as_ref()).await {
+        Ok(token) => HttpResponse::Ok().json(json!({ "token": token })),
+        Err(err) => {
+            log::error!("{}", err);
+            bad_request("DB failed to create token")
+        }
+    }
+}
+
+pub async fn delete(
+    path: web::Path<i64>,
+    auth: Auth,
+    pool: web::Data<PgPool>,
+    srv: web::Data<server::Chat>,
+) -> HttpResponse {
+    let Some(app) = owned(path.into_inner(), &auth, pool.as_ref()).await else {
+        return bad_request("No such application");
+    };
+    match db::applications::delete_application(app.id, auth.user_id, pool.as_ref()).await {
+        Ok(Some(left)) => {
+            for guild_id in left {
+                srv.remove_member(guild_id.to_string(), app.bot_id as usize)
+                    .await;
+            }
+            srv.disconnect_user(
+                app.bot_id as usize,
+                None,
+                CLOSE_SESSION_REVOKED,
+                "Application deleted",
+            )
+            .await;
+            HttpResponse::Ok().finish()
+        }
+        Ok(None) => bad_request("No such application"),
+        Err(err) => {
+            log::error!("{}", err);
+            bad_request("Bad request (database errored, you are unlucky)")
+        }
+    }
+}
+
+// Only the guild's creator can bring a bot in
+pub async fn add_to_guild(
+    path: web::Path<(i64, Uuid)>,
+    auth: Auth,
+    pool: web::Data<PgPool>,
+    srv: web::Data<server::Chat>,
+) -> HttpResponse {
+    let (id, guild_id) = path.into_inner();
+    let Some(app) = owned(id, &auth, pool.as_ref()).await else {
+        return bad_request("No such application");
+    };
+    let guild = match db::ws_session::get_guild_by_id(guild_id, pool.as_ref()).await {
+        Ok(guild) if guild.creator_id == auth.user_id => guild,
+        _ => return bad_request("No such guild"),
+    };
+    match db::ws_session::join_guild(app.bot_id, guild_id, pool.as_ref()).await {
+        Ok(channels) => {
+            srv.add_member(guild, app.bot_id as usize, channels).await;
+            HttpResponse::Ok().finish()
+        }
+        Err(sqlx::Error::RowNotFound) => bad_request("The bot is banned from this guild"),
+        // already a member most likely
+        Err(_) => bad_request("Could not add the bot to the guild"),
+    }
+}
@@ -0,0 +1,192 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use actix_http::Payload;
+use actix_identity::Identity;
+use actix_web::{
+    error::{ErrorForbidden, ErrorInternalServerError, ErrorUnauthorized},
+    http::{header, header::ContentType, StatusCode},
+    web, FromRequest, HttpRequest, HttpResponse,
+};
+use serde_json::json;
+use sqlx::{types::Uuid, PgPool};
+use user_agent_parser::UserAgentParser;
+
+use super::alerts;
+use super::login::{
+    check_code, describe, dummy_hash, failed_login, passed_login, suspended, throttled,
+    user_agent, verify_password,
+};
+use crate::{db, server, token};
+use utoipa;
+
+// Who is making the request, from either an `Authorization: Bearer` token or the auth cookie.
+// Either way the user_sessions row has to still exist and the account can't be suspended
+// (that's a 403). Take `Auth` to require it, `Option<Auth>` when anonymous requests are fine too.
+#[derive(Clone, Debug)]
+pub struct Auth {
+    pub user_id: i64,
+    pub session_id: String,
+}
+
+pub fn bearer(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(|t| t.trim().to_string())
+}
+
+impl Auth {
+    // Only the session, FromRequest checks the suspension on top
+    pub async fn from_token(token: &str, pool: &PgPool) -> Option<Self> {
+        match db::auth::session_for_token(token::hash(token), pool).await {
+            Ok(Some((user_id, session_id))) => Some(Self {
+                user_id,
+                session_id: session_id.to_string(),
+            }),
+            Ok(None) => None,
+            Err(err) => {
+                log::error!("{}", err);
+                None
+            }
+        }
+    }
+
+    // Suspending revokes the sessions too, this catches whatever slips past that
+    pub async fn unless_suspended(self, pool: &PgPool) -> Result<Self, actix_web::Error> {
+        match db::suspension::active(self.user_id, pool).await {
+            Ok(None) => Ok(self),
+            Ok(Some(suspension)) => Err(ErrorForbidden(describe(&suspension))),
+            Err(err) => Err(ErrorInternalServerError(err)),
+        }
+    }
+}
+
+impl FromRequest for Auth {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            let pool = req
+                .app_data::<web::Data<PgPool>>()
+                .ok_or_else(|| ErrorInternalServerError("No database"))?
+                .clone();
+            // a token wins over a cookie, scripts might have both
+            if let Some(token) = bearer(&req) {
+                return match Auth::from_token(&token, &pool).await {
+                    Some(auth) => auth.unless_suspended(&pool).await,
+                    None => Err(ErrorUnauthorized("Invalid token")),
+                };
+            }
+            let id = Identity::extract(&req)
+                .await
+                .map_err(|_| ErrorUnauthorized("Not logged in"))?;
+            let cookie: server::AuthCookie = id
+                .id()
+                .ok()
+                .and_then(|id| serde_json::from_str(&id).ok())
+                .ok_or_else(|| ErrorUnauthorized("Not logged in"))?;
+            let session_id = Uuid::parse_str(&cookie.session_id)
+                .map_err(|_| ErrorUnauthorized("Not logged in"))?;
+            match db::auth::session_exists(cookie.user_id, session_id, &pool).await {
+                Ok(true) => {
+                    Auth {
+                        user_id: cookie.user_id,
+                        session_id: cookie.session_id,
+                    }
+                    .unless_suspended(&pool)
+                    .await
+                }
+                Ok(false) => {
+                    id.logout();
+                    Err(ErrorUnauthorized("Session was logged out"))
+                }
+                Err(err) => Err(ErrorInternalServerError(err)),
+            }
+        })
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/auth/token",
+    responses(
+        (status = 200, description = "{\"token\": ...}, send it as `Authorization: Bearer <token>`", body = String),
+        (status = 400, description = "Email, password or two-factor code does not match", body = String),
+        (status = 403, description = "Account suspended", body = String),
+        (status = 429, description = "Too many failed attempts for the account or the IP, see Retry-After", body = String)
+    ),
+    request_body(content = TokenEvent, description = "user email, user password, two-factor code if it's on", content_type = "application/json")
+)]
+pub async fn token(
+    body: web::Json<server::TokenEvent>,
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    ua_parser: web::Data<Arc<UserAgentParser>>,
+) -> HttpResponse {
+    let pl = body.into_inner();
+    if let Some(res) = throttled(&pl.email, &req, pool.as_ref()).await {
+        return res;
+    }
+    let user_id = match db::login::get_user_and_password(pl.email.clone(), pool.as_ref()).await {
+        Ok((user_id, hash)) if verify_password(&pl.password, &hash) => user_id,
+        found => {
+            if found.is_err() {
+                verify_password(&pl.password, dummy_hash());
+            }
+            failed_login(&pl.email, found.ok().map(|f| f.0), &req, pool.as_ref()).await;
+            return HttpResponse::build(StatusCode::BAD_REQUEST)
+                .content_type(ContentType::plaintext())
+                .body("Email or password does not match");
+        }
+    };
+    if let Some(res) = suspended(user_id, pool.as_ref()).await {
+        return res;
+    }
+    match db::mfa::is_enabled(user_id, pool.as_ref()).await {
+        Ok(false) => {}
+        Ok(true) => {
+            let code = pl.code.unwrap_or_default();
+            if !check_code(user_id, code.trim(), pool.as_ref()).await {
+                // a wrong code counts like a wrong password, leaving it out is just asking
+                if !code.trim().is_empty() {
+                    failed_login(&pl.email, Some(user_id), &req, pool.as_ref()).await;
+                }
+                return HttpResponse::build(StatusCode::BAD_REQUEST)
+                    .content_type(ContentType::plaintext())
+                    .body("Two-factor code needed");
+            }
+        }
+        Err(err) => {
+            log::error!("{}", err);
+            return HttpResponse::build(StatusCode::BAD_REQUEST)
+                .content_type(ContentType::plaintext())
+                .body("DB failed to create session");
+        }
+    }
+    // only now, or guessing codes would wipe the failures it piled up
+    passed_login(&pl.email, pool.as_ref()).await;
+    let token = token::generate();
+    let uag = user_agent(&req, &ua_parser);
+    let created = match db::login::create_session(user_id, pool.as_ref(), uag.clone()).await {
+        Ok(session_id) => {
+            alerts::check_device(user_id, session_id, &uag, &req, pool.as_ref()).await;
+            db::auth::create_token(session_id, token::hash(&token), pool.as_ref()).await
+        }
+        Err(err) => Err(err),
+    };
+    match created {
+        Ok(_) => HttpResponse::Ok().json(json!({ "token": token })),
+        Err(err) => {
+            log::error!("{}", err);
+            HttpResponse::build(StatusCode::BAD_REQUEST)
+                .content_type(ContentType::plaintext())
+                .body("DB failed to create session")
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use actix_session::Session;
+use actix_web::{
+    http::{header::ContentType, StatusCode},
+    web, HttpResponse,
+};
+use sqlx::{types::Uuid, PgPool};
+
+use crate::captcha::{CaptchaProvider, CAPTCHA_SECONDS};
+use crate::db;
+
+// the session only holds the challenge id, the answer is in the captcha table
+const SESSION_KEY: &str = "captcha";
+
+fn bad_request(body: &'static str) -> HttpResponse {
+    HttpResponse::build(StatusCode::BAD_REQUEST)
+        .content_type(ContentType::plaintext())
+        .body(body)
+}
+
+// GET /login and GET /signup
+pub async fn get(
+    session: Session,
+    pool: web::Data<PgPool>,
+    provider: web::Data<dyn CaptchaProvider>,
+) -> HttpResponse {
+    let challenge = provider.challenge();
+    if !provider.required() {
+        return HttpResponse::Ok()
+            .content_type(ContentType::plaintext())
+            .body(challenge.body);
+    }
+    let id = match db::captcha::create(challenge.expected, CAPTCHA_SECONDS, pool.as_ref()).await {
+        Ok(id) => id,
+        Err(err) => {
+            log::error!("{}", err);
+            return bad_request("Bad request (database errored, you are unlucky)");
+        }
+    };
+    if session.insert(SESSION_KEY, id.to_string()).is_err() {
+        return bad_request("Could not store the captcha");
+    }
+    HttpResponse::Ok()
+        .content_type(if challenge.json {
+            ContentType::json()
+        } else {
+            ContentType::plaintext()
+        })
+        .body(challenge.body)
+}
+
+// Err is the response to send back. Whatever happens the challenge can't be used again.
+pub async fn verify(
+    provider: &dyn CaptchaProvider,
+    session: &Session,
+    answer: &str,
+    pool: &PgPool,
+) -> Result<(), HttpResponse> {
+    if !provider.required() {
+        return Ok(());
+    }
+    let id = session
+        .remove_as::<String>(SESSION_KEY)
+        .and_then(|id| id.ok())
+        .and_then(|id| Uuid::parse_str(&id).ok())
+        .ok_or_else(|| bad_request("Get a captcha first"))?;
+    match db::captcha::take(id, pool).await {
+        Ok(Some(expected)) if provider.check(&expected, answer) => Ok(()),
+        Ok(Some(_)) => Err(bad_request("You are a bot")),
+        Ok(None) => Err(bad_request("Captcha expired, get a new one")),
+        Err(err) => {
+            log::error!("{}", err);
+            Err(bad_request(
+                "Bad request (database errored, you are unlucky)",
+            ))
+        }
+    }
+}
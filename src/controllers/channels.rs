@@ -1,4 +1,3 @@
-use actix_identity::Identity;
 use actix_web::{
     // http::{header::ContentType, StatusCode},
     web,
@@ -9,6 +8,7 @@ use actix_web::{
 use sqlx::postgres::PgPool;
 use sqlx::types::Uuid;
 
+use super::auth::Auth;
 use crate::db;
 use utoipa;
 
@@ -21,10 +21,10 @@ use utoipa;
 )]
 pub async fn get(
     pool: web::Data<PgPool>,
-    id: Option<Identity>,
+    auth: Option<Auth>,
     path: web::Path<(Uuid,)>,
 ) -> HttpResponse {
-    if id.is_some() {
+    if auth.is_some() {
         return HttpResponse::Ok().finish();
     }
     let pl = path.into_inner();
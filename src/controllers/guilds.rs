@@ -1,9 +1,9 @@
-use actix_identity::Identity;
 use actix_web::{web, HttpResponse};
 
 use sqlx::postgres::PgPool;
 use sqlx::types::Uuid;
 
+use super::auth::Auth;
 use crate::db;
 use utoipa;
 
@@ -16,10 +16,10 @@ use utoipa;
 )]
 pub async fn get(
     pool: web::Data<PgPool>,
-    id: Option<Identity>,
+    auth: Option<Auth>,
     path: web::Path<(Uuid,)>,
 ) -> HttpResponse {
-    if id.is_some() {
+    if auth.is_some() {
         return HttpResponse::Ok().finish();
     }
     let pl = path.into_inner();
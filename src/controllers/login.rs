@@ -1,7 +1,7 @@
 use actix_identity::Identity;
 use actix_session::Session;
 use actix_web::{
-    http::{header::ContentType, StatusCode},
+    http::{header, header::ContentType, StatusCode},
     web, HttpMessage, HttpRequest, HttpResponse,
 };
 
@@ -9,25 +9,121 @@ use argon2::{
     password_hash::{PasswordHash, PasswordVerifier},
     Argon2,
 };
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
 use serde_json::json;
 use sqlx::postgres::PgPool;
 use user_agent_parser::UserAgentParser;
 
+use crate::captcha::CaptchaProvider;
 use crate::db;
-use crate::server;
-use db::signup::UserAgent;
+use crate::{server, throttle, token, totp};
+use db::signup::{create_password, UserAgent};
+use db::suspension::Suspension;
 use utoipa;
 
+use super::alerts;
+use super::auth::Auth;
+use super::captcha;
 use super::extractor::ValidatedForm;
 
+// how long a client has to come back with a two-factor code after the password
+const TICKET_MINUTES: i32 = 5;
+const TICKET_ATTEMPTS: i32 = 5;
+
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+pub fn user_agent(req: &HttpRequest, ua_parser: &UserAgentParser) -> UserAgent {
+    let user_agent = req
+        .headers()
+        .get("user-agent")
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("");
+    let browser = ua_parser.parse_product(user_agent);
+    let os = ua_parser.parse_os(user_agent);
+    let device = ua_parser.parse_device(user_agent);
+    log::debug!(
+        "User Agents\nProduct {:#?}\nOs {:#?}\nDevice {:#?}",
+        browser,
+        os,
+        device
+    );
+    UserAgent {
+        os: Some(format!(
+            "{} {} {}",
+            os.name.unwrap_or_default(),
+            os.major.unwrap_or_default(),
+            os.minor.unwrap_or_default()
+        )),
+        browser: Some(format!(
+            "{} {} {}",
+            browser.name.unwrap_or_default(),
+            browser.major.unwrap_or_default(),
+            browser.minor.unwrap_or_default()
+        )),
+        device: Some(format!(
+            "{} {} {}",
+            device.name.unwrap_or_default(),
+            device.model.unwrap_or_default(),
+            device.brand.unwrap_or_default()
+        )),
+        original: user_agent.to_string(),
+    }
+}
+
+// creates the session row and sets the cookie, the user has proven everything by now
+pub async fn log_in(
+    user_id: i64,
+    req: &HttpRequest,
+    uag: UserAgent,
+    pool: &PgPool,
+) -> sqlx::Result<()> {
+    let session_id = db::login::create_session(user_id, pool, uag.clone()).await?;
+    alerts::check_device(user_id, session_id, &uag, req, pool).await;
+    Identity::login(
+        &req.extensions(),
+        json!({
+            "user_id": user_id,
+            "session_id": session_id.to_string()
+        })
+        .to_string(),
+    )
+    .unwrap();
+    Ok(())
+}
+
+pub async fn create_session(
+    user_id: i64,
+    req: &HttpRequest,
+    uag: UserAgent,
+    pool: &PgPool,
+) -> HttpResponse {
+    match log_in(user_id, req, uag, pool).await {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(err) => {
+            log::error!("{}", err);
+            HttpResponse::build(StatusCode::BAD_REQUEST)
+                .content_type(ContentType::plaintext())
+                .body("DB failed to create session")
+        }
+    }
+}
+
 #[utoipa::path(
     post,
     path = "/login",
     responses(
-        (status = 200, description = "Successful Response", body = String),
-        (status = 400, description = "Password does not match or email doesn't exist or the database failed to create a session", body = String)
+        (status = 200, description = "Successful Response, or {\"mfa\": true, \"ticket\": ...} when a two-factor code is needed at /login/mfa", body = String),
+        (status = 400, description = "Email or password does not match, or the database failed to create a session", body = String),
+        (status = 403, description = "Account suspended, the body says until when and why", body = String),
+        (status = 429, description = "Too many failed attempts for the account or the IP, see Retry-After", body = String)
     ),
     request_body(content = LoginEvent, description = "user email, user password", content_type = "application/json")
 )]
@@ -35,102 +131,288 @@ pub async fn post(
     body: ValidatedForm<server::LoginEvent>,
     pool: web::Data<PgPool>,
     session: Session,
-    id: Option<Identity>,
+    auth: Option<Auth>,
     req: HttpRequest,
     ua_parser: web::Data<Arc<UserAgentParser>>,
+    provider: web::Data<dyn CaptchaProvider>,
 ) -> HttpResponse {
-    if id.is_some() {
+    if auth.is_some() {
         return HttpResponse::Ok().finish();
     }
     let pl = body.decode();
-    log::info!(
-        "GIVEN: {}\n REAL CODE: {}",
-        pl.code,
-        session.get::<String>("captcha").unwrap().unwrap()
-    );
-    if pl.code != session.get::<String>("captcha").unwrap().unwrap() {
-        return HttpResponse::build(StatusCode::BAD_REQUEST)
-            .content_type(ContentType::plaintext())
-            .body("You are a bot");
+    if let Err(res) = captcha::verify(provider.as_ref(), &session, &pl.code, pool.as_ref()).await {
+        return res;
     }
-    session.remove("captcha");
-    let argon2 = Argon2::default();
-    let user_agent = req
-        .headers()
-        .get("user-agent")
-        .unwrap()
-        .to_str()
-        .ok()
-        .unwrap();
-    // println!("USER AGENT {}", user_agent.unwrap());
-    let browser = ua_parser.parse_product(user_agent);
-    let os = ua_parser.parse_os(user_agent);
-    let device = ua_parser.parse_device(user_agent);
-    println!(
-        "User Agents\nProduct {:#?}\nOs {:#?}\nDevice {:#?}",
-        browser, os, device
-    );
-    match db::login::get_user_and_password(pl.email, pool.as_ref()).await {
-        Ok((user_id, password)) => {
-            if argon2
-                .verify_password(
-                    pl.password.as_bytes(),
-                    &PasswordHash::new(&password).unwrap(),
-                )
-                .is_ok()
-            {
-                let uag = UserAgent {
-                    os: Some(format!(
-                        "{} {} {}",
-                        os.name.unwrap(),
-                        os.major.unwrap(),
-                        os.minor.unwrap()
-                    )),
-                    browser: Some(format!(
-                        "{} {} {}",
-                        browser.name.unwrap(),
-                        browser.major.unwrap(),
-                        browser.minor.unwrap()
-                    )),
-                    device: Some(format!(
-                        "{} {} {}",
-                        device.name.unwrap(),
-                        device.model.unwrap(),
-                        device.brand.unwrap()
-                    )),
-                    original: user_agent.to_string(),
-                };
-                match db::login::create_session(user_id, pool.as_ref(), uag).await {
-                    Ok(session_id) => {
-                        Identity::login(
-                            &req.extensions(),
-                            json!({
-                                "user_id": user_id,
-                                "session_id": session_id.to_string()
-                            })
-                            .to_string(),
-                        )
-                        .unwrap();
-                        HttpResponse::Ok().finish()
-                    }
-                    Err(err) => {
-                        println!("{}", err);
-                        HttpResponse::build(StatusCode::BAD_REQUEST)
-                            .content_type(ContentType::plaintext())
-                            .body("DB failed to create session")
-                    }
-                }
-            } else {
+    if let Some(res) = throttled(&pl.email, &req, pool.as_ref()).await {
+        return res;
+    }
+    let user_id = match db::login::get_user_and_password(pl.email.clone(), pool.as_ref()).await {
+        Ok((user_id, password)) if verify_password(&pl.password, &password) => user_id,
+        found => {
+            // as slow as a wrong password, so timing doesn't give the email away
+            if found.is_err() {
+                verify_password(&pl.password, dummy_hash());
+            }
+            failed_login(&pl.email, found.ok().map(|f| f.0), &req, pool.as_ref()).await;
+            return HttpResponse::build(StatusCode::BAD_REQUEST)
+                .content_type(ContentType::plaintext())
+                .body("Email or password does not match");
+        }
+    };
+    if let Some(res) = suspended(user_id, pool.as_ref()).await {
+        return res;
+    }
+    match db::mfa::is_enabled(user_id, pool.as_ref()).await {
+        Ok(false) => {
+            passed_login(&pl.email, pool.as_ref()).await;
+            create_session(user_id, &req, user_agent(&req, &ua_parser), pool.as_ref()).await
+        }
+        // no session until the second factor checks out, and the failures stay until then too
+        Ok(true) => match mfa_ticket(user_id, pool.as_ref()).await {
+            Ok(ticket) => HttpResponse::Ok().json(json!({ "mfa": true, "ticket": ticket })),
+            Err(err) => {
+                log::error!("{}", err);
                 HttpResponse::build(StatusCode::BAD_REQUEST)
                     .content_type(ContentType::plaintext())
-                    .body("Password does not match")
+                    .body("DB failed to create session")
             }
-        }
+        },
         Err(err) => {
-            println!("{}", err);
+            log::error!("{}", err);
             HttpResponse::build(StatusCode::BAD_REQUEST)
                 .content_type(ContentType::plaintext())
-                .body("Email is not in record")
+                .body("DB failed to create session")
         }
     }
 }
+
+// What /login/mfa takes instead of a password, once whatever came first checked out
+pub async fn mfa_ticket(user_id: i64, pool: &PgPool) -> sqlx::Result<String> {
+    let ticket = token::generate();
+    db::mfa::create_ticket(user_id, token::hash(&ticket), TICKET_MINUTES, pool).await?;
+    Ok(ticket)
+}
+
+pub fn client_ip(req: &HttpRequest) -> String {
+    req.connection_info()
+        .realip_remote_addr()
+        .unwrap_or("unknown")
+        .to_string()
+}
+
+// something to verify against when the email doesn't exist
+pub fn dummy_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| create_password("not a real password".to_string()).unwrap())
+}
+
+// A 429 if either the account or the IP is locked out. Unknown emails get locked
+// just the same, so this says nothing about whether the account exists.
+pub async fn throttled(email: &str, req: &HttpRequest, pool: &PgPool) -> Option<HttpResponse> {
+    locked(
+        &[
+            throttle::account_key(email),
+            throttle::ip_key(&client_ip(req)),
+        ],
+        pool,
+    )
+    .await
+}
+
+pub async fn failed_login(email: &str, user_id: Option<i64>, req: &HttpRequest, pool: &PgPool) {
+    record_failure(
+        &[
+            (throttle::account_key(email), throttle::ACCOUNT_FREE),
+            (throttle::ip_key(&client_ip(req)), throttle::IP_FREE),
+        ],
+        user_id,
+        req,
+        "login_locked",
+        pool,
+    )
+    .await
+}
+
+// a 429 as soon as one of the keys is locked
+pub async fn locked(keys: &[String], pool: &PgPool) -> Option<HttpResponse> {
+    for key in keys {
+        match db::throttle::locked_for(key, pool).await {
+            Ok(Some(left)) => {
+                return Some(
+                    HttpResponse::build(StatusCode::TOO_MANY_REQUESTS)
+                        .insert_header((header::RETRY_AFTER, left.ceil().to_string()))
+                        .content_type(ContentType::plaintext())
+                        .body(format!(
+                            "Too many attempts, try again in {} seconds",
+                            left.ceil()
+                        )),
+                )
+            }
+            Ok(None) => {}
+            Err(err) => log::error!("{}", err),
+        }
+    }
+    None
+}
+
+// Counts a failure on each key, locking the ones past their free attempts.
+// action is what the audit log calls the lock.
+pub async fn record_failure(
+    keys: &[(String, i32)],
+    user_id: Option<i64>,
+    req: &HttpRequest,
+    action: &str,
+    pool: &PgPool,
+) {
+    let ip = client_ip(req);
+    for (key, free) in keys {
+        let failures = match db::throttle::record_failure(key, throttle::FORGET_MINUTES, pool).await
+        {
+            Ok(failures) => failures,
+            Err(err) => {
+                log::error!("{}", err);
+                continue;
+            }
+        };
+        let Some(seconds) = throttle::lock_seconds(failures, *free) else {
+            continue;
+        };
+        if let Err(err) = db::throttle::lock(key, seconds, pool).await {
+            log::error!("{}", err);
+        }
+        let detail = format!(
+            "{} locked for {}s after {} failures",
+            key, seconds, failures
+        );
+        log::warn!("{}", detail);
+        if let Err(err) = db::audit::record(user_id, action, Some(ip.clone()), detail, pool).await {
+            log::error!("{}", err);
+        }
+    }
+}
+
+// the account's slate is wiped, the IP's isn't, one good password shouldn't excuse a spray
+pub async fn passed_login(email: &str, pool: &PgPool) {
+    if let Err(err) = db::throttle::clear(&throttle::account_key(email), pool).await {
+        log::error!("{}", err);
+    }
+}
+
+pub fn describe(suspension: &Suspension) -> String {
+    let mut msg = match suspension.until {
+        Some(until) => format!(
+            "Account suspended until {} UTC",
+            until.format("%Y-%m-%d %H:%M")
+        ),
+        None => "Account suspended".to_string(),
+    };
+    if let Some(reason) = &suspension.reason {
+        msg.push_str(": ");
+        msg.push_str(reason);
+    }
+    msg
+}
+
+// A 403 when staff suspended the account. Only asked once the password (or whatever
+// stands in for it) checked out, so it doesn't tell strangers anything.
+pub async fn suspended(user_id: i64, pool: &PgPool) -> Option<HttpResponse> {
+    match db::suspension::active(user_id, pool).await {
+        Ok(None) => None,
+        Ok(Some(suspension)) => Some(
+            HttpResponse::build(StatusCode::FORBIDDEN)
+                .content_type(ContentType::plaintext())
+                .body(describe(&suspension)),
+        ),
+        Err(err) => {
+            log::error!("{}", err);
+            Some(
+                HttpResponse::build(StatusCode::BAD_REQUEST)
+                    .content_type(ContentType::plaintext())
+                    .body("Bad request (database errored, you are unlucky)"),
+            )
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/login/mfa",
+    responses(
+        (status = 200, description = "Successful Response", body = String),
+        (status = 400, description = "Ticket expired or used up, or the code is wrong", body = String),
+        (status = 429, description = "Too many failed attempts for the account or the IP, see Retry-After", body = String)
+    ),
+    request_body(content = MfaLoginEvent, description = "ticket from /login, authenticator code or recovery code", content_type = "application/json")
+)]
+pub async fn mfa(
+    body: web::Json<server::MfaLoginEvent>,
+    pool: web::Data<PgPool>,
+    auth: Option<Auth>,
+    req: HttpRequest,
+    ua_parser: web::Data<Arc<UserAgentParser>>,
+) -> HttpResponse {
+    if auth.is_some() {
+        return HttpResponse::Ok().finish();
+    }
+    let pl = body.into_inner();
+    let ticket_hash = token::hash(&pl.ticket);
+    let user_id =
+        match db::mfa::ticket_attempt(ticket_hash.clone(), TICKET_ATTEMPTS, pool.as_ref()).await {
+            Ok(Some(user_id)) => user_id,
+            Ok(None) => {
+                return HttpResponse::build(StatusCode::BAD_REQUEST)
+                    .content_type(ContentType::plaintext())
+                    .body("Ticket expired, log in again")
+            }
+            Err(err) => {
+                log::error!("{}", err);
+                return HttpResponse::build(StatusCode::BAD_REQUEST)
+                    .content_type(ContentType::plaintext())
+                    .body("Bad request (database errored, you are unlucky)");
+            }
+        };
+    // the ticket's own tries run out, but new tickets are a password away,
+    // so wrong codes count against the account like wrong passwords
+    let email = match db::alerts::get_email(user_id, pool.as_ref()).await {
+        Ok(email) => email,
+        Err(err) => {
+            log::error!("{}", err);
+            return HttpResponse::build(StatusCode::BAD_REQUEST)
+                .content_type(ContentType::plaintext())
+                .body("Bad request (database errored, you are unlucky)");
+        }
+    };
+    if let Some(res) = throttled(&email, &req, pool.as_ref()).await {
+        return res;
+    }
+    if !check_code(user_id, pl.code.trim(), pool.as_ref()).await {
+        failed_login(&email, Some(user_id), &req, pool.as_ref()).await;
+        return HttpResponse::build(StatusCode::BAD_REQUEST)
+            .content_type(ContentType::plaintext())
+            .body("Wrong code");
+    }
+    passed_login(&email, pool.as_ref()).await;
+    let _ = db::mfa::delete_ticket(ticket_hash, pool.as_ref()).await;
+    // could have been suspended while the ticket was out
+    if let Some(res) = suspended(user_id, pool.as_ref()).await {
+        return res;
+    }
+    create_session(user_id, &req, user_agent(&req, &ua_parser), pool.as_ref()).await
+}
+
+// an authenticator code, or failing that one of the recovery codes
+pub async fn check_code(user_id: i64, code: &str, pool: &PgPool) -> bool {
+    let Ok(Some(t)) = db::mfa::get_totp(user_id, pool).await else {
+        return false;
+    };
+    if !t.enabled {
+        return false;
+    }
+    if let Some(step) = totp::check(&t.secret, code, t.last_step) {
+        return matches!(db::mfa::use_step(user_id, step, pool).await, Ok(true));
+    }
+    matches!(
+        db::mfa::use_recovery_code(user_id, token::hash(&code.to_lowercase()), pool).await,
+        Ok(true)
+    )
+}
@@ -5,28 +5,29 @@ use actix_web::{
 };
 use sqlx::PgPool;
 
+use super::auth::Auth;
 use crate::db;
-use crate::server::AuthCookie;
 
-pub async fn delete(id: Option<Identity>, pool: web::Data<PgPool>) -> HttpResponse {
-    if let Some(session_id) = id {
-        let session_cookie: AuthCookie = serde_json::from_str(&session_id.id().unwrap()).unwrap();
+// ends the session behind the cookie or the bearer token, which revokes the token too
+pub async fn delete(
+    auth: Option<Auth>,
+    id: Option<Identity>,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    if let Some(id) = id {
+        id.logout();
+    }
+    if let Some(auth) = auth {
         match db::logout::delete_session(
-            sqlx::types::Uuid::parse_str(&session_cookie.session_id).unwrap(),
+            sqlx::types::Uuid::parse_str(&auth.session_id).unwrap(),
             pool.as_ref(),
         )
         .await
         {
-            Ok(_) => {
-                session_id.logout();
-                HttpResponse::Ok().finish()
-            }
-            Err(_) => {
-                session_id.logout();
-                HttpResponse::build(StatusCode::BAD_REQUEST)
-                    .content_type(ContentType::plaintext())
-                    .body("Bad request")
-            }
+            Ok(_) => HttpResponse::Ok().finish(),
+            Err(_) => HttpResponse::build(StatusCode::BAD_REQUEST)
+                .content_type(ContentType::plaintext())
+                .body("Bad request"),
         }
     } else {
         HttpResponse::Ok().finish()
@@ -0,0 +1,297 @@
+use actix_identity::Identity;
+use actix_web::{
+    http::{header, header::ContentType, StatusCode},
+    web, HttpResponse,
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+use super::auth::Auth;
+use super::login::{check_code, verify_password};
+use crate::config::Config;
+use crate::controllers::ws::CLOSE_SESSION_REVOKED;
+use crate::db::{self, models::UserClient};
+use crate::mailer::{templates, MailQueue};
+use crate::messages::UserFetchType;
+use crate::{server, token};
+use utoipa;
+
+// days between username changes
+const USERNAME_COOLDOWN_DAYS: i32 = 7;
+// how long the link to confirm a new email works
+const EMAIL_CHANGE_MINUTES: i32 = 60;
+
+fn bad_request(body: &'static str) -> HttpResponse {
+    HttpResponse::build(StatusCode::BAD_REQUEST)
+        .content_type(ContentType::plaintext())
+        .body(body)
+}
+
+async fn collect(auth: &Auth, pool: &PgPool) -> sqlx::Result<Value> {
+    let user = db::ws_session::get_user_by_session_id(auth.session_id.clone(), pool).await?;
+    Ok(json!({
+        "profile": UserClient::from(user),
+        "sessions": db::sessions::list_sessions(auth.user_id, pool).await?,
+        "memberships": db::account::memberships(auth.user_id, pool).await?,
+        "dm_channels": db::account::dm_channels(auth.user_id, pool).await?,
+        "messages": db::account::messages(auth.user_id, pool).await?,
+        "applications": db::applications::list_applications(auth.user_id, pool).await?,
+    }))
+}
+
+// Everything we have on the user as one json file
+pub async fn export(auth: Auth, pool: web::Data<PgPool>) -> HttpResponse {
+    match collect(&auth, pool.as_ref()).await {
+        Ok(data) => HttpResponse::Ok()
+            .insert_header((
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"flettex-export-{}.json\"",
+                    auth.user_id
+                ),
+            ))
+            .json(data),
+        Err(err) => {
+            log::error!("{}", err);
+            bad_request("Bad request (database errored, you are unlucky)")
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/me",
+    responses(
+        (status = 200, description = "Account deleted, every session was logged out", body = String),
+        (status = 400, description = "Password or two-factor code does not match", body = String)
+    ),
+    request_body(content = AccountDeleteEvent, description = "user password, two-factor code if it's on", content_type = "application/json")
+)]
+pub async fn delete(
+    body: web::Json<server::AccountDeleteEvent>,
+    auth: Auth,
+    id: Option<Identity>,
+    pool: web::Data<PgPool>,
+    srv: web::Data<server::Chat>,
+) -> HttpResponse {
+    let pl = body.into_inner();
+    match db::login::get_password(auth.user_id, pool.as_ref()).await {
+        Ok(hash) if verify_password(&pl.password, &hash) => {}
+        _ => return bad_request("Password does not match"),
+    }
+    match db::mfa::is_enabled(auth.user_id, pool.as_ref()).await {
+        Ok(false) => {}
+        Ok(true) => {
+            let code = pl.code.unwrap_or_default();
+            if !check_code(auth.user_id, code.trim(), pool.as_ref()).await {
+                return bad_request("Two-factor code needed");
+            }
+        }
+        Err(_) => return bad_request("Bad request (database errored, you are unlucky)"),
+    }
+    // their bots go with them, and so do the bots' sockets
+    let bots = match db::applications::list_applications(auth.user_id, pool.as_ref()).await {
+        Ok(apps) => apps.into_iter().map(|app| app.bot_id).collect::<Vec<i64>>(),
+        Err(_) => return bad_request("Bad request (database errored, you are unlucky)"),
+    };
+    let deleted = match db::account::delete_account(auth.user_id, pool.as_ref()).await {
+        Ok(deleted) => deleted,
+        Err(err) => {
+            log::error!("{}", err);
+            return bad_request("DB failed to delete the account");
+        }
+    };
+    for guild_id in deleted.guilds {
+        srv.delete_guild(guild_id).await;
+    }
+    for (guild_id, user_id) in deleted.left {
+        srv.remove_member(guild_id.to_string(), user_id as usize)
+            .await;
+    }
+    srv.disconnect_user(
+        auth.user_id as usize,
+        None,
+        CLOSE_SESSION_REVOKED,
+        "Account deleted",
+    )
+    .await;
+    for bot_id in bots {
+        srv.disconnect_user(
+            bot_id as usize,
+            None,
+            CLOSE_SESSION_REVOKED,
+            "Owner deleted their account",
+        )
+        .await;
+    }
+    if let Some(id) = id {
+        id.logout();
+    }
+    HttpResponse::Ok().finish()
+}
+
+// Shared by PATCH /me and the gateway's UserUpdate. Err is what to tell the user.
+pub async fn edit(
+    user_id: i64,
+    update: &server::ProfileUpdateEvent,
+    pool: &PgPool,
+) -> Result<UserFetchType, &'static str> {
+    if let Some(description) = &update.description {
+        if description.chars().count() > 255 {
+            return Err("Description is too long");
+        }
+    }
+    if let Some(profile) = &update.profile {
+        if !profile.is_empty()
+            && (profile.len() > 512
+                || !(profile.starts_with("https://") || profile.starts_with("http://")))
+        {
+            return Err("Avatar must be an http(s) url");
+        }
+    }
+    let username = update.username.as_ref().map(|u| u.trim().to_string());
+    if let Some(username) = &username {
+        if username.is_empty() || username.chars().count() > 64 {
+            return Err("Username must be 1 to 64 characters");
+        }
+        if db::account::reserved_username(username) {
+            return Err("Username taken");
+        }
+    }
+    let current = db::ws_session::get_user_by_id(user_id, pool)
+        .await
+        .map_err(|_| "Bad request (database errored, you are unlucky)")?;
+    if let Some(username) = username.filter(|u| *u != current.username) {
+        match db::profile::update_username(user_id, username, USERNAME_COOLDOWN_DAYS, pool).await {
+            Ok(true) => {}
+            Ok(false) => return Err("Username can only be changed once a week"),
+            Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+                return Err("Username taken")
+            }
+            Err(_) => return Err("Bad request (database errored, you are unlucky)"),
+        }
+    }
+    let empty_is_none = |s: &String| Some(s.clone()).filter(|s| !s.is_empty());
+    if let Some(description) = &update.description {
+        db::profile::update_description(user_id, empty_is_none(description), pool)
+            .await
+            .map_err(|_| "Bad request (database errored, you are unlucky)")?;
+    }
+    if let Some(profile) = &update.profile {
+        db::profile::update_avatar(user_id, empty_is_none(profile), pool)
+            .await
+            .map_err(|_| "Bad request (database errored, you are unlucky)")?;
+    }
+    db::ws_session::get_user_by_id(user_id, pool)
+        .await
+        .map_err(|_| "Bad request (database errored, you are unlucky)")
+}
+
+#[utoipa::path(
+    patch,
+    path = "/me",
+    responses(
+        (status = 200, description = "The updated user", body = String),
+        (status = 400, description = "Username taken or changed too recently, description too long or a bad avatar url", body = String)
+    ),
+    request_body(content = ProfileUpdateEvent, description = "any of username, description, profile", content_type = "application/json")
+)]
+pub async fn patch(
+    body: web::Json<server::ProfileUpdateEvent>,
+    auth: Auth,
+    pool: web::Data<PgPool>,
+    srv: web::Data<server::Chat>,
+) -> HttpResponse {
+    match edit(auth.user_id, &body.into_inner(), pool.as_ref()).await {
+        Ok(user) => {
+            srv.update_profile(user.clone(), pool.as_ref()).await;
+            HttpResponse::Ok().json(user)
+        }
+        Err(msg) => HttpResponse::build(StatusCode::BAD_REQUEST)
+            .content_type(ContentType::plaintext())
+            .body(msg),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/me/email",
+    responses(
+        (status = 200, description = "A confirmation link was sent to the new address", body = String),
+        (status = 400, description = "Password does not match or the address is taken", body = String)
+    ),
+    request_body(content = EmailChangeEvent, description = "new email, user password", content_type = "application/json")
+)]
+pub async fn change_email(
+    body: web::Json<server::EmailChangeEvent>,
+    auth: Auth,
+    pool: web::Data<PgPool>,
+    mail: web::Data<MailQueue>,
+    config: web::Data<Config>,
+) -> HttpResponse {
+    let pl = body.into_inner();
+    let email = pl.email.trim().to_string();
+    if !email.contains('@') || email.len() > 191 {
+        return bad_request("Not an email address");
+    }
+    if db::account::reserved_email(&email) {
+        return bad_request("Email taken");
+    }
+    match db::login::get_password(auth.user_id, pool.as_ref()).await {
+        Ok(hash) if verify_password(&pl.password, &hash) => {}
+        _ => return bad_request("Password does not match"),
+    }
+    if db::password::get_user_id_by_email(email.clone(), pool.as_ref())
+        .await
+        .is_ok()
+    {
+        return bad_request("Email taken");
+    }
+    let confirm = token::generate();
+    if let Err(err) = db::profile::create_email_change(
+        auth.user_id,
+        email.clone(),
+        token::hash(&confirm),
+        EMAIL_CHANGE_MINUTES,
+        pool.as_ref(),
+    )
+    .await
+    {
+        log::error!("{}", err);
+        return bad_request("Bad request (database errored, you are unlucky)");
+    }
+    let link = format!("{}/me/email/confirm/{}", config.public_url(), confirm);
+    mail.send(templates::email_change(&email, &link, EMAIL_CHANGE_MINUTES));
+    HttpResponse::Ok().finish()
+}
+
+// The link from the email. The old address gets told once it's done.
+pub async fn confirm_email(
+    path: web::Path<String>,
+    pool: web::Data<PgPool>,
+    mail: web::Data<MailQueue>,
+) -> HttpResponse {
+    match db::profile::confirm_email_change(token::hash(&path.into_inner()), pool.as_ref()).await {
+        Ok(Some((_, old, new))) => {
+            mail.send(templates::notification(
+                &old,
+                "Your flettex email was changed",
+                &format!(
+                    "Your account now uses {}. If that wasn't you, contact us right away.",
+                    new
+                ),
+            ));
+            HttpResponse::Ok()
+                .content_type(ContentType::plaintext())
+                .body("Email changed, you can close this page")
+        }
+        Ok(None) => bad_request("Invalid or expired link"),
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => {
+            bad_request("That email was taken in the meantime")
+        }
+        Err(err) => {
+            log::error!("{}", err);
+            bad_request("Bad request (database errored, you are unlucky)")
+        }
+    }
+}
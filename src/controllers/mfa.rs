@@ -0,0 +1,134 @@
+use actix_web::{
+    http::{header::ContentType, StatusCode},
+    web, HttpResponse,
+};
+use serde_json::json;
+use sqlx::PgPool;
+
+use super::auth::Auth;
+use super::login::{check_code, verify_password};
+use crate::db;
+use crate::{server, token, totp};
+use utoipa;
+
+fn db_error(err: sqlx::Error) -> HttpResponse {
+    log::error!("{}", err);
+    HttpResponse::build(StatusCode::BAD_REQUEST)
+        .content_type(ContentType::plaintext())
+        .body("Bad request (database errored, you are unlucky)")
+}
+
+#[utoipa::path(
+    post,
+    path = "/mfa/totp/enroll",
+    responses(
+        (status = 200, description = "{\"secret\": ..., \"uri\": ...}, not active until confirmed", body = String),
+        (status = 400, description = "Two-factor is already on", body = String)
+    )
+)]
+pub async fn enroll(auth: Auth, pool: web::Data<PgPool>) -> HttpResponse {
+    let user = match db::ws_session::get_user_by_session_id(auth.session_id, pool.as_ref()).await {
+        Ok(user) => user,
+        Err(err) => return db_error(err),
+    };
+    match db::mfa::is_enabled(user.id, pool.as_ref()).await {
+        Ok(false) => {}
+        Ok(true) => {
+            return HttpResponse::build(StatusCode::BAD_REQUEST)
+                .content_type(ContentType::plaintext())
+                .body("Two-factor is already on, disable it first")
+        }
+        Err(err) => return db_error(err),
+    }
+    let secret = totp::new_secret();
+    if let Err(err) = db::mfa::set_pending_secret(user.id, secret.clone(), pool.as_ref()).await {
+        return db_error(err);
+    }
+    HttpResponse::Ok().json(json!({
+        "uri": totp::uri(&secret, &user.email),
+        "secret": secret,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/mfa/totp/confirm",
+    responses(
+        (status = 200, description = "Two-factor is on, returns the recovery codes. They are never shown again", body = String),
+        (status = 400, description = "Wrong code or nothing to confirm", body = String)
+    ),
+    request_body(content = TotpConfirmEvent, description = "code from the authenticator app", content_type = "application/json")
+)]
+pub async fn confirm(
+    body: web::Json<server::TotpConfirmEvent>,
+    auth: Auth,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let user_id = auth.user_id;
+    let pending = match db::mfa::get_totp(user_id, pool.as_ref()).await {
+        Ok(Some(t)) if !t.enabled => t,
+        Ok(_) => {
+            return HttpResponse::build(StatusCode::BAD_REQUEST)
+                .content_type(ContentType::plaintext())
+                .body("Nothing to confirm, enroll first")
+        }
+        Err(err) => return db_error(err),
+    };
+    let step = match totp::check(&pending.secret, body.code.trim(), pending.last_step) {
+        Some(step) => step,
+        None => {
+            return HttpResponse::build(StatusCode::BAD_REQUEST)
+                .content_type(ContentType::plaintext())
+                .body("Wrong code")
+        }
+    };
+    let _ = db::mfa::use_step(user_id, step, pool.as_ref()).await;
+    let codes = totp::recovery_codes();
+    match db::mfa::enable(
+        user_id,
+        codes.iter().map(|c| token::hash(c)).collect(),
+        pool.as_ref(),
+    )
+    .await
+    {
+        Ok(_) => HttpResponse::Ok().json(json!({ "recovery_codes": codes })),
+        Err(err) => db_error(err),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/mfa/totp/disable",
+    responses(
+        (status = 200, description = "Two-factor is off and the recovery codes are gone", body = String),
+        (status = 400, description = "Password or code does not match", body = String)
+    ),
+    request_body(content = TotpDisableEvent, description = "current password and a code", content_type = "application/json")
+)]
+pub async fn disable(
+    body: web::Json<server::TotpDisableEvent>,
+    auth: Auth,
+    pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let user_id = auth.user_id;
+    let pl = body.into_inner();
+    match db::login::get_password(user_id, pool.as_ref()).await {
+        Ok(hash) if verify_password(&pl.password, &hash) => {}
+        Ok(_) => {
+            return HttpResponse::build(StatusCode::BAD_REQUEST)
+                .content_type(ContentType::plaintext())
+                .body("Password does not match")
+        }
+        Err(err) => return db_error(err),
+    }
+    // a stolen cookie plus a guessed password shouldn't be enough either
+    if !check_code(user_id, pl.code.trim(), pool.as_ref()).await {
+        return HttpResponse::build(StatusCode::BAD_REQUEST)
+            .content_type(ContentType::plaintext())
+            .body("Wrong code");
+    }
+    match db::mfa::disable(user_id, pool.as_ref()).await {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(err) => db_error(err),
+    }
+}
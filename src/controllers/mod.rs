@@ -1,11 +1,12 @@
-use actix_session::Session;
 use actix_web::{http::header::ContentType, services, web, HttpResponse};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::{SwaggerUi, Url};
-// use std::collections::HashMap;
-use captcha_rs::CaptchaBuilder;
 
 pub mod admin;
+pub mod alerts;
+pub mod applications;
+pub mod auth;
+pub mod captcha;
 pub mod channels;
 pub mod count;
 pub mod default;
@@ -15,15 +16,23 @@ pub mod guilds;
 pub mod index;
 pub mod login;
 pub mod logout;
+pub mod me;
+pub mod mfa;
+pub mod oidc;
+pub mod password;
 pub mod samesite;
+pub mod sessions;
 pub mod signup;
-pub mod sqlx;
 pub mod verify;
+pub mod webauthn;
 pub mod ws;
-// use self::admin::format_html;
 use crate::html;
-use crate::server::{ClientEvent, LoginEvent, SignUpEvent};
-use crate::IS_DEV;
+use crate::server::{
+    AccountDeleteEvent, AdminSuspendEvent, AdminUserUpdateEvent, ApplicationCreateEvent,
+    ClientEvent, EmailChangeEvent, ForgotPasswordEvent, LoginEvent, MfaLoginEvent,
+    ProfileUpdateEvent, ResetPasswordEvent, SignUpEvent, TokenEvent, TotpConfirmEvent,
+    TotpDisableEvent,
+};
 
 macro_rules! view {
     ( $path: expr, $content: expr ) => {
@@ -33,39 +42,73 @@ macro_rules! view {
                 .body($content)
         }))
     };
-    ( $path: expr, $content: expr, $useless: expr) => {
-        web::resource($path).route(web::get().to(|session: Session| async move {
-            let captcha = CaptchaBuilder::new()
-                .length(5)
-                .width(130)
-                .height(50)
-                .dark_mode(false)
-                .complexity(5) // min: 1, max: 10
-                .build();
-            session.insert("captcha", captcha.text).unwrap();
-            let replacements: HashMap<&str, String> =
-                HashMap::from_iter([("captcha", captcha.to_base64())]);
-            HttpResponse::Ok()
-                .content_type(ContentType::html())
-                .body(format_html($content, replacements))
-        }))
-    };
 }
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     #[derive(OpenApi)]
     #[openapi(
-        paths(index::post, login::post, signup::post),
-        components(schemas(LoginEvent, ClientEvent, SignUpEvent))
+        paths(
+            index::post,
+            login::post,
+            login::mfa,
+            auth::token,
+            signup::post,
+            password::forgot,
+            password::reset,
+            mfa::enroll,
+            mfa::confirm,
+            mfa::disable,
+            applications::create,
+            me::delete,
+            me::patch,
+            me::change_email,
+            admin::update_user,
+            admin::suspend
+        ),
+        components(schemas(
+            LoginEvent,
+            ClientEvent,
+            SignUpEvent,
+            ForgotPasswordEvent,
+            ResetPasswordEvent,
+            MfaLoginEvent,
+            TotpConfirmEvent,
+            TotpDisableEvent,
+            TokenEvent,
+            ApplicationCreateEvent,
+            AccountDeleteEvent,
+            ProfileUpdateEvent,
+            EmailChangeEvent,
+            AdminUserUpdateEvent,
+            AdminSuspendEvent
+        ))
     )]
     struct ApiDoc;
 
-    // dev only
+    // staff only, see admin::Staff
 
-    if IS_DEV {
-        cfg.service(web::resource("/admin").route(web::get().to(admin::get)))
-            .service(web::resource("/sqlx").route(web::post().to(sqlx::post)));
-    }
+    cfg.service(
+        web::scope("/admin/api")
+            .service(web::resource("/users").route(web::get().to(admin::users)))
+            .service(
+                web::resource("/users/{id}")
+                    .route(web::get().to(admin::user))
+                    .route(web::patch().to(admin::update_user)),
+            )
+            .service(web::resource("/users/{id}/sessions").route(web::delete().to(admin::revoke_sessions)))
+            .service(
+                web::resource("/users/{id}/suspension")
+                    .route(web::post().to(admin::suspend))
+                    .route(web::delete().to(admin::unsuspend)),
+            )
+            .service(web::resource("/sessions").route(web::get().to(admin::sessions)))
+            .service(web::resource("/sessions/{id}").route(web::delete().to(admin::delete_session)))
+            .service(web::resource("/guilds").route(web::get().to(admin::guilds)))
+            .service(web::resource("/channels").route(web::get().to(admin::channels)))
+            .service(web::resource("/channels/{id}").route(web::delete().to(admin::delete_channel)))
+            .service(web::resource("/messages").route(web::get().to(admin::messages)))
+            .service(web::resource("/messages/{id}").route(web::delete().to(admin::delete_message))),
+    );
 
     // views
 
@@ -83,40 +126,58 @@ pub fn config(cfg: &mut web::ServiceConfig) {
     cfg.service(web::resource("/health").route(web::get().to(HttpResponse::Ok)))
         .service(
             web::resource("/signup")
-                .route(web::get().to(|session: Session| async move {
-                    let captcha = CaptchaBuilder::new()
-                        .length(5)
-                        .width(130)
-                        .height(50)
-                        .dark_mode(false)
-                        .complexity(5) // min: 1, max: 10
-                        .build();
-                    session.insert("captcha", captcha.text.clone()).unwrap();
-                    HttpResponse::Ok()
-                        .content_type(ContentType::plaintext())
-                        .body(captcha.to_base64())
-                }))
+                .route(web::get().to(captcha::get))
                 .route(web::post().to(signup::post)),
         )
         .service(
             web::resource("/login")
-                .route(web::get().to(|session: Session| async move {
-                    let captcha = CaptchaBuilder::new()
-                        .length(5)
-                        .width(130)
-                        .height(50)
-                        .dark_mode(false)
-                        .complexity(5) // min: 1, max: 10
-                        .build();
-                    session.insert("captcha", captcha.text.clone()).unwrap();
-                    HttpResponse::Ok()
-                        .content_type(ContentType::plaintext())
-                        .body(captcha.to_base64())
-                }))
+                .route(web::get().to(captcha::get))
                 .route(web::post().to(login::post)),
         )
+        .service(web::resource("/auth/token").route(web::post().to(auth::token)))
+        .service(
+            web::resource("/login/alerts/{token}")
+                .route(web::get().to(alerts::confirm_not_me))
+                .route(web::post().to(alerts::not_me)),
+        )
+        .service(web::resource("/login/mfa").route(web::post().to(login::mfa)))
+        .service(web::resource("/mfa/totp/enroll").route(web::post().to(mfa::enroll)))
+        .service(web::resource("/mfa/totp/confirm").route(web::post().to(mfa::confirm)))
+        .service(web::resource("/mfa/totp/disable").route(web::post().to(mfa::disable)))
+        .service(web::resource("/webauthn/register/start").route(web::post().to(webauthn::register_start)))
+        .service(web::resource("/webauthn/register/finish").route(web::post().to(webauthn::register_finish)))
+        .service(web::resource("/webauthn/login/start").route(web::post().to(webauthn::login_start)))
+        .service(web::resource("/webauthn/login/finish").route(web::post().to(webauthn::login_finish)))
+        .service(web::resource("/webauthn/credentials").route(web::get().to(webauthn::list)))
+        .service(web::resource("/webauthn/credentials/{id}").route(web::delete().to(webauthn::delete)))
+        .service(
+            web::resource("/applications")
+                .route(web::get().to(applications::list))
+                .route(web::post().to(applications::create)),
+        )
+        .service(web::resource("/applications/{id}").route(web::delete().to(applications::delete)))
+        .service(web::resource("/applications/{id}/token").route(web::post().to(applications::reset_token)))
+        .service(web::resource("/applications/{id}/guilds/{guild_id}").route(web::put().to(applications::add_to_guild)))
+        .service(web::resource("/oidc").route(web::get().to(oidc::list)))
+        .service(web::resource("/oidc/{provider}/login").route(web::get().to(oidc::login)))
+        .service(web::resource("/oidc/{provider}/callback").route(web::get().to(oidc::callback)))
+        .service(
+            web::resource("/me")
+                .route(web::patch().to(me::patch))
+                .route(web::delete().to(me::delete)),
+        )
+        .service(web::resource("/me/email").route(web::post().to(me::change_email)))
+        .service(web::resource("/me/email/confirm/{token}").route(web::get().to(me::confirm_email)))
+        .service(web::resource("/me/export").route(web::get().to(me::export)))
+        .service(web::resource("/sessions").route(web::get().to(sessions::list)))
+        .service(web::resource("/sessions/others").route(web::delete().to(sessions::delete_others)))
+        .service(web::resource("/sessions/{id}").route(web::delete().to(sessions::delete)))
         .service(web::resource("/logout").route(web::delete().to(logout::delete)))
+        .service(web::resource("/password/forgot").route(web::post().to(password::forgot)))
+        .service(web::resource("/password/reset").route(web::post().to(password::reset)))
         .service(web::resource("verify").route(web::post().to(verify::post)))
+        .service(web::resource("/verify/resend").route(web::post().to(verify::resend)))
+        .service(web::resource("/verify/link/{token}").route(web::get().to(verify::link)))
         .service(web::resource("/count").route(web::get().to(count::get)))
         .service(web::resource("/samesite").route(web::get().to(samesite::get)))
         .service(web::resource("/ws").route(web::get().to(ws::get)))
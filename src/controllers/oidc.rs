@@ -0,0 +1,296 @@
+use actix_session::Session;
+use actix_web::{
+    http::{
+        header::{self, ContentType},
+        StatusCode,
+    },
+    web, HttpRequest, HttpResponse,
+};
+use openidconnect::core::{CoreAuthenticationFlow, CoreIdTokenClaims};
+use openidconnect::reqwest::async_http_client;
+use openidconnect::{
+    AuthorizationCode, CsrfToken, Nonce, PkceCodeChallenge, PkceCodeVerifier, Scope, TokenResponse,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use user_agent_parser::UserAgentParser;
+
+use super::auth::Auth;
+use super::login::{log_in, mfa_ticket, suspended, user_agent};
+use super::verify::{mail_code, new_code, CODE_MINUTES};
+use crate::{config::Config, db, mailer::MailQueue, oidc::Providers, token};
+
+const PENDING: &str = "oidc";
+
+// what has to survive the trip to the provider and back
+#[derive(Serialize, Deserialize)]
+struct Pending {
+    provider: String,
+    csrf: String,
+    nonce: String,
+    pkce_verifier: String,
+}
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+fn bad_request(body: String) -> HttpResponse {
+    HttpResponse::build(StatusCode::BAD_REQUEST)
+        .content_type(ContentType::plaintext())
+        .body(body)
+}
+
+fn redirect(to: &str) -> HttpResponse {
+    HttpResponse::Found()
+        .insert_header((header::LOCATION, to))
+        .finish()
+}
+
+pub async fn list(providers: web::Data<Providers>) -> HttpResponse {
+    HttpResponse::Ok().json(providers.names())
+}
+
+// sends the browser off to the provider
+pub async fn login(
+    path: web::Path<String>,
+    session: Session,
+    providers: web::Data<Providers>,
+) -> HttpResponse {
+    let provider = path.into_inner();
+    let Some(client) = providers.client(&provider).await else {
+        return bad_request(format!("Unknown provider {}", provider));
+    };
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+    let (url, csrf, nonce) = client
+        .authorize_url(
+            CoreAuthenticationFlow::AuthorizationCode,
+            CsrfToken::new_random,
+            Nonce::new_random,
+        )
+        .add_scope(Scope::new("email".to_string()))
+        .add_scope(Scope::new("profile".to_string()))
+        .set_pkce_challenge(pkce_challenge)
+        .url();
+    session
+        .insert(
+            PENDING,
+            Pending {
+                provider,
+                csrf: csrf.secret().clone(),
+                nonce: nonce.secret().clone(),
+                pkce_verifier: pkce_verifier.secret().clone(),
+            },
+        )
+        .unwrap();
+    redirect(url.as_str())
+}
+
+// The provider sends the browser back here. The subject is looked up in user_identity,
+// then linked to whoever is logged in, or to the account with the same (verified) email,
+// or to a brand new account. Accounts with two-factor on are sent to the frontend's
+// /login/mfa with a ticket (in the fragment, so it stays out of logs) instead of a session.
+#[allow(clippy::too_many_arguments)]
+pub async fn callback(
+    path: web::Path<String>,
+    query: web::Query<CallbackQuery>,
+    auth: Option<Auth>,
+    session: Session,
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    providers: web::Data<Providers>,
+    mail: web::Data<MailQueue>,
+    ua_parser: web::Data<Arc<UserAgentParser>>,
+    config: web::Data<Config>,
+) -> HttpResponse {
+    let provider = path.into_inner();
+    let query = query.into_inner();
+    let Ok(Some(pending)) = session.remove_as::<Pending>(PENDING).transpose() else {
+        return bad_request("No login in progress".to_string());
+    };
+    if let Some(err) = query.error {
+        return bad_request(format!("{} said no: {}", provider, err));
+    }
+    if pending.provider != provider || query.state.as_deref() != Some(pending.csrf.as_str()) {
+        return bad_request("State does not match, try again".to_string());
+    }
+    let Some(client) = providers.client(&provider).await else {
+        return bad_request(format!("Unknown provider {}", provider));
+    };
+    let response = match client
+        .exchange_code(AuthorizationCode::new(query.code.unwrap_or_default()))
+        .set_pkce_verifier(PkceCodeVerifier::new(pending.pkce_verifier))
+        .request_async(async_http_client)
+        .await
+    {
+        Ok(response) => response,
+        Err(err) => {
+            log::info!("OIDC code exchange with {} failed: {}", provider, err);
+            return bad_request("Could not log in with the provider".to_string());
+        }
+    };
+    // signature against the provider's JWKS, issuer, audience, expiry and our nonce
+    let claims = match response
+        .id_token()
+        .map(|t| t.claims(&client.id_token_verifier(), &Nonce::new(pending.nonce)))
+    {
+        Some(Ok(claims)) => claims.clone(),
+        Some(Err(err)) => {
+            log::info!("Bad ID token from {}: {}", provider, err);
+            return bad_request("Could not log in with the provider".to_string());
+        }
+        None => return bad_request("Provider sent no ID token".to_string()),
+    };
+    let subject = claims.subject().to_string();
+    let email = claims.email().map(|e| e.to_string());
+
+    let logged_in = auth.map(|auth| auth.user_id);
+    let user_id = match db::oidc::get_linked_user(&provider, &subject, pool.as_ref()).await {
+        Ok(Some(user_id)) => user_id,
+        Ok(None) => {
+            let user_id = match logged_in {
+                Some(user_id) => user_id,
+                None => {
+                    match account_for(&claims, email.clone(), pool.as_ref(), &mail, &config).await {
+                        Ok(user_id) => user_id,
+                        Err(resp) => return resp,
+                    }
+                }
+            };
+            if let Err(err) =
+                db::oidc::link(user_id, &provider, &subject, email, pool.as_ref()).await
+            {
+                log::error!("{}", err);
+                return bad_request("This provider account is already linked".to_string());
+            }
+            user_id
+        }
+        Err(err) => {
+            log::error!("{}", err);
+            return bad_request("Bad request (database errored, you are unlucky)".to_string());
+        }
+    };
+
+    // linking from an account that is already logged in, no new session needed
+    match logged_in {
+        Some(current) if current == user_id => {}
+        Some(_) => {
+            return bad_request("This provider account is linked to someone else".to_string())
+        }
+        None => {
+            if let Some(res) = suspended(user_id, pool.as_ref()).await {
+                return res;
+            }
+            // the provider stands in for the password, not for the second factor
+            match db::mfa::is_enabled(user_id, pool.as_ref()).await {
+                Ok(false) => {}
+                Ok(true) => {
+                    return match mfa_ticket(user_id, pool.as_ref()).await {
+                        Ok(ticket) => redirect(&format!(
+                            "{}/login/mfa#ticket={}",
+                            config.server.frontend_url, ticket
+                        )),
+                        Err(err) => {
+                            log::error!("{}", err);
+                            bad_request("DB failed to create session".to_string())
+                        }
+                    }
+                }
+                Err(err) => {
+                    log::error!("{}", err);
+                    return bad_request("DB failed to create session".to_string());
+                }
+            }
+            if let Err(err) =
+                log_in(user_id, &req, user_agent(&req, &ua_parser), pool.as_ref()).await
+            {
+                log::error!("{}", err);
+                return bad_request("DB failed to create session".to_string());
+            }
+        }
+    }
+    redirect(&config.server.frontend_url)
+}
+
+// The account with the same email if the provider checked it, otherwise a new one
+async fn account_for(
+    claims: &CoreIdTokenClaims,
+    email: Option<String>,
+    pool: &PgPool,
+    mail: &MailQueue,
+    config: &Config,
+) -> Result<i64, HttpResponse> {
+    let Some(email) = email else {
+        return Err(bad_request("Provider did not share an email".to_string()));
+    };
+    if db::account::reserved_email(&email) {
+        return Err(bad_request(
+            "Provider shared an email that can't be used".to_string(),
+        ));
+    }
+    let email_verified = claims.email_verified().unwrap_or(false);
+    match db::password::get_user_id_by_email(email.clone(), pool).await {
+        Ok(user_id) if email_verified => return Ok(user_id),
+        Ok(_) => {
+            return Err(bad_request(
+                "An account with this email exists, log in and link it from there".to_string(),
+            ))
+        }
+        Err(_) => {}
+    }
+    let username = match pick_username(claims, &email, pool).await {
+        Ok(username) => username,
+        Err(err) => {
+            log::error!("{}", err);
+            return Err(bad_request("DB failed to create user".to_string()));
+        }
+    };
+    // unverified emails get the same code as a normal signup
+    let (code, link_token) = new_code();
+    match db::oidc::create_user(
+        username,
+        email.clone(),
+        (!email_verified).then_some(code),
+        (!email_verified).then(|| token::hash(&link_token)),
+        CODE_MINUTES,
+        pool,
+    )
+    .await
+    {
+        Ok(user_id) => {
+            if !email_verified {
+                mail_code(mail, config, &email, code, &link_token);
+            }
+            Ok(user_id)
+        }
+        Err(err) => {
+            log::error!("{}", err);
+            Err(bad_request("DB failed to create user".to_string()))
+        }
+    }
+}
+
+// preferred_username, or the start of the email, with a number on the end if it's taken
+async fn pick_username(
+    claims: &CoreIdTokenClaims,
+    email: &str,
+    pool: &PgPool,
+) -> sqlx::Result<String> {
+    let base: String = claims
+        .preferred_username()
+        .map(|u| u.to_string())
+        .unwrap_or_else(|| email.split('@').next().unwrap_or("user").to_string())
+        .chars()
+        .take(58)
+        .collect();
+    let mut username = base.clone();
+    while db::oidc::username_taken(&username, pool).await? {
+        username = format!("{}{}", base, rand::thread_rng().gen_range(1000..10000));
+    }
+    Ok(username)
+}
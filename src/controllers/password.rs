@@ -0,0 +1,106 @@
+use actix_web::{
+    http::{header::ContentType, StatusCode},
+    web, HttpRequest, HttpResponse,
+};
+use sqlx::PgPool;
+
+use crate::controllers::login::{client_ip, locked, record_failure};
+use crate::controllers::ws::CLOSE_SESSION_REVOKED;
+use crate::db::{self, signup::create_password};
+use crate::mailer::{templates, MailQueue};
+use crate::{server, throttle, token};
+use utoipa;
+
+// how long a reset email stays valid
+pub const RESET_MINUTES: i32 = 30;
+
+#[utoipa::path(
+    post,
+    path = "/password/forgot",
+    responses(
+        (status = 200, description = "Always, whether or not the email exists", body = String),
+        (status = 429, description = "Too many requests for this email or from this IP", body = String)
+    ),
+    request_body(content = ForgotPasswordEvent, description = "account email", content_type = "application/json")
+)]
+pub async fn forgot(
+    req: HttpRequest,
+    body: web::Json<server::ForgotPasswordEvent>,
+    pool: web::Data<PgPool>,
+    mail: web::Data<MailQueue>,
+) -> HttpResponse {
+    let pl = body.into_inner();
+    // the IP shares its count with logins, the email gets one of its own
+    let keys = [
+        (throttle::reset_key(&pl.email), throttle::RESET_FREE),
+        (throttle::ip_key(&client_ip(&req)), throttle::IP_FREE),
+    ];
+    let names: Vec<String> = keys.iter().map(|(key, _)| key.clone()).collect();
+    if let Some(res) = locked(&names, pool.as_ref()).await {
+        return res;
+    }
+    // every request counts, found or not
+    record_failure(&keys, None, &req, "reset_locked", pool.as_ref()).await;
+    // don't tell anyone whether the email is in record
+    if let Ok(user_id) = db::password::get_user_id_by_email(pl.email.clone(), pool.as_ref()).await {
+        let reset = token::generate();
+        match db::password::create_reset(user_id, token::hash(&reset), RESET_MINUTES, pool.as_ref())
+            .await
+        {
+            Ok(_) => mail.send(templates::password_reset(&pl.email, &reset, RESET_MINUTES)),
+            Err(err) => log::error!("{}", err),
+        }
+    }
+    HttpResponse::Ok().finish()
+}
+
+#[utoipa::path(
+    post,
+    path = "/password/reset",
+    responses(
+        (status = 200, description = "Password changed, every session was logged out", body = String),
+        (status = 400, description = "Token is wrong, used or expired", body = String)
+    ),
+    request_body(content = ResetPasswordEvent, description = "token from the email, new password", content_type = "application/json")
+)]
+pub async fn reset(
+    body: web::Json<server::ResetPasswordEvent>,
+    pool: web::Data<PgPool>,
+    srv: web::Data<server::Chat>,
+) -> HttpResponse {
+    let pl = body.into_inner();
+    let password_hash = match create_password(pl.password) {
+        Ok(hash) => hash,
+        Err(_) => {
+            return HttpResponse::build(StatusCode::BAD_REQUEST)
+                .content_type(ContentType::plaintext())
+                .body("Unable to hash password")
+        }
+    };
+    let user_id = match db::password::use_reset(token::hash(&pl.token), pool.as_ref()).await {
+        Ok(user_id) => user_id,
+        Err(_) => {
+            return HttpResponse::build(StatusCode::BAD_REQUEST)
+                .content_type(ContentType::plaintext())
+                .body("Invalid or expired token")
+        }
+    };
+    if let Err(err) = db::password::set_password(user_id, password_hash, pool.as_ref()).await {
+        log::error!("{}", err);
+        return HttpResponse::build(StatusCode::BAD_REQUEST)
+            .content_type(ContentType::plaintext())
+            .body("DB failed to update password");
+    }
+    // whoever had the old password is out
+    if let Err(err) = db::password::revoke_sessions(user_id, pool.as_ref()).await {
+        log::error!("{}", err);
+    }
+    srv.disconnect_user(
+        user_id as usize,
+        None,
+        CLOSE_SESSION_REVOKED,
+        "Password changed",
+    )
+    .await;
+    HttpResponse::Ok().finish()
+}
@@ -0,0 +1,88 @@
+use actix_web::{
+    http::{header::ContentType, StatusCode},
+    web, HttpResponse,
+};
+use serde::Serialize;
+use sqlx::{types::Uuid, PgPool};
+
+use super::auth::Auth;
+use crate::controllers::ws::CLOSE_SESSION_REVOKED;
+use crate::db::{self, models::UserSession};
+use crate::server;
+
+#[derive(Serialize)]
+pub struct SessionInfo {
+    #[serde(flatten)]
+    pub session: UserSession,
+    // the one making this request
+    pub current: bool,
+}
+
+fn bad_request(body: &'static str) -> HttpResponse {
+    HttpResponse::build(StatusCode::BAD_REQUEST)
+        .content_type(ContentType::plaintext())
+        .body(body)
+}
+
+pub async fn list(auth: Auth, pool: web::Data<PgPool>) -> HttpResponse {
+    match db::sessions::list_sessions(auth.user_id, pool.as_ref()).await {
+        Ok(sessions) => HttpResponse::Ok().json(
+            sessions
+                .into_iter()
+                .map(|session| SessionInfo {
+                    current: session.session_id.to_string() == auth.session_id,
+                    session,
+                })
+                .collect::<Vec<SessionInfo>>(),
+        ),
+        Err(_) => bad_request("Bad request (database errored, you are unlucky)"),
+    }
+}
+
+// Logs out one session, its token and gateway connection go with it
+pub async fn delete(
+    path: web::Path<Uuid>,
+    auth: Auth,
+    pool: web::Data<PgPool>,
+    srv: web::Data<server::Chat>,
+) -> HttpResponse {
+    let session_id = path.into_inner();
+    match db::sessions::delete_session(auth.user_id, session_id, pool.as_ref()).await {
+        Ok(true) => {
+            srv.disconnect_user(
+                auth.user_id as usize,
+                Some(session_id.to_string()),
+                CLOSE_SESSION_REVOKED,
+                "Session revoked",
+            )
+            .await;
+            HttpResponse::Ok().finish()
+        }
+        Ok(false) => bad_request("No such session"),
+        Err(_) => bad_request("Bad request (database errored, you are unlucky)"),
+    }
+}
+
+// Log out everywhere else
+pub async fn delete_others(
+    auth: Auth,
+    pool: web::Data<PgPool>,
+    srv: web::Data<server::Chat>,
+) -> HttpResponse {
+    let keep = Uuid::parse_str(&auth.session_id).unwrap();
+    match db::sessions::delete_other_sessions(auth.user_id, keep, pool.as_ref()).await {
+        Ok(revoked) => {
+            for session_id in revoked.iter() {
+                srv.disconnect_user(
+                    auth.user_id as usize,
+                    Some(session_id.to_string()),
+                    CLOSE_SESSION_REVOKED,
+                    "Session revoked",
+                )
+                .await;
+            }
+            HttpResponse::Ok().json(revoked)
+        }
+        Err(_) => bad_request("Bad request (database errored, you are unlucky)"),
+    }
+}
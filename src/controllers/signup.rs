@@ -3,15 +3,19 @@ use actix_web::{
     http::{header::ContentType, StatusCode},
     web, HttpMessage, HttpRequest, HttpResponse,
 };
-use lettre::transport::smtp::authentication::Credentials;
-use lettre::{Message, SmtpTransport, Transport};
-use rand::Rng;
-use std::{sync::Arc, borrow::Cow};
+use std::sync::Arc;
 
 use actix_session::Session;
 
-use crate::db::signup::{create_password, create_user};
-use crate::{db::signup::UserAgent, server, EMAIL_PASSWORD};
+use crate::db::account::{reserved_email, reserved_username};
+use crate::db::signup::{create_password, create_user, NewUser};
+use crate::captcha::CaptchaProvider;
+use crate::config::Config;
+use crate::controllers::auth::Auth;
+use crate::controllers::captcha;
+use crate::controllers::verify::{mail_code, new_code, CODE_MINUTES};
+use crate::controllers::login::user_agent;
+use crate::{mailer::MailQueue, server, token};
 use serde_json::json;
 use sqlx::postgres::PgPool;
 use utoipa;
@@ -27,74 +31,44 @@ use user_agent_parser::UserAgentParser;
     ),
     request_body(content = SignUpEvent, description = "user email, user password, username", content_type = "application/json")
 )]
+// Everything here is an extractor, so they can't be bundled up
+#[allow(clippy::too_many_arguments)]
 pub async fn post(
     body: web::Json<server::SignUpEvent>,
     pool: web::Data<PgPool>,
-    id: Option<Identity>,
+    auth: Option<Auth>,
     req: HttpRequest,
     session: Session,
     ua_parser: web::Data<Arc<UserAgentParser>>,
+    mail: web::Data<MailQueue>,
+    provider: web::Data<dyn CaptchaProvider>,
+    config: web::Data<Config>,
 ) -> HttpResponse {
-    let user_agent = req
-        .headers()
-        .get("user-agent")
-        .unwrap()
-        .to_str()
-        .ok()
-        .unwrap_or("");
-    // println!("USER AGENT {}", user_agent.unwrap());
-    let browser = ua_parser.parse_product(user_agent);
-    let os = ua_parser.parse_os(user_agent);
-    let device = ua_parser.parse_device(user_agent);
-    println!(
-        "User Agents\nProduct {:#?}\nOs {:#?}\nDevice {:#?}",
-        browser, os, device
-    );
-    if id.is_some() {
+    if auth.is_some() {
         return HttpResponse::Ok().finish();
     }
     let pl = body.into_inner();
-    if pl.code != session.get::<String>("captcha").unwrap().unwrap() {
+    if let Err(res) = captcha::verify(provider.as_ref(), &session, &pl.code, pool.as_ref()).await {
+        return res;
+    }
+    if reserved_username(&pl.username) || reserved_email(&pl.email) {
         return HttpResponse::build(StatusCode::BAD_REQUEST)
             .content_type(ContentType::plaintext())
-            .body("You are a bot");
+            .body("Bad request, duplicate");
     }
-    session.remove("captcha");
     match create_password(pl.password) {
         Ok(password_hash) => {
             // generate random
-            let code = rand::thread_rng().gen_range(100000..999999);
-            let uag = UserAgent {
-                os: Some(format!(
-                    "{} {} {}",
-                    os.name.unwrap_or(Cow::Borrowed("")),
-                    os.major.unwrap_or(Cow::Borrowed("")),
-                    os.minor.unwrap_or(Cow::Borrowed(""))
-                )),
-                browser: Some(format!(
-                    "{} {} {}",
-                    browser.name.unwrap_or(Cow::Borrowed("")),
-                    browser.major.unwrap_or(Cow::Borrowed("")),
-                    browser.minor.unwrap_or(Cow::Borrowed(""))
-                )),
-                device: Some(format!(
-                    "{} {} {}",
-                    device.name.unwrap_or(Cow::Borrowed("")),
-                    device.model.unwrap_or(Cow::Borrowed("")),
-                    device.brand.unwrap_or(Cow::Borrowed(""))
-                )),
-                original: user_agent.to_string(),
-            };
-            match create_user(
-                pl.username,
-                pl.email.clone(),
-                code.into(),
+            let (code, link_token) = new_code();
+            let user = NewUser {
+                username: pl.username,
+                email: pl.email.clone(),
                 password_hash,
-                uag,
-                pool.as_ref(),
-            )
-            .await
-            {
+                code,
+                token_hash: token::hash(&link_token),
+                minutes: CODE_MINUTES,
+            };
+            match create_user(user, user_agent(&req, &ua_parser), pool.as_ref()).await {
                 Ok((session_id, user_id)) => {
                     Identity::login(
                         &req.extensions(),
@@ -105,36 +79,7 @@ pub async fn post(
                         .to_string(),
                     )
                     .unwrap();
-                    let email = Message::builder()
-                        .from(
-                            "Balls Eater <capitalismdiscordbot@gmail.com>"
-                                .parse()
-                                .unwrap(),
-                        )
-                        .reply_to("Ballz <capitalismdiscordbot@gmail.com>".parse().unwrap())
-                        .to(format!("<{}>", pl.email.clone()).parse().unwrap())
-                        .subject("Thanks for registering for flettex")
-                        .body(format!("Your verification code is: {}", code))
-                        .unwrap();
-
-                    println!("CODE IS {}", code);
-
-                    let creds = Credentials::new(
-                        "capitalismdiscordbot@gmail.com".to_string(),
-                        EMAIL_PASSWORD.to_string(),
-                    );
-
-                    // Open a remote connection to gmail
-                    let mailer = SmtpTransport::relay("smtp.gmail.com")
-                        .unwrap()
-                        .credentials(creds)
-                        .build();
-
-                    // Send the email
-                    match mailer.send(&email) {
-                        Ok(_) => println!("Email sent successfully!"),
-                        Err(e) => println!("Could not send email: {:?}", e),
-                    }
+                    mail_code(&mail, &config, &pl.email, code, &link_token);
                     HttpResponse::Ok().finish()
                 }
                 Err(err) => match err {
@@ -145,7 +90,7 @@ pub async fn post(
                                 .content_type(ContentType::plaintext())
                                 .body("Bad request, duplicate")
                         } else {
-                            println!("{}", err);
+                            log::error!("{}", err);
                             if let Some(errcode) = err.code() {
                                 HttpResponse::build(StatusCode::BAD_REQUEST)
                                     .content_type(ContentType::plaintext())
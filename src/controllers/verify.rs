@@ -1,29 +1,61 @@
-use actix_identity::Identity;
 use actix_web::{
     http::{header::ContentType, StatusCode},
     web, HttpResponse,
 };
+use rand::Rng;
 use sqlx::PgPool;
 
+use super::auth::Auth;
+use crate::config::Config;
 use crate::db;
-use crate::server;
+use crate::mailer::{templates, MailQueue};
+use crate::{server, token};
+
+// how long a code (and its link) is good for
+pub const CODE_MINUTES: i32 = 15;
+// wrong guesses before the code is burnt and a new one has to be sent
+pub const MAX_ATTEMPTS: i32 = 5;
+// seconds between resends
+pub const RESEND_COOLDOWN: f64 = 60.0;
+
+// A fresh code and the magic link token that goes with it
+pub fn new_code() -> (i64, String) {
+    (
+        rand::thread_rng().gen_range(100000..999999),
+        token::generate(),
+    )
+}
+
+pub fn mail_code(mail: &MailQueue, config: &Config, email: &str, code: i64, link_token: &str) {
+    let link = format!("{}/verify/link/{}", config.public_url(), link_token);
+    mail.send(templates::verification(
+        email,
+        code,
+        CODE_MINUTES,
+        Some(link),
+    ));
+}
 
 pub async fn post(
     body: web::Json<server::VerifyEvent>,
     pool: web::Data<PgPool>,
-    id: Option<Identity>,
+    auth: Option<Auth>,
 ) -> HttpResponse {
-    if let Some(session_id) = id {
-        let session_cookie: server::AuthCookie =
-            serde_json::from_str(&session_id.id().unwrap()).unwrap();
-        match db::verify::code(session_cookie.user_id, pool.as_ref()).await {
-            Ok(code) => {
-                if code.is_none() {
-                    return HttpResponse::Ok().body("already verified");
-                }
-                if body.code == code.unwrap() {
+    if let Some(auth) = auth {
+        match db::verify::attempt(auth.user_id, pool.as_ref()).await {
+            Ok(None) => HttpResponse::Ok().body("already verified"),
+            Ok(Some(attempt)) => {
+                if attempt.attempts > MAX_ATTEMPTS {
+                    HttpResponse::build(StatusCode::TOO_MANY_REQUESTS)
+                        .content_type(ContentType::plaintext())
+                        .body("Too many attempts, ask for a new code at /verify/resend")
+                } else if attempt.expired {
+                    HttpResponse::build(StatusCode::BAD_REQUEST)
+                        .content_type(ContentType::plaintext())
+                        .body("Code expired, ask for a new code at /verify/resend")
+                } else if body.code == attempt.code {
                     // this can't error bro we just got the code
-                    db::verify::delete_code(session_cookie.user_id, pool.as_ref())
+                    db::verify::delete_code(auth.user_id, pool.as_ref())
                         .await
                         .unwrap();
                     HttpResponse::Ok().finish()
@@ -41,3 +73,55 @@ pub async fn post(
         HttpResponse::Ok().finish()
     }
 }
+
+pub async fn resend(
+    pool: web::Data<PgPool>,
+    mail: web::Data<MailQueue>,
+    config: web::Data<Config>,
+    auth: Auth,
+) -> HttpResponse {
+    let (code, link_token) = new_code();
+    match db::verify::new_code(
+        auth.user_id,
+        code,
+        token::hash(&link_token),
+        CODE_MINUTES,
+        RESEND_COOLDOWN,
+        pool.as_ref(),
+    )
+    .await
+    {
+        Ok(Some(email)) => {
+            mail_code(&mail, &config, &email, code, &link_token);
+            HttpResponse::Ok().finish()
+        }
+        // either verified or asked too recently
+        Ok(None) => match db::verify::code(auth.user_id, pool.as_ref()).await {
+            Ok(None) => HttpResponse::Ok().body("already verified"),
+            _ => HttpResponse::build(StatusCode::TOO_MANY_REQUESTS)
+                .content_type(ContentType::plaintext())
+                .body(format!(
+                    "Wait {} seconds between codes",
+                    RESEND_COOLDOWN as i64
+                )),
+        },
+        Err(err) => {
+            log::error!("{}", err);
+            HttpResponse::build(StatusCode::BAD_REQUEST)
+                .content_type(ContentType::plaintext())
+                .body("Bad request (database errored, you are unlucky)")
+        }
+    }
+}
+
+// the link in the email, works without being logged in
+pub async fn link(path: web::Path<String>, pool: web::Data<PgPool>) -> HttpResponse {
+    match db::verify::verify_token(token::hash(&path.into_inner()), pool.as_ref()).await {
+        Ok(_) => HttpResponse::Ok()
+            .content_type(ContentType::plaintext())
+            .body("Email verified, you can close this page"),
+        Err(_) => HttpResponse::build(StatusCode::BAD_REQUEST)
+            .content_type(ContentType::plaintext())
+            .body("Invalid or expired link"),
+    }
+}
@@ -0,0 +1,224 @@
+use actix_session::Session;
+use actix_web::{
+    http::{header::ContentType, StatusCode},
+    web, HttpRequest, HttpResponse,
+};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::sync::Arc;
+use user_agent_parser::UserAgentParser;
+use webauthn_rs::prelude::{
+    CredentialID, Passkey, PasskeyAuthentication, PasskeyRegistration, PublicKeyCredential,
+    RegisterPublicKeyCredential,
+};
+use webauthn_rs::Webauthn;
+
+use super::auth::Auth;
+use super::login::{create_session, suspended, user_agent};
+use crate::{db, passkey};
+
+// ceremony state lives in the (encrypted) cookie session between start and finish
+const REGISTRATION: &str = "webauthn_registration";
+const AUTHENTICATION: &str = "webauthn_authentication";
+
+#[derive(Deserialize)]
+pub struct RegisterQuery {
+    pub name: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct LoginStartEvent {
+    pub email: String,
+}
+
+fn bad_request(body: &'static str) -> HttpResponse {
+    HttpResponse::build(StatusCode::BAD_REQUEST)
+        .content_type(ContentType::plaintext())
+        .body(body)
+}
+
+// how a credential id is keyed in user_credential, its json form is already base64url
+fn credential_key(id: &CredentialID) -> String {
+    serde_json::to_string(id).unwrap()
+}
+
+fn passkeys(stored: Vec<String>) -> Vec<Passkey> {
+    stored
+        .iter()
+        .filter_map(|p| serde_json::from_str(p).ok())
+        .collect()
+}
+
+pub async fn register_start(
+    auth: Auth,
+    session: Session,
+    pool: web::Data<PgPool>,
+    webauthn: web::Data<Webauthn>,
+) -> HttpResponse {
+    let user = match db::ws_session::get_user_by_session_id(auth.session_id, pool.as_ref()).await {
+        Ok(user) => user,
+        Err(_) => return bad_request("Bad request (database errored, you are unlucky)"),
+    };
+    // the authenticator refuses to register a second key for the same account
+    let existing: Vec<CredentialID> = passkeys(
+        db::webauthn::get_passkeys(user.id, pool.as_ref())
+            .await
+            .unwrap_or_default(),
+    )
+    .iter()
+    .map(|p| p.cred_id().clone())
+    .collect();
+    match webauthn.start_passkey_registration(
+        passkey::user_handle(user.id),
+        &user.email,
+        &user.username,
+        Some(existing),
+    ) {
+        Ok((challenge, state)) => {
+            session.insert(REGISTRATION, (user.id, state)).unwrap();
+            HttpResponse::Ok().json(challenge)
+        }
+        Err(err) => {
+            log::error!("{:?}", err);
+            bad_request("Could not start registration")
+        }
+    }
+}
+
+pub async fn register_finish(
+    body: web::Json<RegisterPublicKeyCredential>,
+    query: web::Query<RegisterQuery>,
+    auth: Auth,
+    session: Session,
+    pool: web::Data<PgPool>,
+    webauthn: web::Data<Webauthn>,
+) -> HttpResponse {
+    let Ok(Some((user_id, state))) = session
+        .remove_as::<(i64, PasskeyRegistration)>(REGISTRATION)
+        .transpose()
+    else {
+        return bad_request("No registration in progress");
+    };
+    // started as someone else, then logged in as this user
+    if user_id != auth.user_id {
+        return bad_request("No registration in progress");
+    }
+    let passkey = match webauthn.finish_passkey_registration(&body, &state) {
+        Ok(passkey) => passkey,
+        Err(err) => {
+            log::info!("{:?}", err);
+            return bad_request("Registration failed");
+        }
+    };
+    match db::webauthn::add_passkey(
+        user_id,
+        credential_key(passkey.cred_id()),
+        serde_json::to_string(&passkey).unwrap(),
+        query
+            .into_inner()
+            .name
+            .unwrap_or_else(|| "Passkey".to_string()),
+        pool.as_ref(),
+    )
+    .await
+    {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(err) => {
+            log::error!("{}", err);
+            bad_request("Passkey is already registered")
+        }
+    }
+}
+
+pub async fn login_start(
+    body: web::Json<LoginStartEvent>,
+    session: Session,
+    pool: web::Data<PgPool>,
+    webauthn: web::Data<Webauthn>,
+) -> HttpResponse {
+    let user_id = match db::password::get_user_id_by_email(body.email.clone(), pool.as_ref()).await
+    {
+        Ok(user_id) => user_id,
+        Err(_) => return bad_request("No passkeys for this account"),
+    };
+    let keys = passkeys(
+        db::webauthn::get_passkeys(user_id, pool.as_ref())
+            .await
+            .unwrap_or_default(),
+    );
+    if keys.is_empty() {
+        return bad_request("No passkeys for this account");
+    }
+    match webauthn.start_passkey_authentication(&keys) {
+        Ok((challenge, state)) => {
+            session.insert(AUTHENTICATION, (user_id, state)).unwrap();
+            HttpResponse::Ok().json(challenge)
+        }
+        Err(err) => {
+            log::error!("{:?}", err);
+            bad_request("Could not start authentication")
+        }
+    }
+}
+
+// ends up exactly where a password login does, a user_sessions row and the auth cookie
+pub async fn login_finish(
+    body: web::Json<PublicKeyCredential>,
+    session: Session,
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    webauthn: web::Data<Webauthn>,
+    ua_parser: web::Data<Arc<UserAgentParser>>,
+) -> HttpResponse {
+    let Ok(Some((user_id, state))) = session
+        .remove_as::<(i64, PasskeyAuthentication)>(AUTHENTICATION)
+        .transpose()
+    else {
+        return bad_request("No login in progress");
+    };
+    let result = match webauthn.finish_passkey_authentication(&body, &state) {
+        Ok(result) => result,
+        Err(err) => {
+            log::info!("{:?}", err);
+            return bad_request("Authentication failed");
+        }
+    };
+    let key = credential_key(result.cred_id());
+    let stored = match db::webauthn::get_passkey(key.clone(), pool.as_ref()).await {
+        Ok(stored) if stored.user_id == user_id => stored,
+        _ => return bad_request("Authentication failed"),
+    };
+    // keeps the sign counter moving so a cloned authenticator gets noticed
+    if let Ok(mut passkey) = serde_json::from_str::<Passkey>(&stored.passkey) {
+        passkey.update_credential(&result);
+        if let Err(err) = db::webauthn::update_passkey(
+            key,
+            serde_json::to_string(&passkey).unwrap(),
+            result.counter() as i64,
+            pool.as_ref(),
+        )
+        .await
+        {
+            log::error!("{}", err);
+        }
+    }
+    if let Some(res) = suspended(user_id, pool.as_ref()).await {
+        return res;
+    }
+    create_session(user_id, &req, user_agent(&req, &ua_parser), pool.as_ref()).await
+}
+
+pub async fn list(auth: Auth, pool: web::Data<PgPool>) -> HttpResponse {
+    match db::webauthn::list_credentials(auth.user_id, pool.as_ref()).await {
+        Ok(creds) => HttpResponse::Ok().json(creds),
+        Err(_) => bad_request("Bad request (database errored, you are unlucky)"),
+    }
+}
+
+pub async fn delete(path: web::Path<i64>, auth: Auth, pool: web::Data<PgPool>) -> HttpResponse {
+    match db::webauthn::delete_credential(path.into_inner(), auth.user_id, pool.as_ref()).await {
+        Ok(res) if res.rows_affected() == 1 => HttpResponse::Ok().finish(),
+        Ok(_) => bad_request("No such passkey"),
+        Err(_) => bad_request("Bad request (database errored, you are unlucky)"),
+    }
+}
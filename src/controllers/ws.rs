@@ -1,16 +1,15 @@
-use std::{collections::HashSet, sync::Arc, time::Instant};
+use std::time::Duration;
 
-use actix_identity::Identity;
-use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_web::{http::StatusCode, rt::time::timeout, web, Error, HttpRequest, HttpResponse};
 
-use actix_ws::{CloseCode, CloseReason};
+use actix_ws::{CloseCode, CloseReason, Message, MessageStream};
 use serde::Deserialize;
 
-use futures::future;
-use tokio::sync::Mutex;
-
+use futures::{future, StreamExt};
 use sqlx::PgPool;
 
+use super::auth::Auth;
+
 // use serde_cbor;
 
 use crate::{
@@ -20,12 +19,15 @@ use crate::{
         self,
         // MessageTypes,
         // MessageCreateType,
-        AuthCookie,
     },
     session::WsChatSession,
-    PLACEHOLDER_UUID,
 };
 
+// Close codes sent to gateway clients
+pub const CLOSE_UNAUTHORIZED: u16 = 4000;
+pub const CLOSE_SESSION_REVOKED: u16 = 4001;
+pub const CLOSE_SUSPENDED: u16 = 4002;
+
 #[derive(Deserialize)]
 pub struct WsQuery {
     pub recv_type: Option<String>,
@@ -37,96 +39,123 @@ pub enum WsMsgType {
     Cbor,
 }
 
+// Clients that can't set headers on a websocket (browsers without the cookie) send this
+// as their first frame instead: {"type": "Identify", "data": {"token": "..."}}
+#[derive(Deserialize)]
+#[serde(tag = "type", content = "data")]
+enum Handshake {
+    Identify { token: String },
+}
+
+const IDENTIFY_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn suspended_close() -> CloseReason {
+    CloseReason {
+        code: CloseCode::Other(CLOSE_SUSPENDED),
+        description: Some("Account suspended".to_string()),
+    }
+}
+
+// Why a known user can't open a gateway connection, None lets them in. A session
+// can outlive a suspension that came after it.
+pub async fn refusal(user_id: i64, pool: &PgPool) -> Option<CloseReason> {
+    match db::suspension::active(user_id, pool).await {
+        Ok(None) => None,
+        Ok(Some(_)) => Some(suspended_close()),
+        Err(err) => {
+            log::error!("{}", err);
+            Some(CloseReason {
+                code: CloseCode::Error,
+                description: None,
+            })
+        }
+    }
+}
+
+async fn identify(
+    stream: &mut MessageStream,
+    recv_type: &WsMsgType,
+    pool: &PgPool,
+) -> Option<Auth> {
+    let msg = timeout(IDENTIFY_TIMEOUT, stream.next()).await.ok()??.ok()?;
+    let handshake: Handshake = match (msg, recv_type) {
+        (Message::Text(text), _) => serde_json::from_str(&text).ok()?,
+        (Message::Binary(bin), WsMsgType::Cbor) => serde_cbor::from_slice(&bin).ok()?,
+        _ => return None,
+    };
+    let Handshake::Identify { token } = handshake;
+    Auth::from_token(&token, pool).await
+}
+
 pub async fn get(
     req: HttpRequest,
     stream: web::Payload,
     srv: web::Data<server::Chat>,
     pool: web::Data<PgPool>,
-    id: Option<Identity>,
+    auth: Result<Auth, Error>,
     query: web::Query<WsQuery>,
 ) -> Result<HttpResponse, Error> {
-    if let Some(session_id) = id {
-        println!("Receiving ws request");
-        let recv_type = match query.into_inner().recv_type {
-            Some(t) => {
-                if t == *"json" {
-                    WsMsgType::Json
-                // } else if t == "cbor".to_string() {
-                //     WsMsgType::Cbor
-                } else {
-                    WsMsgType::Cbor
-                }
+    log::debug!("Receiving ws request");
+    let recv_type = match query.into_inner().recv_type {
+        Some(t) => {
+            if t == *"json" {
+                WsMsgType::Json
+            // } else if t == "cbor".to_string() {
+            //     WsMsgType::Cbor
+            } else {
+                WsMsgType::Cbor
             }
-            None => WsMsgType::Cbor,
+        }
+        None => WsMsgType::Cbor,
+    };
+    let (response, session, mut stream) = actix_ws::handle(&req, stream)?;
+    actix_web::rt::spawn(async move {
+        // cookie or bearer header, otherwise the first frame has to be an Identify
+        let auth = match auth {
+            Ok(auth) => Some(auth),
+            // the extractor found the session but the account is suspended
+            Err(err) if err.as_response_error().status_code() == StatusCode::FORBIDDEN => {
+                let _ = session.close(Some(suspended_close())).await;
+                return;
+            }
+            Err(_) => identify(&mut stream, &recv_type, pool.as_ref()).await,
+        };
+        let Some(auth) = auth else {
+            log::debug!("Unauthorized user");
+            let _ = session
+                .close(Some(CloseReason {
+                    code: CloseCode::Other(CLOSE_UNAUTHORIZED),
+                    description: Some("Unauthorized".to_string()),
+                }))
+                .await;
+            return;
         };
-        let (response, session, stream) = actix_ws::handle(&req, stream)?;
-        let session_cookie: AuthCookie = serde_json::from_str(&session_id.id().unwrap()).unwrap();
-        log::info!("Inserted session");
-        let alive = Arc::new(Mutex::new(Instant::now()));
-        println!("{}", session_cookie.session_id.clone());
-        match db::ws_session::get_user_by_session_id(
-            session_cookie.session_id.clone(),
-            pool.as_ref(),
-        )
-        .await
-        {
+        match db::ws_session::get_user_by_session_id(auth.session_id.clone(), pool.as_ref()).await {
             Ok(user) => {
-                actix_web::rt::spawn(async move {
-                    let chat_session = WsChatSession {
-                        user: user.clone(),
-                        rooms: Arc::new(Mutex::new(HashSet::from([PLACEHOLDER_UUID.to_owned()]))),
-                        srv: srv.as_ref().clone(),
-                        pool: pool.as_ref().clone(),
-                        alive,
-                        session,
-                        session_id: session_cookie.session_id,
-                        recv_type, // stream: Arc::new(Mutex::new(stream))
-                    };
-                    srv.insert_session(user.id as usize, chat_session.clone())
-                        .await;
-                    future::join(chat_session.hb(), chat_session.start(stream)).await;
-                });
-                log::info!("Spawned");
+                // Identify only looked at the token, so this is asked either way
+                if let Some(reason) = refusal(user.id, pool.as_ref()).await {
+                    let _ = session.close(Some(reason)).await;
+                    return;
+                }
+                let (chat_session, writer) = WsChatSession::new(
+                    user.clone(),
+                    srv.as_ref().clone(),
+                    pool.as_ref().clone(),
+                    session,
+                    auth.session_id,
+                    recv_type,
+                );
+                actix_web::rt::spawn(writer.run());
+                srv.insert_session(user.id as usize, chat_session.handle())
+                    .await;
+                log::info!("Inserted session");
+                future::join(chat_session.hb(), chat_session.start(stream)).await;
             }
             Err(_err) => {
-                println!("{:?}", _err);
+                log::error!("{:?}", _err);
                 let _ = session.close(None).await;
             }
         };
-
-        Ok(response)
-    } else {
-        println!("Unauthorized user");
-        // don't need to read stream bec they're unauthorized
-        let (response, session, _stream) = actix_ws::handle(&req, stream)?;
-        let _ = session
-            .close(Some(CloseReason {
-                code: CloseCode::Other(4000),
-                description: Some("Unauthorized".to_string()),
-            }))
-            .await;
-        // let recv_type = match query.into_inner().recv_type {
-        //     Some(t) => {
-        //         if t == "json".to_string() {
-        //             WsMsgType::Json
-        //         } else if t == "cbor".to_string() {
-        //             WsMsgType::Cbor
-        //         } else {
-        //             WsMsgType::Cbor
-        //         }
-        //     }
-        //     None => WsMsgType::Cbor
-        // };
-        // match recv_type {
-        //     WsMsgType::Cbor => {
-        //         session.binary(serde_cbor::to_vec(&ErrorMessageTypes::ErrorUnauthorized(UnauthorizedError { content: "Unauthorized".to_string()})).unwrap()).await.unwrap();
-        //     }
-        //     WsMsgType::Json => {
-        //         session.text(serde_json::to_string(&ErrorMessageTypes::ErrorUnauthorized(UnauthorizedError { content: "Unauthorized".to_string()})).unwrap()).await.unwrap();
-        //     }
-        // }
-        // // 4000 is unauthorized error
-        // let _ = session.close(Some(CloseReason { code: CloseCode::Other(4000), description: Some("Unauthorized".to_string()) })).await;
-        Ok(response)
-    }
+    });
+    Ok(response)
 }
@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::{types::Uuid, PgConnection, PgPool};
+
+use super::models::{Channel, Message};
+use crate::format;
+
+// who deleted accounts' messages end up belonging to
+pub const GHOST_EMAIL: &str = "deleted@users.invalid";
+pub const GHOST_USERNAME: &str = "[deleted]";
+
+// nobody gets to sign up as, or rename themselves to, the ghost
+pub fn reserved_username(username: &str) -> bool {
+    username.trim().eq_ignore_ascii_case(GHOST_USERNAME)
+}
+
+pub fn reserved_email(email: &str) -> bool {
+    email.trim().eq_ignore_ascii_case(GHOST_EMAIL)
+}
+
+#[derive(Serialize)]
+pub struct Membership {
+    pub guild_id: Uuid,
+    pub guild_name: String,
+    pub nick_name: Option<String>,
+    #[serde(with = "format::date_format2")]
+    pub joined_at: NaiveDateTime,
+    pub owner: bool,
+}
+
+pub async fn memberships(user_id: i64, pool: &PgPool) -> sqlx::Result<Vec<Membership>> {
+    sqlx::query_as!(
+        Membership,
+        r#"
+SELECT m.guild_id, g.name as guild_name, m.nick_name, m.joined_at, g.creator_id = $1 as "owner!"
+FROM member m
+INNER JOIN guild g ON g.id = m.guild_id
+WHERE m.user_id = $1
+ORDER BY m.joined_at
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn dm_channels(user_id: i64, pool: &PgPool) -> sqlx::Result<Vec<Channel>> {
+    sqlx::query_as!(
+        Channel,
+        r#"
+SELECT * FROM channel WHERE user1 = $1 OR user2 = $1 ORDER BY created_at
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Everything they wrote anywhere, plus what the other side wrote in their DMs
+pub async fn messages(user_id: i64, pool: &PgPool) -> sqlx::Result<Vec<Message>> {
+    sqlx::query_as!(
+        Message,
+        r#"
+SELECT msg.* FROM message msg
+INNER JOIN channel c ON c.id = msg.channel_id
+WHERE msg.author_id = $1 OR c.user1 = $1 OR c.user2 = $1
+ORDER BY msg.created_at
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// Migration 14 makes the ghost, but skipped it when a real account already had the name.
+// In that case it's made here, with a name nobody can have taken.
+pub async fn ghost(conn: &mut PgConnection) -> sqlx::Result<i64> {
+    if let Some(rec) = sqlx::query!(
+        r#"
+SELECT id FROM users WHERE email = $1
+        "#,
+        GHOST_EMAIL
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    {
+        return Ok(rec.id);
+    }
+    match sqlx::query!(
+        r#"
+INSERT INTO users (username, email, password, allow_login)
+SELECT CASE WHEN EXISTS (SELECT 1 FROM users WHERE username = $1)
+    THEN $1 || ' ' || substr(md5(random()::text), 1, 8) ELSE $1 END,
+    $2, '', FALSE
+RETURNING id
+        "#,
+        GHOST_USERNAME,
+        GHOST_EMAIL
+    )
+    .fetch_one(&mut *conn)
+    .await
+    {
+        Ok(rec) => Ok(rec.id),
+        Err(err) => Err(err),
+    }
+}
+
+// Their seat in DMs goes to the ghost along with what they wrote there, so whoever is on
+// the other end keeps the conversation. DMs between two of them (or with the ghost) have
+// nobody left to read them and go. Relations just go.
+pub async fn ghost_dms(ids: &[i64], ghost: i64, conn: &mut PgConnection) -> sqlx::Result<()> {
+    let mut gone = ids.to_vec();
+    gone.push(ghost);
+    sqlx::query!(
+        r#"
+DELETE FROM channel WHERE user1 = ANY($1) AND user2 = ANY($1)
+        "#,
+        &gone
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!(
+        r#"
+UPDATE message SET author_id = $2
+WHERE author_id = ANY($1) AND channel_id IN (SELECT id FROM channel WHERE guild_id IS NULL)
+        "#,
+        ids,
+        ghost
+    )
+    .execute(&mut *conn)
+    .await?;
+    // There's one DM per pair, so everyone on the other end keeps a single one with the
+    // ghost: the one they already had with it, otherwise the oldest. The others are
+    // merged into it.
+    let dms = sqlx::query!(
+        r#"
+SELECT c.id, CASE WHEN c.user1 = ANY($1) THEN c.user2 ELSE c.user1 END as "partner!"
+FROM channel c
+WHERE (c.user1 = ANY($1) OR c.user2 = ANY($1))
+    AND CASE WHEN c.user1 = ANY($1) THEN c.user2 ELSE c.user1 END IN (
+        SELECT CASE WHEN user1 = ANY($2) THEN user2 ELSE user1 END
+        FROM channel WHERE user1 = ANY($2) OR user2 = ANY($2)
+    )
+ORDER BY (c.user1 = $3 OR c.user2 = $3) DESC, c.created_at, c.id
+        "#,
+        &gone,
+        ids,
+        ghost
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    let mut kept = HashMap::new();
+    let (mut dups, mut keep) = (vec![], vec![]);
+    for dm in dms {
+        match kept.get(&dm.partner) {
+            Some(id) => {
+                dups.push(dm.id);
+                keep.push(*id);
+            }
+            None => {
+                kept.insert(dm.partner, dm.id);
+            }
+        }
+    }
+    sqlx::query!(
+        r#"
+UPDATE message m SET channel_id = t.keep
+FROM UNNEST($1::uuid[], $2::uuid[]) AS t(dup, keep)
+WHERE m.channel_id = t.dup
+        "#,
+        &dups,
+        &keep
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!(
+        r#"
+DELETE FROM channel WHERE id = ANY($1)
+        "#,
+        &dups
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!(
+        r#"
+UPDATE channel
+SET user1 = CASE WHEN user1 = ANY($1) THEN $2 ELSE user1 END,
+    user2 = CASE WHEN user2 = ANY($1) THEN $2 ELSE user2 END
+WHERE user1 = ANY($1) OR user2 = ANY($1)
+        "#,
+        ids,
+        ghost
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!(
+        r#"
+DELETE FROM user_relations WHERE user1 = ANY($1) OR user2 = ANY($1)
+        "#,
+        ids
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+pub struct Deleted {
+    // (guild, user) for every guild they or their bots were still a member of
+    pub left: Vec<(Uuid, i64)>,
+    // owned guilds nobody was left to inherit
+    pub guilds: Vec<Uuid>,
+}
+
+// Returns what the other members need to hear about. Guild messages go to the ghost user and so does their side
+// of DMs, owned guilds go to the longest standing member or away if nobody is left, bots
+// they own are deleted along with them.
+pub async fn delete_account(user_id: i64, pool: &PgPool) -> sqlx::Result<Deleted> {
+    let mut tx = pool.begin().await?;
+    let ghost = ghost(&mut tx).await?;
+    let mut ids = vec![user_id];
+    for rec in sqlx::query!(
+        r#"
+SELECT bot_id FROM application WHERE owner_id = $1
+        "#,
+        user_id
+    )
+    .fetch_all(&mut *tx)
+    .await?
+    {
+        ids.push(rec.bot_id);
+    }
+    sqlx::query!(
+        r#"
+UPDATE message SET author_id = $2
+WHERE author_id = $1 AND channel_id IN (SELECT id FROM channel WHERE guild_id IS NOT NULL)
+        "#,
+        user_id,
+        ghost
+    )
+    .execute(&mut *tx)
+    .await?;
+    ghost_dms(&ids, ghost, &mut tx).await?;
+    sqlx::query!(
+        r#"
+UPDATE guild g SET creator_id = heir.user_id
+FROM (
+    SELECT DISTINCT ON (m.guild_id) m.guild_id, m.user_id
+    FROM member m
+    INNER JOIN users u ON u.id = m.user_id
+    WHERE m.user_id <> $1 AND NOT u.is_bot
+    ORDER BY m.guild_id, m.joined_at
+) heir
+WHERE g.id = heir.guild_id AND g.creator_id = $1
+        "#,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    let guilds = sqlx::query!(
+        r#"
+DELETE FROM guild WHERE creator_id = $1 RETURNING id
+        "#,
+        user_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let left = sqlx::query!(
+        r#"
+DELETE FROM member WHERE user_id = ANY($1) RETURNING guild_id, user_id
+        "#,
+        &ids
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    sqlx::query!(
+        r#"
+DELETE FROM users WHERE id = ANY($1)
+        "#,
+        &ids
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Deleted {
+        left: left
+            .into_iter()
+            .map(|rec| (rec.guild_id, rec.user_id))
+            .collect(),
+        guilds: guilds.into_iter().map(|rec| rec.id).collect(),
+    })
+}
@@ -0,0 +1,269 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::{postgres::PgQueryResult, types::Uuid, PgPool};
+
+use super::models::{Channel, Message, User, UserSession};
+use crate::format;
+
+// A user as staff get to see it, no password hash or codes
+#[derive(Serialize)]
+pub struct AdminUser {
+    pub id: i64,
+    pub username: String,
+    pub email: String,
+    #[serde(with = "format::date_format2")]
+    pub created_at: NaiveDateTime,
+    pub allow_login: bool,
+    pub is_online: bool,
+    pub is_staff: bool,
+    pub is_superuser: bool,
+    pub is_bot: bool,
+    pub verified: bool,
+    pub suspended: bool,
+    #[serde(with = "format::date_format2::option")]
+    pub suspended_until: Option<NaiveDateTime>,
+    pub suspension_reason: Option<String>,
+}
+
+// Made by an admin, so there is no email code to verify
+pub async fn create_user(
+    username: String,
+    email: String,
+    password_hash: String,
+    is_staff: bool,
+    is_superuser: bool,
+    pool: &PgPool,
+) -> sqlx::Result<i64> {
+    match sqlx::query!(
+        r#"
+INSERT INTO users (username, email, password, is_staff, is_superuser)
+VALUES ($1, $2, $3, $4, $5)
+RETURNING id
+        "#,
+        username,
+        email,
+        password_hash,
+        is_staff,
+        is_superuser
+    )
+    .fetch_one(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.id),
+        Err(err) => Err(err),
+    }
+}
+
+// by id, email or username
+pub async fn find_user(key: &str, pool: &PgPool) -> sqlx::Result<Option<User>> {
+    sqlx::query_as!(
+        User,
+        r#"
+SELECT * FROM users
+WHERE id::text = $1 OR lower(email) = lower($1) OR username = $1
+LIMIT 1
+        "#,
+        key
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn set_roles(
+    user_id: i64,
+    is_staff: bool,
+    is_superuser: bool,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+UPDATE users SET is_staff = $2, is_superuser = $3 WHERE id = $1
+        "#,
+        user_id,
+        is_staff,
+        is_superuser
+    )
+    .execute(pool)
+    .await
+}
+
+#[derive(Serialize)]
+pub struct GuildSummary {
+    pub id: Uuid,
+    pub name: String,
+    pub creator_id: i64,
+    pub members: i64,
+    pub channels: i64,
+}
+
+// The filters below are all optional, None matches everything. q is a substring.
+
+pub async fn list_guilds(
+    q: Option<String>,
+    limit: i64,
+    offset: i64,
+    pool: &PgPool,
+) -> sqlx::Result<Vec<GuildSummary>> {
+    sqlx::query_as!(
+        GuildSummary,
+        r#"
+SELECT g.id, g.name, g.creator_id,
+    (SELECT COUNT(*) FROM member m WHERE m.guild_id = g.id) as "members!",
+    (SELECT COUNT(*) FROM channel c WHERE c.guild_id = g.id) as "channels!"
+FROM guild g
+WHERE ($1::text IS NULL OR g.name ILIKE '%' || $1 || '%')
+ORDER BY g.created_at
+LIMIT $2 OFFSET $3
+        "#,
+        q,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn list_users(
+    q: Option<String>,
+    staff: Option<bool>,
+    suspended: Option<bool>,
+    bot: Option<bool>,
+    limit: i64,
+    offset: i64,
+    pool: &PgPool,
+) -> sqlx::Result<Vec<AdminUser>> {
+    sqlx::query_as!(
+        AdminUser,
+        r#"
+SELECT id, username, email, created_at, allow_login, is_online, is_staff, is_superuser, is_bot,
+    code IS NULL as "verified!",
+    NOT allow_login AND (suspended_until IS NULL OR suspended_until > NOW()) as "suspended!",
+    suspended_until, suspension_reason
+FROM users
+WHERE ($1::text IS NULL OR username ILIKE '%' || $1 || '%' OR email ILIKE '%' || $1 || '%')
+    AND ($2::bool IS NULL OR (is_staff OR is_superuser) = $2)
+    AND ($3::bool IS NULL
+        OR (NOT allow_login AND (suspended_until IS NULL OR suspended_until > NOW())) = $3)
+    AND ($4::bool IS NULL OR is_bot = $4)
+ORDER BY id
+LIMIT $5 OFFSET $6
+        "#,
+        q,
+        staff,
+        suspended,
+        bot,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_user(user_id: i64, pool: &PgPool) -> sqlx::Result<Option<AdminUser>> {
+    sqlx::query_as!(
+        AdminUser,
+        r#"
+SELECT id, username, email, created_at, allow_login, is_online, is_staff, is_superuser, is_bot,
+    code IS NULL as "verified!",
+    NOT allow_login AND (suspended_until IS NULL OR suspended_until > NOW()) as "suspended!",
+    suspended_until, suspension_reason
+FROM users
+WHERE id = $1
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn list_sessions(
+    user_id: Option<i64>,
+    limit: i64,
+    offset: i64,
+    pool: &PgPool,
+) -> sqlx::Result<Vec<UserSession>> {
+    sqlx::query_as!(
+        UserSession,
+        r#"
+SELECT * FROM user_sessions
+WHERE ($1::bigint IS NULL OR userid = $1)
+ORDER BY last_login DESC
+LIMIT $2 OFFSET $3
+        "#,
+        user_id,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// whose session it was, None if there was no such session
+pub async fn delete_session(session_id: Uuid, pool: &PgPool) -> sqlx::Result<Option<i64>> {
+    match sqlx::query!(
+        r#"
+DELETE FROM user_sessions WHERE session_id = $1 RETURNING userid
+        "#,
+        session_id
+    )
+    .fetch_optional(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.map(|rec| rec.userid)),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn list_channels(
+    guild_id: Option<Uuid>,
+    q: Option<String>,
+    limit: i64,
+    offset: i64,
+    pool: &PgPool,
+) -> sqlx::Result<Vec<Channel>> {
+    sqlx::query_as!(
+        Channel,
+        r#"
+SELECT * FROM channel
+WHERE ($1::uuid IS NULL OR guild_id = $1)
+    AND ($2::text IS NULL OR name ILIKE '%' || $2 || '%')
+ORDER BY created_at
+LIMIT $3 OFFSET $4
+        "#,
+        guild_id,
+        q,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// newest first
+pub async fn list_messages(
+    channel_id: Option<Uuid>,
+    author_id: Option<i64>,
+    q: Option<String>,
+    limit: i64,
+    offset: i64,
+    pool: &PgPool,
+) -> sqlx::Result<Vec<Message>> {
+    sqlx::query_as!(
+        Message,
+        r#"
+SELECT * FROM message
+WHERE ($1::uuid IS NULL OR channel_id = $1)
+    AND ($2::bigint IS NULL OR author_id = $2)
+    AND ($3::text IS NULL OR content ILIKE '%' || $3 || '%')
+ORDER BY created_at DESC
+LIMIT $4 OFFSET $5
+        "#,
+        channel_id,
+        author_id,
+        q,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,100 @@
+use sqlx::{postgres::PgQueryResult, types::Uuid, PgPool};
+
+// Remembers the device and says whether it is new. The very first device an
+// account logs in from isn't new to anyone, so that one is false too.
+pub async fn see_device(user_id: i64, fingerprint: &str, pool: &PgPool) -> sqlx::Result<bool> {
+    let mut tx = pool.begin().await?;
+    let any = sqlx::query!(
+        r#"
+SELECT EXISTS (SELECT 1 FROM known_device WHERE user_id = $1) as "any!"
+        "#,
+        user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    let seen = sqlx::query!(
+        r#"
+INSERT INTO known_device (user_id, fingerprint) VALUES ($1, $2)
+ON CONFLICT (user_id, fingerprint) DO UPDATE SET last_seen = NOW()
+RETURNING (xmax = 0) as "inserted!"
+        "#,
+        user_id,
+        fingerprint
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(any.any && seen.inserted)
+}
+
+pub async fn create_alert(
+    user_id: i64,
+    session_id: Uuid,
+    token_hash: String,
+    minutes: i32,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+INSERT INTO login_alert (token_hash, user_id, session_id, expires_at)
+VALUES ($1, $2, $3, NOW() + make_interval(mins => $4))
+        "#,
+        token_hash,
+        user_id,
+        session_id,
+        minutes
+    )
+    .execute(pool)
+    .await
+}
+
+// Whether the link still works, without using it up
+pub async fn alert_exists(token_hash: String, pool: &PgPool) -> sqlx::Result<bool> {
+    match sqlx::query!(
+        r#"
+SELECT EXISTS (SELECT 1 FROM login_alert WHERE token_hash = $1 AND expires_at > NOW()) as "exists!"
+        "#,
+        token_hash
+    )
+    .fetch_one(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.exists),
+        Err(err) => Err(err),
+    }
+}
+
+// (user_id, email) of the alert, which can only be used once
+pub async fn use_alert(token_hash: String, pool: &PgPool) -> sqlx::Result<Option<(i64, String)>> {
+    match sqlx::query!(
+        r#"
+WITH used AS (
+    DELETE FROM login_alert WHERE token_hash = $1 AND expires_at > NOW()
+    RETURNING user_id
+)
+SELECT u.id, u.email FROM users u INNER JOIN used ON used.user_id = u.id
+        "#,
+        token_hash
+    )
+    .fetch_optional(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.map(|rec| (rec.id, rec.email))),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn get_email(user_id: i64, pool: &PgPool) -> sqlx::Result<String> {
+    match sqlx::query!(
+        r#"
+SELECT email FROM users WHERE id = $1
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.email),
+        Err(err) => Err(err),
+    }
+}
@@ -0,0 +1,107 @@
+use sqlx::{types::Uuid, PgPool};
+
+use super::{account, models::Application};
+
+// The bot user comes with the application, named after it. It has no password and an
+// address nobody can receive mail at, so the only way in is the token.
+pub async fn create_application(
+    owner_id: i64,
+    name: String,
+    description: Option<String>,
+    pool: &PgPool,
+) -> sqlx::Result<Application> {
+    let mut tx = pool.begin().await?;
+    let bot = sqlx::query!(
+        r#"
+INSERT INTO users ( username, email, password, is_bot )
+VALUES ( $1, $2, '', TRUE )
+RETURNING id
+        "#,
+        name,
+        format!("bot-{}@bots.invalid", Uuid::new_v4())
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    let app = sqlx::query_as!(
+        Application,
+        r#"
+INSERT INTO application ( owner_id, bot_id, name, description )
+VALUES ( $1, $2, $3, $4 )
+RETURNING *
+        "#,
+        owner_id,
+        bot.id,
+        name,
+        description
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(app)
+}
+
+pub async fn list_applications(owner_id: i64, pool: &PgPool) -> sqlx::Result<Vec<Application>> {
+    sqlx::query_as!(
+        Application,
+        r#"
+SELECT * FROM application WHERE owner_id = $1 ORDER BY created_at
+        "#,
+        owner_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_application(id: i64, pool: &PgPool) -> sqlx::Result<Option<Application>> {
+    sqlx::query_as!(
+        Application,
+        r#"
+SELECT * FROM application WHERE id = $1
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// Deleting the bot takes the application, its sessions and memberships with it, its DMs go
+// to the ghost. Returns the guilds it was in, None if it isn't the owner's application.
+pub async fn delete_application(
+    id: i64,
+    owner_id: i64,
+    pool: &PgPool,
+) -> sqlx::Result<Option<Vec<Uuid>>> {
+    let mut tx = pool.begin().await?;
+    let Some(app) = sqlx::query!(
+        r#"
+SELECT bot_id FROM application WHERE id = $1 AND owner_id = $2
+        "#,
+        id,
+        owner_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        return Ok(None);
+    };
+    let ghost = account::ghost(&mut tx).await?;
+    account::ghost_dms(&[app.bot_id], ghost, &mut tx).await?;
+    let left = sqlx::query!(
+        r#"
+DELETE FROM member WHERE user_id = $1 RETURNING guild_id
+        "#,
+        app.bot_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    sqlx::query!(
+        r#"
+DELETE FROM users WHERE id = $1
+        "#,
+        app.bot_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Some(left.into_iter().map(|rec| rec.guild_id).collect()))
+}
@@ -0,0 +1,21 @@
+use sqlx::{postgres::PgQueryResult, PgPool};
+
+pub async fn record(
+    user_id: Option<i64>,
+    event: &str,
+    ip: Option<String>,
+    detail: String,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+INSERT INTO audit_log (user_id, event, ip, detail) VALUES ($1, $2, $3, $4)
+        "#,
+        user_id,
+        event,
+        ip,
+        detail
+    )
+    .execute(pool)
+    .await
+}
@@ -0,0 +1,65 @@
+use sqlx::{postgres::PgQueryResult, types::Uuid, PgPool};
+
+pub async fn create_token(
+    session_id: Uuid,
+    token_hash: String,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+INSERT INTO session_token (token_hash, session_id) VALUES ($1, $2)
+        "#,
+        token_hash,
+        session_id
+    )
+    .execute(pool)
+    .await
+}
+
+// (user_id, session_id) of the session the token belongs to. Using a token counts as
+// activity, so last_login moves along (at most once an hour) and the nightly wipe of
+// idle sessions leaves tokens that are still in use alone.
+pub async fn session_for_token(
+    token_hash: String,
+    pool: &PgPool,
+) -> sqlx::Result<Option<(i64, Uuid)>> {
+    match sqlx::query!(
+        r#"
+WITH found AS (
+    SELECT us.userid, us.session_id
+    FROM session_token st
+    INNER JOIN user_sessions us ON us.session_id = st.session_id
+    WHERE st.token_hash = $1
+), touched AS (
+    UPDATE user_sessions SET last_login = NOW()
+    WHERE session_id = (SELECT session_id FROM found)
+        AND last_login < NOW() - INTERVAL '1 hour'
+)
+SELECT userid as "userid!", session_id as "session_id!" FROM found
+        "#,
+        token_hash
+    )
+    .fetch_optional(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.map(|rec| (rec.userid, rec.session_id))),
+        Err(err) => Err(err),
+    }
+}
+
+// a cookie outlives its session when the session is revoked from somewhere else
+pub async fn session_exists(user_id: i64, session_id: Uuid, pool: &PgPool) -> sqlx::Result<bool> {
+    match sqlx::query!(
+        r#"
+SELECT EXISTS (SELECT 1 FROM user_sessions WHERE session_id = $1 AND userid = $2) as "exists!"
+        "#,
+        session_id,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.exists),
+        Err(err) => Err(err),
+    }
+}
@@ -0,0 +1,56 @@
+use sqlx::{postgres::PgQueryResult, PgPool};
+
+pub async fn notify(channel: &str, payload: String, pool: &PgPool) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+SELECT pg_notify($1, $2)
+        "#,
+        channel,
+        payload
+    )
+    .execute(pool)
+    .await
+}
+
+pub async fn store_event(payload: String, pool: &PgPool) -> sqlx::Result<i64> {
+    match sqlx::query!(
+        r#"
+INSERT INTO bus_event (payload)
+VALUES ($1)
+RETURNING id
+        "#,
+        payload
+    )
+    .fetch_one(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.id),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn get_event(id: i64, pool: &PgPool) -> sqlx::Result<String> {
+    match sqlx::query!(
+        r#"
+SELECT payload FROM bus_event WHERE id = $1
+        "#,
+        id
+    )
+    .fetch_one(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.payload),
+        Err(err) => Err(err),
+    }
+}
+
+// every node has read them by then
+pub async fn prune_events(pool: &PgPool) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+DELETE FROM bus_event WHERE created_at < (NOW() - INTERVAL '1 hour')
+        "#
+    )
+    .execute(pool)
+    .await
+}
@@ -0,0 +1,44 @@
+use sqlx::{postgres::PgQueryResult, types::Uuid, PgPool};
+
+pub async fn create(expected: String, seconds: f64, pool: &PgPool) -> sqlx::Result<Uuid> {
+    match sqlx::query!(
+        r#"
+INSERT INTO captcha (expected, expires_at) VALUES ($1, NOW() + make_interval(secs => $2))
+RETURNING id
+        "#,
+        expected,
+        seconds
+    )
+    .fetch_one(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.id),
+        Err(err) => Err(err),
+    }
+}
+
+// the expected answer, and the challenge is gone whether or not it gets answered right
+pub async fn take(id: Uuid, pool: &PgPool) -> sqlx::Result<Option<String>> {
+    match sqlx::query!(
+        r#"
+DELETE FROM captcha WHERE id = $1 RETURNING expected, expires_at > NOW() as "valid!"
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.filter(|rec| rec.valid).map(|rec| rec.expected)),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn prune(pool: &PgPool) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+DELETE FROM captcha WHERE expires_at < NOW()
+        "#
+    )
+    .execute(pool)
+    .await
+}
@@ -0,0 +1,100 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::{postgres::PgQueryResult, types::Uuid, PgPool};
+
+use crate::format;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GuildBan {
+    pub guild_id: Uuid,
+    pub user_id: i64,
+    pub moderator_id: Option<i64>,
+    pub reason: Option<String>,
+    #[serde(with = "format::date_format2")]
+    pub created_at: NaiveDateTime,
+    // None lasts until someone unbans
+    #[serde(with = "format::date_format2::option")]
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+// a ban that ran out doesn't count anymore
+pub async fn active(user_id: i64, guild_id: Uuid, pool: &PgPool) -> sqlx::Result<Option<GuildBan>> {
+    sqlx::query_as!(
+        GuildBan,
+        r#"
+SELECT * FROM guild_ban
+WHERE user_id = $1 AND guild_id = $2 AND (expires_at IS NULL OR expires_at > NOW())
+        "#,
+        user_id,
+        guild_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// Banning again replaces the old ban. minutes None is permanent.
+pub async fn ban(
+    guild_id: Uuid,
+    user_id: i64,
+    moderator_id: i64,
+    reason: Option<String>,
+    minutes: Option<i32>,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+INSERT INTO guild_ban (guild_id, user_id, moderator_id, reason, expires_at)
+VALUES ($1, $2, $3, $4, NOW() + make_interval(mins => $5))
+ON CONFLICT (guild_id, user_id) DO UPDATE
+SET moderator_id = $3, reason = $4, created_at = NOW(), expires_at = NOW() + make_interval(mins => $5)
+        "#,
+        guild_id,
+        user_id,
+        moderator_id,
+        reason,
+        minutes
+    )
+    .execute(pool)
+    .await
+}
+
+pub async fn unban(guild_id: Uuid, user_id: i64, pool: &PgPool) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+DELETE FROM guild_ban WHERE guild_id = $1 AND user_id = $2
+        "#,
+        guild_id,
+        user_id
+    )
+    .execute(pool)
+    .await
+}
+
+pub struct PurgedMessage {
+    pub id: Uuid,
+    pub channel_id: Uuid,
+}
+
+// What the user wrote in the guild's channels over the last minutes, gone
+pub async fn purge_messages(
+    guild_id: Uuid,
+    user_id: i64,
+    minutes: i32,
+    pool: &PgPool,
+) -> sqlx::Result<Vec<PurgedMessage>> {
+    sqlx::query_as!(
+        PurgedMessage,
+        r#"
+DELETE FROM message
+WHERE author_id = $2
+    AND created_at > NOW() - make_interval(mins => $3)
+    AND channel_id IN (SELECT id FROM channel WHERE guild_id = $1)
+RETURNING id, channel_id
+        "#,
+        guild_id,
+        user_id,
+        minutes
+    )
+    .fetch_all(pool)
+    .await
+}
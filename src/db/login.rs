@@ -37,3 +37,18 @@ SELECT id, password FROM users WHERE email = $1
         Err(err) => Err(err),
     }
 }
+
+pub async fn get_password(user_id: i64, pool: &PgPool) -> sqlx::Result<String> {
+    match sqlx::query!(
+        r#"
+SELECT password FROM users WHERE id = $1
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.password),
+        Err(err) => Err(err),
+    }
+}
@@ -0,0 +1,210 @@
+use sqlx::{postgres::PgQueryResult, PgPool};
+
+pub struct Totp {
+    pub secret: String,
+    pub enabled: bool,
+    pub last_step: i64,
+}
+
+pub async fn get_totp(user_id: i64, pool: &PgPool) -> sqlx::Result<Option<Totp>> {
+    sqlx::query_as!(
+        Totp,
+        r#"
+SELECT secret, enabled, last_step FROM user_totp WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn is_enabled(user_id: i64, pool: &PgPool) -> sqlx::Result<bool> {
+    match get_totp(user_id, pool).await {
+        Ok(totp) => Ok(totp.is_some_and(|t| t.enabled)),
+        Err(err) => Err(err),
+    }
+}
+
+// a new enrollment replaces one that was never confirmed, never an enabled one
+pub async fn set_pending_secret(
+    user_id: i64,
+    secret: String,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+INSERT INTO user_totp (user_id, secret)
+VALUES ($1, $2)
+ON CONFLICT (user_id) DO UPDATE SET secret = $2, last_step = 0 WHERE user_totp.enabled = false
+        "#,
+        user_id,
+        secret
+    )
+    .execute(pool)
+    .await
+}
+
+// only moves forward, so two requests can't both spend the same code
+pub async fn use_step(user_id: i64, step: i64, pool: &PgPool) -> sqlx::Result<bool> {
+    match sqlx::query!(
+        r#"
+UPDATE user_totp SET last_step = $2 WHERE user_id = $1 AND last_step < $2
+        "#,
+        user_id,
+        step
+    )
+    .execute(pool)
+    .await
+    {
+        Ok(res) => Ok(res.rows_affected() == 1),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn enable(
+    user_id: i64,
+    recovery_hashes: Vec<String>,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        r#"
+UPDATE user_totp SET enabled = true WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    let res = replace_recovery_codes(user_id, recovery_hashes, &mut tx).await?;
+    tx.commit().await?;
+    Ok(res)
+}
+
+async fn replace_recovery_codes(
+    user_id: i64,
+    recovery_hashes: Vec<String>,
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+DELETE FROM recovery_code WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .execute(&mut **tx)
+    .await?;
+    sqlx::query!(
+        r#"
+INSERT INTO recovery_code (user_id, code_hash)
+SELECT $1, * FROM UNNEST($2::text[])
+        "#,
+        user_id,
+        &recovery_hashes
+    )
+    .execute(&mut **tx)
+    .await
+}
+
+pub async fn disable(user_id: i64, pool: &PgPool) -> sqlx::Result<PgQueryResult> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        r#"
+DELETE FROM recovery_code WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    let res = sqlx::query!(
+        r#"
+DELETE FROM user_totp WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(res)
+}
+
+pub async fn use_recovery_code(
+    user_id: i64,
+    code_hash: String,
+    pool: &PgPool,
+) -> sqlx::Result<bool> {
+    match sqlx::query!(
+        r#"
+UPDATE recovery_code SET used_at = NOW()
+WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+        "#,
+        user_id,
+        code_hash
+    )
+    .execute(pool)
+    .await
+    {
+        Ok(res) => Ok(res.rows_affected() == 1),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn create_ticket(
+    user_id: i64,
+    token_hash: String,
+    minutes: i32,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    // clean up whatever expired while we're here
+    sqlx::query!(
+        r#"
+DELETE FROM mfa_ticket WHERE expires_at < NOW()
+        "#
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query!(
+        r#"
+INSERT INTO mfa_ticket (token_hash, user_id, expires_at)
+VALUES ($1, $2, NOW() + make_interval(mins => $3))
+        "#,
+        token_hash,
+        user_id,
+        minutes
+    )
+    .execute(pool)
+    .await
+}
+
+// Counts the try and returns the ticket's user, None once it expired or ran out of tries
+pub async fn ticket_attempt(
+    token_hash: String,
+    max_attempts: i32,
+    pool: &PgPool,
+) -> sqlx::Result<Option<i64>> {
+    match sqlx::query!(
+        r#"
+UPDATE mfa_ticket SET attempts = attempts + 1
+WHERE token_hash = $1 AND expires_at > NOW() AND attempts < $2
+RETURNING user_id
+        "#,
+        token_hash,
+        max_attempts
+    )
+    .fetch_optional(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.map(|rec| rec.user_id)),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn delete_ticket(token_hash: String, pool: &PgPool) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+DELETE FROM mfa_ticket WHERE token_hash = $1
+        "#,
+        token_hash
+    )
+    .execute(pool)
+    .await
+}
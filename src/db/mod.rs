@@ -1,9 +1,27 @@
+pub mod account;
+pub mod admin;
+pub mod alerts;
+pub mod applications;
+pub mod audit;
+pub mod auth;
+pub mod bus;
+pub mod captcha;
 pub mod channels;
+pub mod guild_ban;
 pub mod guilds;
 pub mod login;
 pub mod logout;
+pub mod mfa;
 pub mod models;
+pub mod oidc;
+pub mod password;
+pub mod presence;
+pub mod profile;
+pub mod sessions;
 pub mod signup;
 pub mod start;
+pub mod suspension;
+pub mod throttle;
 pub mod verify;
+pub mod webauthn;
 pub mod ws_session;
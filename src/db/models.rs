@@ -8,7 +8,7 @@ use sqlx::types::{
     Uuid
 };
 
-use crate::{format, messages::UserFetchType};
+use crate::{format, messages::{PresenceUpdateType, UserFetchType}};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Message {
@@ -36,7 +36,35 @@ pub struct User {
     pub is_online: bool,
     pub is_staff: bool,
     pub is_superuser: bool,
-    pub code: Option<i64>
+    pub code: Option<i64>,
+    pub status: String,
+    pub custom_status: Option<String>,
+    #[serde(skip)]
+    pub code_expires_at: Option<NaiveDateTime>,
+    #[serde(skip)]
+    pub code_sent_at: Option<NaiveDateTime>,
+    #[serde(skip)]
+    pub code_attempts: i32,
+    #[serde(skip)]
+    pub verify_token: Option<String>,
+    pub is_bot: bool,
+    #[serde(skip)]
+    pub username_changed_at: Option<NaiveDateTime>,
+    #[serde(skip)]
+    pub suspended_until: Option<NaiveDateTime>,
+    #[serde(skip)]
+    pub suspension_reason: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Application {
+    pub id: i64,
+    pub owner_id: i64,
+    pub bot_id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(with = "format::date_format2")]
+    pub created_at: NaiveDateTime,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -106,7 +134,8 @@ pub struct MemberClient {
     pub guild_id: Uuid,
     pub user_id: i64,
     // super scuffed ws message referencing
-    pub user: UserFetchType
+    pub user: UserFetchType,
+    pub presence: PresenceUpdateType
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
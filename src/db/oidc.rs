@@ -0,0 +1,90 @@
+use sqlx::{postgres::PgQueryResult, PgPool};
+
+pub async fn get_linked_user(
+    provider: &str,
+    subject: &str,
+    pool: &PgPool,
+) -> sqlx::Result<Option<i64>> {
+    match sqlx::query!(
+        r#"
+SELECT user_id FROM user_identity WHERE provider = $1 AND subject = $2
+        "#,
+        provider,
+        subject
+    )
+    .fetch_optional(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.map(|rec| rec.user_id)),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn link(
+    user_id: i64,
+    provider: &str,
+    subject: &str,
+    email: Option<String>,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+INSERT INTO user_identity (user_id, provider, subject, email)
+VALUES ($1, $2, $3, $4)
+        "#,
+        user_id,
+        provider,
+        subject,
+        email
+    )
+    .execute(pool)
+    .await
+}
+
+pub async fn username_taken(username: &str, pool: &PgPool) -> sqlx::Result<bool> {
+    if super::account::reserved_username(username) {
+        return Ok(true);
+    }
+    match sqlx::query!(
+        r#"
+SELECT EXISTS (SELECT 1 FROM users WHERE username = $1) as "taken!"
+        "#,
+        username
+    )
+    .fetch_one(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.taken),
+        Err(err) => Err(err),
+    }
+}
+
+// No password, they log in through the provider (or set one with a password reset).
+// code is None when the provider already vouched for the email.
+pub async fn create_user(
+    username: String,
+    email: String,
+    code: Option<i64>,
+    token_hash: Option<String>,
+    minutes: i32,
+    pool: &PgPool,
+) -> sqlx::Result<i64> {
+    match sqlx::query!(
+        r#"
+INSERT INTO users ( username, email, password, code, verify_token, code_sent_at, code_expires_at )
+VALUES ( $1, $2, '', $3, $4, NOW(), NOW() + make_interval(mins => $5) )
+RETURNING id
+        "#,
+        username,
+        email,
+        code,
+        token_hash,
+        minutes
+    )
+    .fetch_one(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.id),
+        Err(err) => Err(err),
+    }
+}
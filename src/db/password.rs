@@ -0,0 +1,90 @@
+use sqlx::{postgres::PgQueryResult, PgPool};
+
+pub async fn get_user_id_by_email(email: String, pool: &PgPool) -> sqlx::Result<i64> {
+    match sqlx::query!(
+        r#"
+SELECT id FROM users WHERE email = $1
+        "#,
+        email
+    )
+    .fetch_one(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.id),
+        Err(err) => Err(err),
+    }
+}
+
+// a new request replaces any reset still pending for the user
+pub async fn create_reset(
+    user_id: i64,
+    token_hash: String,
+    minutes: i32,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+DELETE FROM password_reset WHERE user_id = $1 AND used_at IS NULL
+        "#,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query!(
+        r#"
+INSERT INTO password_reset (user_id, token_hash, expires_at)
+VALUES ($1, $2, NOW() + make_interval(mins => $3))
+        "#,
+        user_id,
+        token_hash,
+        minutes
+    )
+    .execute(pool)
+    .await
+}
+
+// marks the token as used and returns its user, in one go so it can't be used twice
+pub async fn use_reset(token_hash: String, pool: &PgPool) -> sqlx::Result<i64> {
+    match sqlx::query!(
+        r#"
+UPDATE password_reset
+SET used_at = NOW()
+WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+RETURNING user_id
+        "#,
+        token_hash
+    )
+    .fetch_one(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.user_id),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn set_password(
+    user_id: i64,
+    password_hash: String,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+UPDATE users SET password = $1 WHERE id = $2
+        "#,
+        password_hash,
+        user_id
+    )
+    .execute(pool)
+    .await
+}
+
+pub async fn revoke_sessions(user_id: i64, pool: &PgPool) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+DELETE FROM user_sessions WHERE userid = $1
+        "#,
+        user_id
+    )
+    .execute(pool)
+    .await
+}
@@ -0,0 +1,122 @@
+use sqlx::{postgres::PgQueryResult, types::Uuid, PgPool};
+
+pub async fn add_session(
+    node_id: Uuid,
+    conn_id: i64,
+    user_id: i64,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+INSERT INTO gateway_session (node_id, conn_id, user_id) VALUES ($1, $2, $3)
+        "#,
+        node_id,
+        conn_id,
+        user_id
+    )
+    .execute(pool)
+    .await
+}
+
+pub async fn remove_session(
+    node_id: Uuid,
+    conn_id: i64,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+DELETE FROM gateway_session WHERE node_id = $1 AND conn_id = $2
+        "#,
+        node_id,
+        conn_id
+    )
+    .execute(pool)
+    .await
+}
+
+pub async fn set_idle(
+    node_id: Uuid,
+    conn_id: i64,
+    idle: bool,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+UPDATE gateway_session SET idle = $3 WHERE node_id = $1 AND conn_id = $2
+        "#,
+        node_id,
+        conn_id,
+        idle
+    )
+    .execute(pool)
+    .await
+}
+
+// What the user picked and the idle flag of each of their live sessions, whichever node they're on
+pub struct Live {
+    pub status: String,
+    pub custom_status: Option<String>,
+    pub idle: Vec<bool>,
+}
+
+pub async fn live(user_id: i64, stale_secs: f64, pool: &PgPool) -> sqlx::Result<Live> {
+    match sqlx::query!(
+        r#"
+SELECT u.status, u.custom_status,
+    COALESCE(ARRAY_AGG(g.idle) FILTER (WHERE g.user_id IS NOT NULL), '{}') as "idle!"
+FROM users u
+LEFT JOIN gateway_session g ON g.user_id = u.id AND g.seen_at > NOW() - make_interval(secs => $2)
+WHERE u.id = $1
+GROUP BY u.id
+        "#,
+        user_id,
+        stale_secs
+    )
+    .fetch_one(pool)
+    .await
+    {
+        Ok(rec) => Ok(Live {
+            status: rec.status,
+            custom_status: rec.custom_status,
+            idle: rec.idle,
+        }),
+        Err(err) => Err(err),
+    }
+}
+
+// this node is still around
+pub async fn touch(node_id: Uuid, pool: &PgPool) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+UPDATE gateway_session SET seen_at = NOW() WHERE node_id = $1
+        "#,
+        node_id
+    )
+    .execute(pool)
+    .await
+}
+
+// Drops the sessions of nodes that stopped touching theirs. Returns the users that left
+// with them, the ones without a live session anywhere else. Only one node gets each.
+pub async fn sweep(stale_secs: f64, pool: &PgPool) -> sqlx::Result<Vec<i64>> {
+    match sqlx::query!(
+        r#"
+WITH gone AS (
+    DELETE FROM gateway_session WHERE seen_at <= NOW() - make_interval(secs => $1)
+    RETURNING user_id
+)
+SELECT DISTINCT gone.user_id FROM gone
+WHERE NOT EXISTS (
+    SELECT 1 FROM gateway_session g
+    WHERE g.user_id = gone.user_id AND g.seen_at > NOW() - make_interval(secs => $1)
+)
+        "#,
+        stale_secs
+    )
+    .fetch_all(pool)
+    .await
+    {
+        Ok(recs) => Ok(recs.into_iter().map(|rec| rec.user_id).collect()),
+        Err(err) => Err(err),
+    }
+}
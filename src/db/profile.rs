@@ -0,0 +1,121 @@
+use sqlx::{postgres::PgQueryResult, PgPool};
+
+// false while the last change is still within cooldown_days
+pub async fn update_username(
+    user_id: i64,
+    username: String,
+    cooldown_days: i32,
+    pool: &PgPool,
+) -> sqlx::Result<bool> {
+    match sqlx::query!(
+        r#"
+UPDATE users SET username = $2, username_changed_at = NOW()
+WHERE id = $1
+AND (username_changed_at IS NULL OR username_changed_at < NOW() - make_interval(days => $3))
+        "#,
+        user_id,
+        username,
+        cooldown_days
+    )
+    .execute(pool)
+    .await
+    {
+        Ok(res) => Ok(res.rows_affected() == 1),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn update_description(
+    user_id: i64,
+    description: Option<String>,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+UPDATE users SET description = $2 WHERE id = $1
+        "#,
+        user_id,
+        description
+    )
+    .execute(pool)
+    .await
+}
+
+pub async fn update_avatar(
+    user_id: i64,
+    profile: Option<String>,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+UPDATE users SET profile = $2 WHERE id = $1
+        "#,
+        user_id,
+        profile
+    )
+    .execute(pool)
+    .await
+}
+
+// a new request replaces the one pending
+pub async fn create_email_change(
+    user_id: i64,
+    new_email: String,
+    token_hash: String,
+    minutes: i32,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+INSERT INTO email_change (user_id, new_email, token_hash, expires_at)
+VALUES ($1, $2, $3, NOW() + make_interval(mins => $4))
+ON CONFLICT (user_id) DO UPDATE
+SET new_email = $2, token_hash = $3, expires_at = NOW() + make_interval(mins => $4)
+        "#,
+        user_id,
+        new_email,
+        token_hash,
+        minutes
+    )
+    .execute(pool)
+    .await
+}
+
+// (user_id, old email, new email). Errors if the address got taken in the meantime.
+pub async fn confirm_email_change(
+    token_hash: String,
+    pool: &PgPool,
+) -> sqlx::Result<Option<(i64, String, String)>> {
+    let mut tx = pool.begin().await?;
+    let Some(change) = sqlx::query!(
+        r#"
+DELETE FROM email_change WHERE token_hash = $1 AND expires_at > NOW()
+RETURNING user_id, new_email
+        "#,
+        token_hash
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    else {
+        return Ok(None);
+    };
+    let old = sqlx::query!(
+        r#"
+SELECT email FROM users WHERE id = $1
+        "#,
+        change.user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    sqlx::query!(
+        r#"
+UPDATE users SET email = $2 WHERE id = $1
+        "#,
+        change.user_id,
+        change.new_email
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(Some((change.user_id, old.email, change.new_email)))
+}
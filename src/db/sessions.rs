@@ -0,0 +1,65 @@
+use sqlx::{postgres::PgQueryResult, types::Uuid, PgPool};
+
+use super::models::UserSession;
+
+pub async fn list_sessions(user_id: i64, pool: &PgPool) -> sqlx::Result<Vec<UserSession>> {
+    sqlx::query_as!(
+        UserSession,
+        r#"
+SELECT * FROM user_sessions WHERE userid = $1 ORDER BY last_login DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// false if it wasn't one of the user's sessions
+pub async fn delete_session(user_id: i64, session_id: Uuid, pool: &PgPool) -> sqlx::Result<bool> {
+    match sqlx::query!(
+        r#"
+DELETE FROM user_sessions WHERE session_id = $1 AND userid = $2
+        "#,
+        session_id,
+        user_id
+    )
+    .execute(pool)
+    .await
+    {
+        Ok(res) => Ok(res.rows_affected() == 1),
+        Err(err) => Err(err),
+    }
+}
+
+// everything but keep, returns what was deleted so the sockets can be closed too
+pub async fn delete_other_sessions(
+    user_id: i64,
+    keep: Uuid,
+    pool: &PgPool,
+) -> sqlx::Result<Vec<Uuid>> {
+    match sqlx::query!(
+        r#"
+DELETE FROM user_sessions WHERE userid = $1 AND session_id <> $2
+RETURNING session_id
+        "#,
+        user_id,
+        keep
+    )
+    .fetch_all(pool)
+    .await
+    {
+        Ok(recs) => Ok(recs.into_iter().map(|rec| rec.session_id).collect()),
+        Err(err) => Err(err),
+    }
+}
+
+// sessions nobody used in a week, cookie or token
+pub async fn wipe_stale(pool: &PgPool) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+DELETE FROM user_sessions WHERE last_login < (NOW() - INTERVAL '7 days')
+        "#
+    )
+    .execute(pool)
+    .await
+}
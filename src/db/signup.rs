@@ -5,6 +5,7 @@ use argon2::{
     Argon2,
 };
 
+#[derive(Clone)]
 pub struct UserAgent {
     pub os: Option<String>,
     pub device: Option<String>,
@@ -12,6 +13,16 @@ pub struct UserAgent {
     pub original: String,
 }
 
+// What signup knows about an account before it exists
+pub struct NewUser {
+    pub username: String,
+    pub email: String,
+    pub password_hash: String,
+    pub code: i64,
+    pub token_hash: String,
+    pub minutes: i32,
+}
+
 pub fn create_password(password: String) -> Result<String, Error> {
     let salt = SaltString::generate(&mut OsRng);
     let argon2 = Argon2::default();
@@ -22,23 +33,22 @@ pub fn create_password(password: String) -> Result<String, Error> {
 }
 
 pub async fn create_user(
-    username: String,
-    email: String,
-    code: i64,
-    password_hash: String,
+    user: NewUser,
     uag: UserAgent,
     pool: &PgPool,
 ) -> sqlx::Result<(Uuid, i64)> {
     match sqlx::query!(
         r#"
-INSERT INTO users ( username, email, password, code )
-VALUES ( $1, $2, $3, $4 )
+INSERT INTO users ( username, email, password, code, verify_token, code_sent_at, code_expires_at )
+VALUES ( $1, $2, $3, $4, $5, NOW(), NOW() + make_interval(mins => $6) )
 RETURNING id
         "#,
-        username,
-        email,
-        password_hash,
-        code
+        user.username,
+        user.email,
+        user.password_hash,
+        user.code,
+        user.token_hash,
+        user.minutes
     )
     .fetch_one(pool)
     .await
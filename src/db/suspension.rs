@@ -0,0 +1,61 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::{postgres::PgQueryResult, PgPool};
+
+use crate::format;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Suspension {
+    // None lasts until someone lifts it
+    #[serde(with = "format::date_format2::option")]
+    pub until: Option<NaiveDateTime>,
+    pub reason: Option<String>,
+}
+
+// The suspension in effect, one that ran out doesn't count anymore
+pub async fn active(user_id: i64, pool: &PgPool) -> sqlx::Result<Option<Suspension>> {
+    sqlx::query_as!(
+        Suspension,
+        r#"
+SELECT suspended_until as until, suspension_reason as reason
+FROM users
+WHERE id = $1 AND NOT allow_login AND (suspended_until IS NULL OR suspended_until > NOW())
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// minutes None is permanent
+pub async fn suspend(
+    user_id: i64,
+    minutes: Option<i32>,
+    reason: Option<String>,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+UPDATE users
+SET allow_login = FALSE, suspended_until = NOW() + make_interval(mins => $2), suspension_reason = $3
+WHERE id = $1
+        "#,
+        user_id,
+        minutes,
+        reason
+    )
+    .execute(pool)
+    .await
+}
+
+pub async fn lift(user_id: i64, pool: &PgPool) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+UPDATE users SET allow_login = TRUE, suspended_until = NULL, suspension_reason = NULL
+WHERE id = $1
+        "#,
+        user_id
+    )
+    .execute(pool)
+    .await
+}
@@ -0,0 +1,65 @@
+use sqlx::{postgres::PgQueryResult, PgPool};
+
+// seconds left on the lock, if any
+pub async fn locked_for(key: &str, pool: &PgPool) -> sqlx::Result<Option<f64>> {
+    match sqlx::query!(
+        r#"
+SELECT EXTRACT(EPOCH FROM (locked_until - NOW()))::FLOAT8 as "left!"
+FROM login_throttle WHERE key = $1 AND locked_until > NOW()
+        "#,
+        key
+    )
+    .fetch_optional(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.map(|rec| rec.left)),
+        Err(err) => Err(err),
+    }
+}
+
+// Counts a failure and returns the new total. A quiet spell longer than forget_minutes starts over.
+pub async fn record_failure(key: &str, forget_minutes: i32, pool: &PgPool) -> sqlx::Result<i32> {
+    match sqlx::query!(
+        r#"
+INSERT INTO login_throttle (key, failures) VALUES ($1, 1)
+ON CONFLICT (key) DO UPDATE SET
+    failures = CASE
+        WHEN login_throttle.last_failure < NOW() - make_interval(mins => $2) THEN 1
+        ELSE login_throttle.failures + 1
+    END,
+    last_failure = NOW()
+RETURNING failures
+        "#,
+        key,
+        forget_minutes
+    )
+    .fetch_one(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.failures),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn lock(key: &str, seconds: f64, pool: &PgPool) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+UPDATE login_throttle SET locked_until = NOW() + make_interval(secs => $2) WHERE key = $1
+        "#,
+        key,
+        seconds
+    )
+    .execute(pool)
+    .await
+}
+
+pub async fn clear(key: &str, pool: &PgPool) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+DELETE FROM login_throttle WHERE key = $1
+        "#,
+        key
+    )
+    .execute(pool)
+    .await
+}
@@ -15,10 +15,91 @@ SELECT code FROM users WHERE id = $1
     }
 }
 
+pub struct Attempt {
+    pub code: i64,
+    pub attempts: i32,
+    pub expired: bool,
+}
+
+// Counts the guess before anything gets compared so parallel requests can't get extra tries.
+// None means the user is already verified.
+pub async fn attempt(user_id: i64, pool: &PgPool) -> sqlx::Result<Option<Attempt>> {
+    match sqlx::query!(
+        r#"
+UPDATE users SET code_attempts = code_attempts + 1
+WHERE id = $1 AND code IS NOT NULL
+RETURNING code as "code!", code_attempts, code_expires_at < NOW() as "expired!"
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.map(|rec| Attempt {
+            code: rec.code,
+            attempts: rec.code_attempts,
+            expired: rec.expired,
+        })),
+        Err(err) => Err(err),
+    }
+}
+
+// Replaces the code unless the last one went out less than cooldown seconds ago.
+// Returns the email to send it to, None if verified or still cooling down.
+pub async fn new_code(
+    user_id: i64,
+    code: i64,
+    token_hash: String,
+    minutes: i32,
+    cooldown: f64,
+    pool: &PgPool,
+) -> sqlx::Result<Option<String>> {
+    match sqlx::query!(
+        r#"
+UPDATE users
+SET code = $2, verify_token = $3, code_attempts = 0, code_sent_at = NOW(),
+    code_expires_at = NOW() + make_interval(mins => $4)
+WHERE id = $1 AND code IS NOT NULL
+    AND (code_sent_at IS NULL OR code_sent_at < NOW() - make_interval(secs => $5))
+RETURNING email
+        "#,
+        user_id,
+        code,
+        token_hash,
+        minutes,
+        cooldown
+    )
+    .fetch_optional(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.map(|rec| rec.email)),
+        Err(err) => Err(err),
+    }
+}
+
+// magic link, same expiry as the code
+pub async fn verify_token(token_hash: String, pool: &PgPool) -> sqlx::Result<i64> {
+    match sqlx::query!(
+        r#"
+UPDATE users
+SET code = NULL, verify_token = NULL, code_attempts = 0, code_expires_at = NULL
+WHERE verify_token = $1 AND code_expires_at > NOW()
+RETURNING id
+        "#,
+        token_hash
+    )
+    .fetch_one(pool)
+    .await
+    {
+        Ok(rec) => Ok(rec.id),
+        Err(err) => Err(err),
+    }
+}
+
 pub async fn delete_code(user_id: i64, pool: &PgPool) -> sqlx::Result<PgQueryResult> {
     sqlx::query!(
         r#"
-UPDATE users SET code = NULL WHERE id = $1
+UPDATE users SET code = NULL, verify_token = NULL, code_attempts = 0, code_expires_at = NULL WHERE id = $1
         "#,
         user_id
     )
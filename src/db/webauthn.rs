@@ -0,0 +1,114 @@
+use serde::Serialize;
+use sqlx::{postgres::PgQueryResult, types::chrono::NaiveDateTime, PgPool};
+
+use crate::format;
+
+pub struct StoredPasskey {
+    pub user_id: i64,
+    pub passkey: String,
+}
+
+#[derive(Serialize)]
+pub struct Credential {
+    pub id: i64,
+    pub name: String,
+    #[serde(with = "format::date_format2")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "format::date_format2::option")]
+    pub last_used_at: Option<NaiveDateTime>,
+}
+
+pub async fn get_passkeys(user_id: i64, pool: &PgPool) -> sqlx::Result<Vec<String>> {
+    match sqlx::query!(
+        r#"
+SELECT passkey FROM user_credential WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    {
+        Ok(recs) => Ok(recs.into_iter().map(|rec| rec.passkey).collect()),
+        Err(err) => Err(err),
+    }
+}
+
+pub async fn get_passkey(credential_id: String, pool: &PgPool) -> sqlx::Result<StoredPasskey> {
+    sqlx::query_as!(
+        StoredPasskey,
+        r#"
+SELECT user_id, passkey FROM user_credential WHERE credential_id = $1
+        "#,
+        credential_id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn add_passkey(
+    user_id: i64,
+    credential_id: String,
+    passkey: String,
+    name: String,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+INSERT INTO user_credential (user_id, credential_id, passkey, name)
+VALUES ($1, $2, $3, $4)
+        "#,
+        user_id,
+        credential_id,
+        passkey,
+        name
+    )
+    .execute(pool)
+    .await
+}
+
+pub async fn update_passkey(
+    credential_id: String,
+    passkey: String,
+    sign_count: i64,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+UPDATE user_credential SET passkey = $2, sign_count = $3, last_used_at = NOW()
+WHERE credential_id = $1
+        "#,
+        credential_id,
+        passkey,
+        sign_count
+    )
+    .execute(pool)
+    .await
+}
+
+pub async fn list_credentials(user_id: i64, pool: &PgPool) -> sqlx::Result<Vec<Credential>> {
+    sqlx::query_as!(
+        Credential,
+        r#"
+SELECT id, name, created_at, last_used_at FROM user_credential WHERE user_id = $1 ORDER BY id
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn delete_credential(
+    id: i64,
+    user_id: i64,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+DELETE FROM user_credential WHERE id = $1 AND user_id = $2
+        "#,
+        id,
+        user_id
+    )
+    .execute(pool)
+    .await
+}
@@ -1,61 +1,13 @@
-use crate::{messages::{WsChannelCreate, WsChannelUpdate, WsGuildCreate, UserFetchType}, db::models::MessageWithGuild};
+use crate::{messages::{WsChannelCreate, WsChannelUpdate, WsGuildCreate, UserFetchType, PresenceUpdateType}, db::models::MessageWithGuild};
 use sqlx::{postgres::PgQueryResult, types::Uuid, PgPool};
 
-use super::models::{Channel, Guild, Member, Message, User, UserSession, MemberClient, MessageInfo};
-
-pub async fn get_all(pool: &PgPool) -> sqlx::Result<Vec<User>> {
-    sqlx::query_as!(
-        User,
-        r#"
-SELECT *
-FROM users;
-        "#
-    )
-    .fetch_all(pool)
-    .await
-}
-
-pub async fn get_all_sessions(pool: &PgPool) -> sqlx::Result<Vec<UserSession>> {
-    sqlx::query_as!(
-        UserSession,
-        r#"
-SELECT *
-FROM user_sessions;
-        "#
-    )
-    .fetch_all(pool)
-    .await
-}
-
-pub async fn get_all_guilds(pool: &PgPool) -> sqlx::Result<Vec<Guild>> {
-    sqlx::query_as!(
-        Guild,
-        r#"
-SELECT *
-FROM guild;
-        "#
-    )
-    .fetch_all(pool)
-    .await
-}
-
-pub async fn get_all_members(pool: &PgPool) -> sqlx::Result<Vec<Member>> {
-    sqlx::query_as!(
-        Member,
-        r#"
-SELECT *
-FROM member;
-        "#
-    )
-    .fetch_all(pool)
-    .await
-}
+use super::models::{Channel, Guild, Message, User, MemberClient, MessageInfo};
 
 pub async fn get_user_by_id(user_id: i64, pool: &PgPool) -> sqlx::Result<UserFetchType> {
     sqlx::query_as!(
         UserFetchType,
         r#"
-SELECT id, username, profile, created_at, description, is_staff, is_superuser
+SELECT id, username, profile, created_at, description, is_staff, is_superuser, is_bot
 FROM users
 WHERE id = $1
         "#,
@@ -142,7 +94,7 @@ pub async fn fetch_member(guild_id: Uuid, pool: &PgPool) -> sqlx::Result<Vec<Mem
     // we might have to run 2 queries
     match sqlx::query!(
         r#"
-SELECT m.*, u.username, u.profile, u.description, u.created_at, u.is_online, u.is_staff, u.is_superuser
+SELECT m.*, u.username, u.profile, u.description, u.created_at, u.is_online, u.is_staff, u.is_superuser, u.is_bot, u.status, u.custom_status
 FROM member m
 JOIN users u ON m.user_id = u.id
 WHERE guild_id = $1
@@ -165,8 +117,16 @@ LIMIT 1000
                 description: m.description.to_owned(),
                 created_at: m.created_at,
                 is_staff: m.is_staff,
-                is_superuser: m.is_superuser
-            }
+                is_superuser: m.is_superuser,
+                is_bot: m.is_bot
+            },
+            // only what the database knows, online members get the live value in the handler
+            presence: PresenceUpdateType::from_db(
+                m.user_id,
+                m.is_online,
+                &m.status,
+                m.custom_status.to_owned()
+            )
         }).collect()),
         Err(err) => Err(err)
     }
@@ -300,19 +260,39 @@ VALUES ($1, $2, $3, $4, $5) RETURNING *
     .await
 }
 
+// A user banned from the guild isn't let in, that comes back as RowNotFound
 pub async fn join_guild(user_id: i64, guild_id: Uuid, pool: &PgPool) -> sqlx::Result<Vec<Channel>> {
-    sqlx::query_as!(
-        Channel,
+    sqlx::query!(
         r#"
-WITH gids AS (
-    INSERT INTO member (user_id, guild_id)
-    VALUES ($1, $2) RETURNING guild_id
-) SELECT * FROM channel WHERE guild_id = (SELECT guild_id from gids)
+INSERT INTO member (user_id, guild_id)
+SELECT $1, $2
+WHERE NOT EXISTS (
+    SELECT 1 FROM guild_ban
+    WHERE user_id = $1 AND guild_id = $2 AND (expires_at IS NULL OR expires_at > NOW())
+)
+RETURNING guild_id
         "#,
         user_id,
-        Some(guild_id)
+        guild_id
     )
-    .fetch_all(pool)
+    .fetch_one(pool)
+    .await?;
+    get_channels_by_guild_id(guild_id, pool).await
+}
+
+pub async fn leave_guild(
+    user_id: i64,
+    guild_id: Uuid,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+DELETE FROM member WHERE user_id = $1 AND guild_id = $2
+        "#,
+        user_id,
+        guild_id
+    )
+    .execute(pool)
     .await
 }
 
@@ -332,6 +312,74 @@ UPDATE users SET is_online = $1 WHERE id = $2
     .await
 }
 
+pub async fn update_presence(
+    user_id: i64,
+    status: &str,
+    custom_status: Option<String>,
+    pool: &PgPool,
+) -> sqlx::Result<PgQueryResult> {
+    sqlx::query!(
+        r#"
+UPDATE users SET status = $1, custom_status = $2 WHERE id = $3
+        "#,
+        status,
+        custom_status,
+        user_id
+    )
+    .execute(pool)
+    .await
+}
+
+pub async fn get_friend_ids(user_id: i64, pool: &PgPool) -> sqlx::Result<Vec<i64>> {
+    match sqlx::query!(
+        r#"
+SELECT CASE WHEN user1 = $1 THEN user2 ELSE user1 END AS "id!"
+FROM user_relations
+WHERE relationship = 'friend' AND (user1 = $1 OR user2 = $1)
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    {
+        Ok(recs) => Ok(recs.iter().map(|r| r.id).collect()),
+        Err(err) => Err(err),
+    }
+}
+
+// everyone whose presence this user gets to see: guild members and friends
+pub async fn get_presences(user_id: i64, pool: &PgPool) -> sqlx::Result<Vec<PresenceUpdateType>> {
+    match sqlx::query!(
+        r#"
+SELECT u.id, u.is_online, u.status, u.custom_status
+FROM users u
+WHERE u.id <> $1 AND (
+    u.id IN (
+        SELECT m2.user_id
+        FROM member m1
+        JOIN member m2 ON m1.guild_id = m2.guild_id
+        WHERE m1.user_id = $1
+    )
+    OR u.id IN (
+        SELECT CASE WHEN user1 = $1 THEN user2 ELSE user1 END
+        FROM user_relations
+        WHERE relationship = 'friend' AND (user1 = $1 OR user2 = $1)
+    )
+)
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    {
+        Ok(recs) => Ok(recs
+            .into_iter()
+            .map(|r| PresenceUpdateType::from_db(r.id, r.is_online, &r.status, r.custom_status))
+            .collect()),
+        Err(err) => Err(err),
+    }
+}
+
 pub async fn update_user_last_login(
     session_id: Uuid,
     pool: &PgPool,
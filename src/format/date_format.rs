@@ -1,5 +1,5 @@
 use serde::{self, Deserialize, Deserializer, Serializer};
-use sqlx::types::chrono::NaiveDateTime;
+use sqlx::types::chrono::{DateTime, NaiveDateTime};
 // de::Error
 
 // const FORMAT: &str = "%+";
@@ -11,7 +11,7 @@ where
     D: Deserializer<'de>,
 {
     let time: i64 = Deserialize::deserialize(deserializer)?;
-    Ok(NaiveDateTime::from_timestamp_opt(time, 0).unwrap())
+    Ok(DateTime::from_timestamp(time, 0).unwrap().naive_utc())
 }
 
 #[allow(dead_code)]
@@ -19,5 +19,5 @@ pub fn serialize<S>(date: &NaiveDateTime, serializer: S) -> Result<S::Ok, S::Err
 where
     S: Serializer,
 {
-    serializer.collect_str(&date.timestamp())
+    serializer.collect_str(&date.and_utc().timestamp())
 }
@@ -8,9 +8,13 @@ where
     D: Deserializer<'de>,
 {
     let time: String = Deserialize::deserialize(deserializer)?;
-    Ok(DateTime::parse_from_rfc3339(&time)
-        .map_err(D::Error::custom)?
-        .naive_utc())
+    // our own serialize output comes back through the event bus
+    match NaiveDateTime::parse_from_str(&time, FORMAT) {
+        Ok(date) => Ok(date),
+        Err(_) => Ok(DateTime::parse_from_rfc3339(&time)
+            .map_err(D::Error::custom)?
+            .naive_utc()),
+    }
 }
 
 pub fn serialize<S>(date: &NaiveDateTime, serializer: S) -> Result<S::Ok, S::Error>
@@ -19,3 +23,18 @@ where
 {
     serializer.collect_str(&date.format(FORMAT))
 }
+
+// for nullable columns, null stays null
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S>(date: &Option<NaiveDateTime>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match date {
+            Some(date) => super::serialize(date, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+}
@@ -0,0 +1 @@
+#[allow(dead_code)]pub static DEFAULT: &str = r#"<!DOCTYPE html><html lang="en"><head><meta charset="UTF-8"><meta http-equiv="X-UA-Compatible" content="IE=edge"><meta name="viewport" content="width=device-width, initial-scale=1.0"><title>Not Found</title><style>body {display: flex;flex-direction: column;align-items: center;justify-content: center;text-align: center;height: 100vh;}</style></head><body><p>Not found noob</p></body></html>"#;#[allow(dead_code)]pub static NOT_ME: &str = r#"<!DOCTYPE html><html lang="en"><head><meta charset="UTF-8"><meta http-equiv="X-UA-Compatible" content="IE=edge"><meta name="viewport" content="width=device-width, initial-scale=1.0"><title>Secure your account</title><style>body {display: flex;flex-direction: column;align-items: center;justify-content: center;text-align: center;height: 100vh;}</style></head><body><p>If that login wasn't you, every session will be logged out and your password will stop working until you set a new one from the email we send you.</p><form method="post"><button type="submit">It wasn't me, secure my account</button></form></body></html>"#;#[allow(dead_code)]pub static INDEX: &str = r#"<!DOCTYPE html><html lang="en"><head><meta charset="UTF-8"><meta http-equiv="X-UA-Compatible" content="IE=edge"><meta name="viewport" content="width=device-width, initial-scale=1.0"><title>Homet</title><style>body {display: flex;flex-direction: column;justify-content: center;align-items: center;text-align: center;}a {margin: 2rem;}</style></head><body><a href="/signup">signup</a><a href="/login">login</a><a href="/chat">chat</a></body></html>"#;#[allow(dead_code)]pub static DISCORD: &str = r#"<html><head><meta name="viewport" content="width=device-width, initial-scale=1, maximum-scale=1, minimum-scale=1, user-scalable=no, viewport-fit=cover"><meta charset="utf-8"><link rel="stylesheet" type="text/css" href="styles.css?releaseName=chef:production:3.9.0:f20c6c571a:1689069828275"><link rel="manifest" href="manifest.json?releaseName=chef:production:3.9.0:f20c6c571a:1689069828275"><style>@media screen and (min-aspect-ratio: 640/1136) {    #application-canvas.fill-mode-KEEP_ASPECT {        width: auto;        height: 100%;        margin: 0 auto;    }}</style><title>Chef</title><script type="text/javascript" nonce="">RTCPeerConnection = null;WebTransport = null;</script><script src="files/assets/26779643/1/bootstrap.build.js?releaseName=chef:production:3.9.0:f20c6c571a:1689069828275"></script><script src="playcanvas-stable.min.js?releaseName=chef:production:3.9.0:f20c6c571a:1689069828275"></script><script src="files/assets/124639000/1/asset-url-patch.js?releaseName=chef:production:3.9.0:f20c6c571a:1689069828275"></script><script src="__settings__.js?releaseName=chef:production:3.9.0:f20c6c571a:1689069828275"></script><script src="files/assets/26432658/1/ArabicConverter.js?t=57898e9d8e8b45def7802b0a743bca41"></script><script src="files/assets/26432659/1/UnicodeBidirectional.js?t=1bfc052d519169210468a2f64d2a3266"></script><script src="files/assets/26432656/1/RtlSetup.js?t=26357d62400e4744acaafc3dbcc8895f"></script><script src="files/assets/20579643/1/libs.build.js?t=205796439826171689069828275"></script><script src="files/assets/20445504/1/main.build.js?t=2044550440593641689069828275"></script><script src="files/assets/26432657/1/RtlElement.js?t=ae8f0d079e5002b69138d5553850cb70"></script><script src="files/assets/35098421/1/StationTransform.js?t=c5f1dc7b20af478aa1792ff8bc2d8d34"></script><script src="files/assets/47550779/1/WaterInit.js?t=e38cea9345cb94705464f85d04904778"></script><script src="files/assets/47591978/1/Buoyancy.js?t=5d4ca5c1383b09d0b0cf520e3faee779"></script><script src="files/assets/47795790/1/sunsetLight.js?t=7a7f4d3f3a7aeb1db040658c343fc096"></script><script src="files/assets/50592417/1/position_tween.js?t=9802914aed550a2ee63df3ea4f2d2246"></script><script src="files/assets/38154636/1/scrolling-texture.js?t=b653211e2f5f0db833d91a9e84860ff4"></script><script src="files/assets/115623491/1/pfp_camerafacing.js?t=8253a5b9e1e28cf3e61ddc45b6eef93e"></script></head><body><script src="__start__.js?releaseName=chef:production:3.9.0:f20c6c571a:1689069828275"></script><canvas id="application-canvas" tabindex="0" width="1891" height="1063" style="user-select: none; width: 1051px; height: 591px;" class="fill-mode-FILL_WINDOW"></canvas><script src="__loading__.js?releaseName=chef:production:3.9.0:f20c6c571a:1689069828275"></script></body></html>"#;
\ No newline at end of file
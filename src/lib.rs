@@ -0,0 +1,37 @@
+// Everything the server and the admin cli share
+
+// self use
+pub mod controllers;
+pub mod server;
+mod session;
+mod test;
+
+// for controllers
+pub mod db;
+// mod session;
+// test views for debugging purposes...
+mod html;
+// serde formatting date, uuid fields in structs
+mod format;
+// messages for server and sessions
+pub mod messages;
+// outgoing email
+pub mod mailer;
+// random secrets and their hashes
+pub mod token;
+// two-factor codes
+mod totp;
+// webauthn relying party
+pub mod passkey;
+// external identity providers
+pub mod oidc;
+// failed login backoff
+mod throttle;
+// image, proof of work or no captcha at all
+pub mod captcha;
+// config.toml and the environment
+pub mod config;
+// migrations/ compiled in
+pub mod migrate;
+
+pub const PLACEHOLDER_UUID: &str = "5fe9d2ab-2174-4a30-8245-cc5de2563dce";
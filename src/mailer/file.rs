@@ -0,0 +1,39 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use uuid::Uuid;
+
+use super::{Mail, MailError, Mailer};
+
+// Drops every mail into a directory instead of sending it, for development and tests
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: &Mail) -> Result<(), MailError> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!(
+            "{}-{}.txt",
+            Utc::now().format("%Y%m%d%H%M%S"),
+            Uuid::new_v4()
+        ));
+        std::fs::write(
+            &path,
+            format!(
+                "To: {}\nSubject: {}\n\n{}\n\n--- html ---\n{}\n",
+                mail.to, mail.subject, mail.text, mail.html
+            ),
+        )?;
+        log::info!("Mail to {} written to {}", mail.to, path.display());
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+use std::{error::Error, sync::Arc};
+
+use async_trait::async_trait;
+
+use crate::config::MailConfig;
+
+pub mod file;
+pub mod queue;
+pub mod smtp;
+pub mod templates;
+
+pub use self::queue::MailQueue;
+
+pub type MailError = Box<dyn Error + Send + Sync>;
+
+#[derive(Clone, Debug)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+// Something that can get a mail out the door. Sending is done by the MailQueue,
+// request handlers never wait on it.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: &Mail) -> Result<(), MailError>;
+}
+
+// Config::validate already made sure file is only picked in dev
+pub fn from_config(config: &MailConfig) -> Result<Arc<dyn Mailer>, MailError> {
+    match config.mailer.as_str() {
+        "smtp" => Ok(Arc::new(smtp::SmtpMailer::from_config(config)?)),
+        "file" => Ok(Arc::new(file::FileMailer::new(&config.outbox))),
+        _ => Err("mail.mailer has to be smtp or file".into()),
+    }
+}
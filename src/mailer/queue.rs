@@ -0,0 +1,58 @@
+use std::{sync::Arc, time::Duration};
+
+use actix_web::rt::time::sleep;
+use tokio::sync::mpsc;
+
+use super::{Mail, Mailer};
+
+// tries per mail, waiting RETRY_DELAY, then twice that, and so on in between
+const MAX_TRIES: u32 = 5;
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+// Mails go out in the background so a slow or dead SMTP server never holds up a request
+#[derive(Clone)]
+pub struct MailQueue {
+    tx: mpsc::UnboundedSender<Mail>,
+}
+
+impl MailQueue {
+    pub fn start(mailer: Arc<dyn Mailer>) -> Self {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Mail>();
+        actix_web::rt::spawn(async move {
+            while let Some(mail) = rx.recv().await {
+                // one mail's retries shouldn't hold up the next
+                actix_web::rt::spawn(deliver(mailer.clone(), mail));
+            }
+        });
+        Self { tx }
+    }
+
+    pub fn send(&self, mail: Mail) {
+        if let Err(err) = self.tx.send(mail) {
+            log::error!("Mail queue is gone, dropped mail to {}", err.0.to);
+        }
+    }
+}
+
+async fn deliver(mailer: Arc<dyn Mailer>, mail: Mail) {
+    let mut delay = RETRY_DELAY;
+    for tries in 1..=MAX_TRIES {
+        match mailer.send(&mail).await {
+            Ok(()) => return,
+            Err(err) => {
+                log::warn!(
+                    "Could not send mail to {} ({}/{}): {}",
+                    mail.to,
+                    tries,
+                    MAX_TRIES,
+                    err
+                );
+                if tries < MAX_TRIES {
+                    sleep(delay).await;
+                    delay *= 2;
+                }
+            }
+        }
+    }
+    log::error!("Gave up on mail to {}", mail.to);
+}
@@ -0,0 +1,61 @@
+use async_trait::async_trait;
+use lettre::{
+    message::{Mailbox, MultiPart},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+
+use super::{Mail, MailError, Mailer};
+use crate::config::MailConfig;
+
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(
+        host: &str,
+        port: u16,
+        credentials: Option<Credentials>,
+        from: Mailbox,
+    ) -> Result<Self, MailError> {
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::relay(host)?.port(port);
+        if let Some(creds) = credentials {
+            builder = builder.credentials(creds);
+        }
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+
+    pub fn from_config(config: &MailConfig) -> Result<Self, MailError> {
+        let credentials = match (&config.smtp_username, &config.smtp_password) {
+            (Some(user), Some(password)) => Some(Credentials::new(user.clone(), password.clone())),
+            _ => None,
+        };
+        Self::new(
+            &config.smtp_host,
+            config.smtp_port,
+            credentials,
+            config.from.parse()?,
+        )
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, mail: &Mail) -> Result<(), MailError> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(mail.to.parse()?)
+            .subject(mail.subject.clone())
+            .multipart(MultiPart::alternative_plain_html(
+                mail.text.clone(),
+                mail.html.clone(),
+            ))?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+use super::Mail;
+
+const VERIFY_TEXT: &str = include_str!("../views/mail/verify.txt");
+const VERIFY_HTML: &str = include_str!("../views/mail/verify.html");
+const RESET_TEXT: &str = include_str!("../views/mail/reset.txt");
+const RESET_HTML: &str = include_str!("../views/mail/reset.html");
+const NOTIFICATION_TEXT: &str = include_str!("../views/mail/notification.txt");
+const NOTIFICATION_HTML: &str = include_str!("../views/mail/notification.html");
+const NEW_DEVICE_TEXT: &str = include_str!("../views/mail/new_device.txt");
+const NEW_DEVICE_HTML: &str = include_str!("../views/mail/new_device.html");
+const EMAIL_CHANGE_TEXT: &str = include_str!("../views/mail/email_change.txt");
+const EMAIL_CHANGE_HTML: &str = include_str!("../views/mail/email_change.html");
+
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+// fills in {{key}}, values are escaped in the html version only
+fn render(to: &str, subject: String, text: &str, html: &str, values: &[(&str, String)]) -> Mail {
+    let (mut text, mut html) = (text.to_string(), html.to_string());
+    for (key, value) in values {
+        let needle = format!("{{{{{}}}}}", key);
+        text = text.replace(&needle, value);
+        html = html.replace(&needle, &escape(value));
+    }
+    Mail {
+        to: to.to_string(),
+        subject,
+        text,
+        html,
+    }
+}
+
+pub fn verification(to: &str, code: i64, minutes: i32, link: Option<String>) -> Mail {
+    let keep = link.is_some();
+    let mut mail = render(
+        to,
+        "Thanks for registering for flettex".to_string(),
+        VERIFY_TEXT,
+        VERIFY_HTML,
+        &[
+            ("code", code.to_string()),
+            ("minutes", minutes.to_string()),
+            ("link", link.clone().unwrap_or_default()),
+        ],
+    );
+    // no link, no paragraph about it
+    mail.text = section(&mail.text, "link", keep);
+    mail.html = section(&mail.html, "link", keep);
+    mail
+}
+
+pub fn password_reset(to: &str, token: &str, minutes: i32) -> Mail {
+    render(
+        to,
+        "Reset your flettex password".to_string(),
+        RESET_TEXT,
+        RESET_HTML,
+        &[
+            ("token", token.to_string()),
+            ("minutes", minutes.to_string()),
+        ],
+    )
+}
+
+pub fn notification(to: &str, title: &str, body: &str) -> Mail {
+    render(
+        to,
+        title.to_string(),
+        NOTIFICATION_TEXT,
+        NOTIFICATION_HTML,
+        &[("title", title.to_string()), ("body", body.to_string())],
+    )
+}
+
+pub fn new_device(
+    to: &str,
+    browser: &str,
+    os: &str,
+    device: &str,
+    time: &str,
+    link: &str,
+    days: i32,
+) -> Mail {
+    render(
+        to,
+        "New login to your flettex account".to_string(),
+        NEW_DEVICE_TEXT,
+        NEW_DEVICE_HTML,
+        &[
+            ("browser", browser.to_string()),
+            ("os", os.to_string()),
+            ("device", device.to_string()),
+            ("time", time.to_string()),
+            ("link", link.to_string()),
+            ("days", days.to_string()),
+        ],
+    )
+}
+
+pub fn email_change(to: &str, link: &str, minutes: i32) -> Mail {
+    render(
+        to,
+        "Confirm your new flettex email".to_string(),
+        EMAIL_CHANGE_TEXT,
+        EMAIL_CHANGE_HTML,
+        &[("link", link.to_string()), ("minutes", minutes.to_string())],
+    )
+}
+
+// {{#name}}...{{/name}} is kept without the markers, or dropped altogether
+fn section(s: &str, name: &str, keep: bool) -> String {
+    let (open, close) = (format!("{{{{#{}}}}}", name), format!("{{{{/{}}}}}", name));
+    match (s.find(&open), s.find(&close)) {
+        (Some(_), Some(_)) if keep => s.replacen(&open, "", 1).replacen(&close, "", 1),
+        (Some(start), Some(end)) => format!("{}{}", &s[..start], &s[end + close.len()..]),
+        _ => s.to_string(),
+    }
+}
@@ -29,6 +29,7 @@ pub enum MessageTypes {
     MessageDelete(MessageDeleteType),
     ReadyEvent(ReadyEventType),
     GuildCreate(GuildCreateType),
+    GuildDelete(GuildDeleteType),
     ChannelCreate(ChannelCreateType),
     ChannelUpdate(ChannelUpdateType),
     ChannelDelete(ChannelDeleteType),
@@ -36,6 +37,9 @@ pub enum MessageTypes {
     MemberUpdate(MemberUpdateType),
     MemberRemove(MemberRemoveType),
     UserFetch(UserFetchType),
+    PresenceUpdate(PresenceUpdateType),
+    Notification(NotificationType),
+    UserUpdate(UserFetchType),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -68,4 +72,14 @@ pub enum WsReceiveTypes {
     MemberCreate(WsMemberCreate),
     //
     MemberUpdate(WsMemberUpdate),
+    // {"type": "MemberKick", "data":{"guild_id": "bruh-bruh-bruh-bruh", "user_id": 2}}
+    MemberKick(WsMemberKick),
+    // {"type": "MemberBan", "data":{"guild_id": "bruh-bruh-bruh-bruh", "user_id": 2, "reason": "spam", "hours": 24, "purge_hours": 1}}
+    MemberBan(WsMemberBan),
+    // {"type": "MemberUnban", "data":{"guild_id": "bruh-bruh-bruh-bruh", "user_id": 2}}
+    MemberUnban(WsMemberUnban),
+    // {"type": "PresenceUpdate", "data":{"status": "dnd", "custom_status": "busy"}}
+    PresenceUpdate(WsPresenceUpdate),
+    // {"type": "UserUpdate", "data":{"username": "new name", "description": "", "profile": "https://..."}}
+    UserUpdate(WsUserUpdate),
 }
@@ -1,5 +1,5 @@
 use super::{send::*, MessageTypes};
-use crate::db;
+use crate::{controllers, db, server};
 use crate::session::WsChatSession;
 use crate::PLACEHOLDER_UUID;
 use async_trait::async_trait;
@@ -7,7 +7,7 @@ use enum_dispatch::enum_dispatch;
 use raspberry_macros::ratelimit;
 use serde::{self, Deserialize, Serialize};
 use sqlx::types::Uuid;
-use std::clone::Clone;
+use std::{clone::Clone, sync::atomic::Ordering};
 
 #[async_trait]
 #[enum_dispatch]
@@ -15,6 +15,21 @@ pub trait Handler {
     async fn handle(&self, ctx: WsChatSession) -> ();
 }
 
+// Unverified accounts can chat but can't create guilds or start DMs.
+// The session's user is from when it connected, so ask the db before saying no.
+async fn verified(ctx: &WsChatSession, action: &str) -> bool {
+    if ctx.user.code.is_none() || matches!(db::verify::code(ctx.user.id, &ctx.pool).await, Ok(None)) {
+        return true;
+    }
+    ctx.send_event(MessageTypes::MessageCreate(Message::system(
+        format!("You need to verify your email before you can {}. Check your email or ask for a new code at /verify/resend", action),
+        PLACEHOLDER_UUID,
+        0,
+    )))
+    .await;
+    false
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[ratelimit(1)]
 pub struct WsMessageCreate {
@@ -89,6 +104,31 @@ pub struct WsMemberUpdate {
     pub nickname: String,
 }
 
+// Kicking, banning and unbanning are up to the guild's creator
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[ratelimit(1)]
+pub struct WsMemberKick {
+    pub guild_id: Uuid,
+    pub user_id: i64,
+}
+
+// no hours is permanent, purge_hours deletes what they wrote in the guild that recently
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[ratelimit(1)]
+pub struct WsMemberBan {
+    pub guild_id: Uuid,
+    pub user_id: i64,
+    pub reason: Option<String>,
+    pub hours: Option<i32>,
+    pub purge_hours: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WsMemberUnban {
+    pub guild_id: Uuid,
+    pub user_id: i64,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct WsMemberFetchType {
     pub guild_id: Uuid,
@@ -99,6 +139,22 @@ pub struct WsUserFetchType {
     pub id: i64,
 }
 
+// idle only applies to the session that sent it, the rest are for the whole account
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[ratelimit(1)]
+pub struct WsPresenceUpdate {
+    pub status: Status,
+    pub custom_status: Option<String>,
+}
+
+// same as PATCH /me
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[ratelimit(5)]
+pub struct WsUserUpdate {
+    #[serde(flatten)]
+    pub update: server::ProfileUpdateEvent,
+}
+
 #[async_trait]
 impl Handler for WsMessageCreate {
     async fn handle(&self, ctx: WsChatSession) {
@@ -225,7 +281,7 @@ impl Handler for WsMessageCreate {
                     MessageTypes::MessageCreate(Message::user(
                         msg.to_string(),
                         &self.channel_id.clone(),
-                        ctx.user.to_owned().into(),
+                        ctx.author(),
                         self.nonce,
                     )),
                 )
@@ -244,7 +300,7 @@ impl Handler for WsMessageCreate {
                             &guild_id.to_string(),
                             MessageTypes::MessageCreate(Message::from_guildmsg(
                                 msg,
-                                ctx.user.to_owned().into(),
+                                ctx.author(),
                                 self.nonce,
                             )),
                         )
@@ -260,7 +316,7 @@ impl Handler for WsMessageCreate {
                             msg.user2.unwrap() as usize,
                             MessageTypes::MessageCreate(Message::from_guildmsg(
                                 msg,
-                                ctx.user.to_owned().into(),
+                                ctx.author(),
                                 self.nonce,
                             ))
                         )
@@ -284,9 +340,10 @@ impl Handler for WsMessageUpdate {
                             content: updated.content,
                             created_at: updated.created_at,
                             edited_at: updated.edited_at,
-                            author: ctx.user.to_owned().into(),
+                            author: ctx.author(),
                             channel_id: updated.channel_id,
                             nonce: self.nonce,
+                            bot: ctx.user.is_bot,
                         }),
                     )
                     .await;
@@ -300,9 +357,10 @@ impl Handler for WsMessageUpdate {
                             content: updated.content,
                             created_at: updated.created_at,
                             edited_at: updated.edited_at,
-                            author: ctx.user.to_owned().into(),
+                            author: ctx.author(),
                             channel_id: updated.channel_id,
                             nonce: self.nonce,
+                            bot: ctx.user.is_bot,
                         }),
                     )
                     .await;
@@ -359,17 +417,25 @@ impl Handler for WsMessageFetchType {
 #[async_trait]
 impl Handler for WsMemberCreate {
     async fn handle(&self, ctx: WsChatSession) {
+        match db::guild_ban::active(ctx.user.id, self.guild_id, &ctx.pool).await {
+            Ok(None) => {}
+            Ok(Some(ban)) => {
+                notice(&ctx, server::moderation::describe_ban(&ban)).await;
+                return;
+            }
+            Err(err) => {
+                log::error!("{:?}", err);
+                return;
+            }
+        }
         match db::ws_session::join_guild(ctx.user.id, self.guild_id, &ctx.pool).await {
             Ok(channels) => {
                 let guild = db::ws_session::get_guild_by_id(self.guild_id, &ctx.pool)
                     .await
                     .unwrap();
                 ctx.srv
-                    .join_guild(guild.id.to_string(), ctx.user.id as usize)
+                    .join_guild(guild.id.to_string(), ctx.user.id as usize, ctx.conn_id)
                     .await;
-                let mut lock = ctx.rooms.lock().await;
-                lock.insert(guild.id.to_string());
-                drop(lock);
                 ctx.send_event(MessageTypes::GuildCreate(GuildCreateType {
                     guild: guild.to_owned(),
                 }))
@@ -390,8 +456,12 @@ impl Handler for WsMemberCreate {
                     .await;
                 }
             }
+            // banned between the check above and the insert
+            Err(sqlx::Error::RowNotFound) => {
+                notice(&ctx, "You are banned from this guild".to_string()).await;
+            }
             Err(err) => {
-                println!("{:?}", err);
+                log::error!("{:?}", err);
             }
         }
     }
@@ -419,13 +489,55 @@ impl Handler for WsMemberUpdate {
                     .await;
             }
             Err(err) => {
-                println!("{:?}", err);
+                log::error!("{:?}", err);
             }
         }
     }
 }
 
-// member delete will be implemented once kicking is implemented in the frontend
+async fn notice(ctx: &WsChatSession, msg: String) {
+    ctx.send_event(MessageTypes::MessageCreate(Message::system(
+        msg,
+        PLACEHOLDER_UUID,
+        0,
+    )))
+    .await;
+}
+
+#[async_trait]
+impl Handler for WsMemberKick {
+    async fn handle(&self, ctx: WsChatSession) {
+        if let Err(msg) = ctx
+            .srv
+            .kick(ctx.user.id, self.guild_id, self.user_id, &ctx.pool)
+            .await
+        {
+            notice(&ctx, msg.to_string()).await;
+        }
+    }
+}
+
+#[async_trait]
+impl Handler for WsMemberBan {
+    async fn handle(&self, ctx: WsChatSession) {
+        if let Err(msg) = ctx.srv.ban(ctx.user.id, self, &ctx.pool).await {
+            notice(&ctx, msg.to_string()).await;
+        }
+    }
+}
+
+#[async_trait]
+impl Handler for WsMemberUnban {
+    async fn handle(&self, ctx: WsChatSession) {
+        if let Err(msg) = ctx
+            .srv
+            .unban(ctx.user.id, self.guild_id, self.user_id, &ctx.pool)
+            .await
+        {
+            notice(&ctx, msg.to_string()).await;
+        }
+    }
+}
 
 #[async_trait]
 impl Handler for WsMemberFetchType {
@@ -434,16 +546,86 @@ impl Handler for WsMemberFetchType {
             // nobody is in Main though hmm, this is purely waste of bandwidth!
             return;
         }
+        let mut members = db::ws_session::fetch_member(self.guild_id, &ctx.pool)
+            .await
+            .unwrap();
+        for m in members.iter_mut() {
+            m.presence = ctx.srv.presence(m.presence.to_owned());
+        }
         ctx.send_event(MessageTypes::Members(MembersType {
             guild_id: self.guild_id,
-            members: db::ws_session::fetch_member(self.guild_id, &ctx.pool)
-                .await
-                .unwrap(),
+            members,
         }))
         .await;
     }
 }
 
+#[async_trait]
+impl Handler for WsPresenceUpdate {
+    async fn handle(&self, ctx: WsChatSession) {
+        if self
+            .custom_status
+            .as_ref()
+            .map_or(false, |c| c.chars().count() > 128)
+        {
+            return;
+        }
+        let user_id = ctx.user.id as usize;
+        let chosen = match self.status {
+            // can't pick offline, that's what invisible is for
+            Status::Offline => return,
+            // idle is per session and never saved, keep whatever they picked before
+            Status::Idle => {
+                ctx.idle.store(true, Ordering::Relaxed);
+                ctx.srv
+                    .registry
+                    .presence_of(user_id)
+                    .map_or(Status::Online, |p| p.status)
+            }
+            status => {
+                ctx.idle.store(false, Ordering::Relaxed);
+                status
+            }
+        };
+        // the other nodes go by the database
+        if let Err(err) = db::presence::set_idle(
+            ctx.srv.node_id,
+            ctx.conn_id as i64,
+            self.status == Status::Idle,
+            &ctx.pool,
+        )
+        .await
+        {
+            log::error!("{:?}", err);
+        }
+        let unchanged = ctx.srv.registry.presence_of(user_id).is_some_and(|p| {
+            p.status == chosen && p.custom_status == self.custom_status
+        });
+        if unchanged {
+            // only this session's idle flag moved
+            ctx.srv.refresh_presence(ctx.user.id, &ctx.pool, false).await;
+            return;
+        }
+        match db::ws_session::update_presence(
+            ctx.user.id,
+            chosen.as_str(),
+            self.custom_status.to_owned(),
+            &ctx.pool,
+        )
+        .await
+        {
+            Ok(_) => {
+                ctx.srv
+                    .set_presence(ctx.user.id, chosen, self.custom_status.to_owned(), &ctx.pool)
+                    .await;
+            }
+            Err(err) => {
+                log::error!("{:?}", err);
+            }
+        }
+    }
+}
+
 #[async_trait]
 impl Handler for WsUserFetchType {
     async fn handle(&self, ctx: WsChatSession) {
@@ -455,7 +637,7 @@ impl Handler for WsUserFetchType {
                     ctx.send_event(MessageTypes::UserFetch(user)).await;
                 }
                 Err(err) => {
-                    println!("{:?}", err);
+                    log::error!("{:?}", err);
                 }
             }
         }
@@ -465,6 +647,9 @@ impl Handler for WsUserFetchType {
 #[async_trait]
 impl Handler for WsGuildCreate {
     async fn handle(&self, ctx: WsChatSession) {
+        if !verified(&ctx, "create a guild").await {
+            return;
+        }
         match db::ws_session::create_guild(ctx.user.id, self.to_owned(), &ctx.pool).await {
             Ok(rec) => {
                 // self.rooms.lock().await.insert(rec.name.to_owned());
@@ -479,7 +664,7 @@ impl Handler for WsGuildCreate {
                 // self.srv.send_message(&rec.id.to_owned(), MessageTypes::MessageCreate(MessageCreateType {content: "joined".to_string(), channel_id: rec.id.to_owned()})).await;
             }
             Err(err) => {
-                println!("{:?}", err);
+                log::error!("{:?}", err);
             }
         }
     }
@@ -488,6 +673,9 @@ impl Handler for WsGuildCreate {
 #[async_trait]
 impl Handler for WsDMChannelCreate {
     async fn handle(&self, ctx: WsChatSession) {
+        if !verified(&ctx, "start a DM").await {
+            return;
+        }
         match db::ws_session::create_dm_channel(ctx.user.id, self.user_id, &ctx.pool).await {
             Ok(rec) => {
                 ctx.srv.send_dm(ctx.user.id as usize, self.user_id as usize, MessageTypes::ChannelCreate(ChannelCreateType {
@@ -496,7 +684,7 @@ impl Handler for WsDMChannelCreate {
                 .await;
             }
             Err(err) => {
-                println!("{:?}", err);
+                log::error!("{:?}", err);
                 // ctx.srv.send_dm(ctx.user.id as usize, self.user_id as usize, MessageTypes::ChannelCreate(ChannelCreateType {
                 //     channel: 
                 // }))
@@ -524,7 +712,7 @@ impl Handler for WsChannelCreate {
                 .await;
             }
             Err(err) => {
-                println!("{:?}", err);
+                log::error!("{:?}", err);
             }
         }
     }
@@ -570,3 +758,20 @@ impl Handler for WsChannelDelete {
         }
     }
 }
+
+#[async_trait]
+impl Handler for WsUserUpdate {
+    async fn handle(&self, ctx: WsChatSession) {
+        match controllers::me::edit(ctx.user.id, &self.update, &ctx.pool).await {
+            Ok(user) => ctx.srv.update_profile(user, &ctx.pool).await,
+            Err(msg) => {
+                ctx.send_event(MessageTypes::MessageCreate(Message::system(
+                    msg.to_string(),
+                    PLACEHOLDER_UUID,
+                    0,
+                )))
+                .await
+            }
+        }
+    }
+}
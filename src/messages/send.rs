@@ -16,6 +16,8 @@ pub struct Message {
     pub author: UserFetchType,
     pub channel_id: Uuid,
     pub nonce: Uuid,
+    // sent by a bot account, same as author.is_bot
+    pub bot: bool,
 }
 
 impl Message {
@@ -35,10 +37,12 @@ impl Message {
                     .unwrap(),
                 is_staff: true,
                 is_superuser: true,
+                is_bot: false,
             },
             edited_at: Utc::now().naive_utc(),
             created_at: Utc::now().naive_utc(),
             nonce: Uuid::new_v4(),
+            bot: false,
         }
     }
 
@@ -47,6 +51,7 @@ impl Message {
             id: Uuid::new_v4(),
             content,
             channel_id: Uuid::parse_str(channel_id).unwrap(),
+            bot: author.is_bot,
             author,
             edited_at: Utc::now().naive_utc(),
             created_at: Utc::now().naive_utc(),
@@ -60,6 +65,7 @@ impl Message {
             id: msg.id,
             content: msg.content,
             channel_id: msg.channel_id,
+            bot: author.is_bot,
             author,
             edited_at: msg.edited_at,
             created_at: msg.edited_at,
@@ -72,6 +78,7 @@ impl Message {
             id: msg.id,
             content: msg.content,
             channel_id: msg.channel_id,
+            bot: author.is_bot,
             author,
             edited_at: msg.edited_at,
             created_at: msg.edited_at,
@@ -90,6 +97,8 @@ pub struct MessageDeleteType {
 pub struct ReadyEventType {
     pub user: UserClient,
     pub guilds: Vec<GuildChannels>,
+    // guild members and friends that aren't offline
+    pub presences: Vec<PresenceUpdateType>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -97,6 +106,11 @@ pub struct GuildCreateType {
     pub guild: Guild,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GuildDeleteType {
+    pub id: Uuid,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct ChannelCreateType {
     pub channel: Channel,
@@ -156,6 +170,7 @@ pub struct UserFetchType {
     pub description: Option<String>,
     pub is_staff: bool,
     pub is_superuser: bool,
+    pub is_bot: bool,
 }
 
 impl From<User> for UserFetchType {
@@ -168,6 +183,74 @@ impl From<User> for UserFetchType {
             description: u.description,
             is_staff: u.is_staff,
             is_superuser: u.is_superuser,
+            is_bot: u.is_bot,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Online,
+    Idle,
+    Dnd,
+    Invisible,
+    Offline,
+}
+
+impl Status {
+    // users.status only ever holds what a user picked: online, dnd or invisible
+    pub fn from_db(s: &str) -> Self {
+        match s {
+            "dnd" => Status::Dnd,
+            "invisible" => Status::Invisible,
+            _ => Status::Online,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::Online => "online",
+            Status::Idle => "idle",
+            Status::Dnd => "dnd",
+            Status::Invisible => "invisible",
+            Status::Offline => "offline",
+        }
+    }
+}
+
+// Something the server wants the user to see, not tied to any channel
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NotificationType {
+    pub title: String,
+    pub body: String,
+    #[serde(with = "format::date_format2")]
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PresenceUpdateType {
+    pub user_id: i64,
+    pub status: Status,
+    pub custom_status: Option<String>,
+}
+
+impl PresenceUpdateType {
+    // for users not connected to this node, built from their users row
+    pub fn from_db(user_id: i64, is_online: bool, status: &str, custom_status: Option<String>) -> Self {
+        let status = match Status::from_db(status) {
+            _ if !is_online => Status::Offline,
+            Status::Invisible => Status::Offline,
+            s => s,
+        };
+        Self {
+            user_id,
+            status,
+            custom_status: if status == Status::Offline {
+                None
+            } else {
+                custom_status
+            },
         }
     }
 }
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use sqlx::{
+    migrate::{Migrate, MigrateError, Migration, Migrator},
+    PgPool,
+};
+
+// everything in migrations/, baked into the binary
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+pub struct Status {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+pub fn up_migrations() -> impl Iterator<Item = &'static Migration> {
+    MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+}
+
+// version -> checksum of what the database has run
+async fn applied(pool: &PgPool) -> Result<HashMap<i64, Vec<u8>>, MigrateError> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    if let Some(version) = conn.dirty_version().await? {
+        return Err(MigrateError::Dirty(version));
+    }
+    Ok(conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| (m.version, m.checksum.into_owned()))
+        .collect())
+}
+
+pub async fn status(pool: &PgPool) -> Result<Vec<Status>, MigrateError> {
+    let applied = applied(pool).await?;
+    Ok(up_migrations()
+        .map(|m| Status {
+            version: m.version,
+            description: m.description.to_string(),
+            applied: applied.contains_key(&m.version),
+        })
+        .collect())
+}
+
+pub async fn up(pool: &PgPool) -> Result<(), MigrateError> {
+    MIGRATOR.run(pool).await
+}
+
+// Reverts the last `steps` migrations the database has
+pub async fn down(pool: &PgPool, steps: usize) -> Result<(), MigrateError> {
+    let mut versions: Vec<i64> = applied(pool).await?.into_keys().collect();
+    versions.sort();
+    let target = match versions.len().checked_sub(steps + 1) {
+        Some(i) => versions[i],
+        None => 0,
+    };
+    MIGRATOR.undo(pool, target).await
+}
+
+// The queries were checked against exactly these migrations. A database that is
+// behind, ahead or had a migration edited after the fact gets refused.
+pub async fn check(pool: &PgPool) -> Result<(), String> {
+    let applied = applied(pool).await.map_err(|err| err.to_string())?;
+    for m in up_migrations() {
+        match applied.get(&m.version) {
+            None => {
+                return Err(format!(
+                    "migration {} ({}) is not applied, run `migrate up`",
+                    m.version, m.description
+                ))
+            }
+            Some(checksum) if *checksum != *m.checksum => {
+                return Err(format!(
+                    "migration {} ({}) was changed after it was applied",
+                    m.version, m.description
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+    match applied
+        .keys()
+        .find(|v| !up_migrations().any(|m| m.version == **v))
+    {
+        Some(version) => Err(format!(
+            "the database has migration {} which this build doesn't know about",
+            version
+        )),
+        None => Ok(()),
+    }
+}
+
+// raspberry-backend-app migrate [status | up | down [steps]]
+pub async fn cli(args: &[String], pool: &PgPool) -> Result<(), MigrateError> {
+    match args.first().map(String::as_str).unwrap_or("status") {
+        "status" => {
+            for m in status(pool).await? {
+                println!(
+                    "{} {} {}",
+                    if m.applied { "applied" } else { "pending" },
+                    m.version,
+                    m.description
+                );
+            }
+        }
+        "up" => {
+            up(pool).await?;
+            println!("up to date");
+        }
+        "down" => {
+            let steps = args.get(1).and_then(|s| s.parse().ok()).unwrap_or(1);
+            down(pool, steps).await?;
+            println!("reverted {} migration(s)", steps);
+        }
+        other => println!(
+            "unknown migrate command {}, try status, up or down [steps]",
+            other
+        ),
+    }
+    Ok(())
+}
@@ -0,0 +1,101 @@
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use openidconnect::core::{CoreClient, CoreProviderMetadata};
+use openidconnect::reqwest::async_http_client;
+use openidconnect::{ClientId, ClientSecret, IssuerUrl, RedirectUrl};
+
+use crate::config::Config;
+
+// discovery (and with it the JWKS) is fetched again after this, so key rotation gets picked up
+const REFRESH: Duration = Duration::from_secs(3600);
+
+#[derive(Clone, Debug)]
+pub struct ProviderConfig {
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_url: String,
+}
+
+// Every configured OpenID Connect provider, by the name used in /oidc/{provider}/...
+#[derive(Default)]
+pub struct Providers {
+    configs: HashMap<String, ProviderConfig>,
+    clients: RwLock<HashMap<String, (Instant, CoreClient)>>,
+}
+
+impl Providers {
+    pub fn new(configs: HashMap<String, ProviderConfig>) -> Self {
+        Self {
+            configs,
+            clients: RwLock::new(HashMap::new()),
+        }
+    }
+
+    // The [oidc.*] sections, the callback for [oidc.corp] is public_url/oidc/corp/callback
+    pub fn from_config(config: &Config) -> Self {
+        let configs = config
+            .oidc
+            .iter()
+            .map(|(name, provider)| {
+                let name = name.to_lowercase();
+                let redirect_url = format!("{}/oidc/{}/callback", config.public_url(), name);
+                (
+                    name,
+                    ProviderConfig {
+                        issuer: provider.issuer.clone(),
+                        client_id: provider.client_id.clone(),
+                        client_secret: provider.client_secret.clone(),
+                        redirect_url,
+                    },
+                )
+            })
+            .collect();
+        Self::new(configs)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.configs.keys().cloned().collect()
+    }
+
+    // None if there's no such provider or it can't be reached
+    pub async fn client(&self, name: &str) -> Option<CoreClient> {
+        let config = self.configs.get(name)?;
+        if let Some((at, client)) = self.clients.read().unwrap().get(name) {
+            if at.elapsed() < REFRESH {
+                return Some(client.clone());
+            }
+        }
+        match discover(config).await {
+            Ok(client) => {
+                self.clients
+                    .write()
+                    .unwrap()
+                    .insert(name.to_string(), (Instant::now(), client.clone()));
+                Some(client)
+            }
+            Err(err) => {
+                log::error!("OIDC discovery for {} failed: {}", name, err);
+                None
+            }
+        }
+    }
+}
+
+async fn discover(config: &ProviderConfig) -> Result<CoreClient, Box<dyn std::error::Error>> {
+    let metadata = CoreProviderMetadata::discover_async(
+        IssuerUrl::new(config.issuer.clone())?,
+        async_http_client,
+    )
+    .await?;
+    Ok(CoreClient::from_provider_metadata(
+        metadata,
+        ClientId::new(config.client_id.clone()),
+        config.client_secret.clone().map(ClientSecret::new),
+    )
+    .set_redirect_uri(RedirectUrl::new(config.redirect_url.clone())?))
+}
@@ -0,0 +1,20 @@
+use webauthn_rs::prelude::{Url, Uuid, WebauthnError};
+use webauthn_rs::{Webauthn, WebauthnBuilder};
+
+use crate::config::PasskeyConfig;
+
+pub fn new(rp_id: &str, origin: &str) -> Result<Webauthn, WebauthnError> {
+    let origin = Url::parse(origin).map_err(|_| WebauthnError::Configuration)?;
+    WebauthnBuilder::new(rp_id, &origin)?
+        .rp_name("flettex")
+        .build()
+}
+
+pub fn from_config(config: &PasskeyConfig) -> Result<Webauthn, WebauthnError> {
+    new(&config.rp_id, &config.origin)
+}
+
+// WebAuthn wants a uuid per account, user ids are public anyway so derive it
+pub fn user_handle(user_id: i64) -> Uuid {
+    Uuid::from_u64_pair(0, user_id as u64)
+}
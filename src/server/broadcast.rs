@@ -0,0 +1,99 @@
+use std::sync::{atomic::AtomicBool, Arc, OnceLock, RwLock};
+
+use actix_web::web::Bytes;
+use actix_ws::{CloseCode, CloseReason};
+use bytestring::ByteString;
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use crate::{
+    db::models::User,
+    messages::{MessageTypes, UserFetchType},
+};
+
+// Default for how many frames can pile up for a single session before it gets kicked
+pub const OUTBOUND_QUEUE_SIZE: usize = 64;
+
+// Close code sent to clients that can't keep up with their queue
+pub const SLOW_CONSUMER: u16 = 4008;
+
+pub type Outbound = mpsc::Sender<Arc<Frame>>;
+
+// An event shared between every recipient of a broadcast.
+// Each wire format is only encoded the first time a session asks for it.
+pub struct Frame {
+    pub event: MessageTypes,
+    json: OnceLock<ByteString>,
+    cbor: OnceLock<Bytes>,
+}
+
+impl Frame {
+    pub fn new(event: MessageTypes) -> Arc<Self> {
+        Arc::new(Self {
+            event,
+            json: OnceLock::new(),
+            cbor: OnceLock::new(),
+        })
+    }
+
+    pub fn json(&self) -> ByteString {
+        self.json
+            .get_or_init(|| ByteString::from(serde_json::to_string(&self.event).unwrap()))
+            .clone()
+    }
+
+    pub fn cbor(&self) -> Bytes {
+        self.cbor
+            .get_or_init(|| Bytes::from(serde_cbor::to_vec(&self.event).unwrap()))
+            .clone()
+    }
+}
+
+// Out of band instructions for a writer task, never queued behind frames
+pub enum Control {
+    Close(Option<CloseReason>),
+}
+
+// What the Chat keeps for every connected socket. Only senders, the socket itself
+// is owned by the session and its writer task.
+#[derive(Clone)]
+pub struct SessionHandle {
+    pub conn_id: usize,
+    pub session_id: String,
+    pub user: Arc<User>,
+    // what the session signs its messages with, replaced when the profile is edited
+    pub profile: Arc<RwLock<UserFetchType>>,
+    pub outbound: Outbound,
+    pub control: mpsc::UnboundedSender<Control>,
+    // set by the client through PresenceUpdate
+    pub idle: Arc<AtomicBool>,
+}
+
+impl SessionHandle {
+    pub fn close(&self, reason: Option<CloseReason>) {
+        // writer already gone means the socket is already closed
+        let _ = self.control.send(Control::Close(reason));
+    }
+}
+
+pub enum Delivery {
+    Sent,
+    // the queue is full, the client fell too far behind
+    Lagging,
+    // the writer is gone, the session should be pruned
+    Closed,
+}
+
+pub fn deliver(tx: &Outbound, frame: &Arc<Frame>) -> Delivery {
+    match tx.try_send(frame.clone()) {
+        Ok(_) => Delivery::Sent,
+        Err(TrySendError::Full(_)) => Delivery::Lagging,
+        Err(TrySendError::Closed(_)) => Delivery::Closed,
+    }
+}
+
+pub fn slow_consumer() -> CloseReason {
+    CloseReason {
+        code: CloseCode::Other(SLOW_CONSUMER),
+        description: Some("Too far behind".to_string()),
+    }
+}
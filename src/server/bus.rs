@@ -0,0 +1,196 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgListener, types::Uuid, PgPool};
+use tokio::sync::broadcast;
+
+use crate::{
+    db,
+    messages::{MessageTypes, UserFetchType},
+};
+
+// Postgres refuses NOTIFY payloads of 8000 bytes or more, leave room for the wrapper
+pub const NOTIFY_LIMIT: usize = 7900;
+
+pub const CHANNEL: &str = "raspberry_events";
+
+// How many envelopes a slow subscriber can miss before it starts skipping
+const BUS_CAPACITY: usize = 1024;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", content = "data")]
+pub enum Target {
+    All,
+    Guild(String),
+    Users(Vec<usize>),
+    // members of any of the guilds plus the users, each only once
+    Audience {
+        guilds: Vec<String>,
+        users: Vec<usize>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", content = "data")]
+pub enum Event {
+    Dispatch {
+        target: Target,
+        event: MessageTypes,
+    },
+    // someone joined or left a guild from outside their own sessions (added by someone else, kicked)
+    Membership {
+        guild_id: String,
+        user_id: usize,
+        joined: bool,
+    },
+    // the guild is gone, nobody is in it anymore
+    GuildDelete {
+        guild_id: String,
+    },
+    // the user edited their profile, their sessions sign messages with the new one
+    Profile {
+        user: UserFetchType,
+    },
+    // session_id None means every session of the user
+    Disconnect {
+        user_id: usize,
+        session_id: Option<String>,
+        code: u16,
+        reason: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Envelope {
+    // node that published it, so it can skip its own events
+    pub origin: Uuid,
+    pub event: Event,
+}
+
+// Whatever carries events between raspberry-backend-app processes.
+// Every node applies its own events locally, the bus only reaches the others.
+#[async_trait]
+pub trait EventBus: Send + Sync {
+    async fn publish(&self, envelope: &Envelope);
+
+    fn subscribe(&self) -> broadcast::Receiver<Arc<Envelope>>;
+}
+
+// Single node, and tests where several Chats share one bus to pretend to be a cluster
+pub struct InMemoryBus {
+    tx: broadcast::Sender<Arc<Envelope>>,
+}
+
+impl InMemoryBus {
+    pub fn new() -> Self {
+        Self {
+            tx: broadcast::channel(BUS_CAPACITY).0,
+        }
+    }
+}
+
+impl Default for InMemoryBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl EventBus for InMemoryBus {
+    async fn publish(&self, envelope: &Envelope) {
+        // no subscribers is fine
+        let _ = self.tx.send(Arc::new(envelope.clone()));
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Arc<Envelope>> {
+        self.tx.subscribe()
+    }
+}
+
+// What actually goes through NOTIFY
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+enum Notice {
+    Inline(Box<Envelope>),
+    // too big for NOTIFY, stored in bus_event
+    Stored(i64),
+}
+
+// LISTEN/NOTIFY on the database every node already talks to
+pub struct PgEventBus {
+    pool: PgPool,
+    tx: broadcast::Sender<Arc<Envelope>>,
+}
+
+impl PgEventBus {
+    pub async fn connect(pool: PgPool) -> sqlx::Result<Self> {
+        let mut listener = PgListener::connect_with(&pool).await?;
+        listener.listen(CHANNEL).await?;
+        let (tx, _) = broadcast::channel(BUS_CAPACITY);
+
+        let sender = tx.clone();
+        let pool2 = pool.clone();
+        actix_web::rt::spawn(async move {
+            loop {
+                // recv reconnects by itself, errors are just the lost connection
+                let notification = match listener.recv().await {
+                    Ok(n) => n,
+                    Err(err) => {
+                        log::error!("Event bus listener: {}", err);
+                        continue;
+                    }
+                };
+                let envelope = match serde_json::from_str::<Notice>(notification.payload()) {
+                    Ok(Notice::Inline(envelope)) => *envelope,
+                    Ok(Notice::Stored(id)) => match db::bus::get_event(id, &pool2).await {
+                        Ok(payload) => match serde_json::from_str(&payload) {
+                            Ok(envelope) => envelope,
+                            Err(err) => {
+                                log::error!("Bad stored event {}: {}", id, err);
+                                continue;
+                            }
+                        },
+                        Err(err) => {
+                            log::error!("Missing stored event {}: {}", id, err);
+                            continue;
+                        }
+                    },
+                    Err(err) => {
+                        log::error!("Bad event bus payload: {}", err);
+                        continue;
+                    }
+                };
+                let _ = sender.send(Arc::new(envelope));
+            }
+        });
+
+        Ok(Self { pool, tx })
+    }
+}
+
+#[async_trait]
+impl EventBus for PgEventBus {
+    async fn publish(&self, envelope: &Envelope) {
+        let payload = serde_json::to_string(&Notice::Inline(Box::new(envelope.clone()))).unwrap();
+        let payload = if payload.len() < NOTIFY_LIMIT {
+            payload
+        } else {
+            match db::bus::store_event(serde_json::to_string(envelope).unwrap(), &self.pool).await
+            {
+                Ok(id) => serde_json::to_string(&Notice::Stored(id)).unwrap(),
+                Err(err) => {
+                    log::error!("Could not store event: {}", err);
+                    return;
+                }
+            }
+        };
+        if let Err(err) = db::bus::notify(CHANNEL, payload, &self.pool).await {
+            log::error!("Could not publish event: {}", err);
+        }
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Arc<Envelope>> {
+        self.tx.subscribe()
+    }
+}
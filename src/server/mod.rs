@@ -0,0 +1,589 @@
+use std::{
+    clone::Clone,
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use utoipa::{self, ToSchema};
+
+use serde::{Deserialize, Serialize};
+use actix_ws::{CloseCode, CloseReason};
+use sqlx::types::Uuid;
+use tokio::sync::broadcast::error::RecvError;
+// use serde_json;
+
+use crate::{
+    db::models::{Channel, Guild, User},
+    messages::{
+        ChannelCreateType, GuildCreateType, GuildDeleteType, MemberCreateType,
+        MemberRemoveType,
+        Message, // MessageUpateType
+        MessageTypes,
+    },
+};
+
+use self::broadcast::{Delivery, Frame, SessionHandle};
+use self::bus::{Envelope, Event, EventBus, Target};
+use self::registry::Registry;
+
+pub mod broadcast;
+pub mod bus;
+pub mod moderation;
+pub mod presence;
+pub mod profile;
+pub mod registry;
+
+#[derive(Serialize, Deserialize)]
+pub struct AuthCookie {
+    pub user_id: i64,
+    pub session_id: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct LoginEvent {
+    #[schema(example = "test@test.com")]
+    pub email: String,
+    #[schema(example = "abcd1234")]
+    pub password: String,
+    // captcha answer, can be left out with CAPTCHA=none
+    #[schema(example = "bruhmeme")]
+    #[serde(default)]
+    pub code: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TokenEvent {
+    #[schema(example = "test@test.com")]
+    pub email: String,
+    #[schema(example = "abcd1234")]
+    pub password: String,
+    // only when two-factor is on
+    #[schema(example = "123456")]
+    pub code: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ApplicationCreateEvent {
+    // also the bot's username
+    #[schema(example = "raspbot")]
+    pub name: String,
+    #[schema(example = "Says hi")]
+    pub description: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MfaLoginEvent {
+    #[schema(example = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08")]
+    pub ticket: String,
+    // authenticator code or a recovery code
+    #[schema(example = "123456")]
+    pub code: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TotpConfirmEvent {
+    #[schema(example = "123456")]
+    pub code: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TotpDisableEvent {
+    #[schema(example = "abcd1234")]
+    pub password: String,
+    #[schema(example = "123456")]
+    pub code: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SignUpEvent {
+    #[schema(example = "test")]
+    pub username: String,
+    #[schema(example = "test@test.com")]
+    pub email: String,
+    #[schema(example = "abcd1234")]
+    pub password: String,
+    // captcha answer, can be left out with CAPTCHA=none
+    #[schema(example = "bruhmeme")]
+    #[serde(default)]
+    pub code: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AccountDeleteEvent {
+    #[schema(example = "abcd1234")]
+    pub password: String,
+    // only when two-factor is on
+    #[schema(example = "123456")]
+    pub code: Option<String>,
+}
+
+// left out fields stay as they are, an empty description or profile clears it
+#[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+pub struct ProfileUpdateEvent {
+    #[schema(example = "test2")]
+    pub username: Option<String>,
+    #[schema(example = "hi")]
+    pub description: Option<String>,
+    // avatar url
+    #[schema(example = "https://example.com/avatar.png")]
+    pub profile: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct EmailChangeEvent {
+    #[schema(example = "new@test.com")]
+    pub email: String,
+    #[schema(example = "abcd1234")]
+    pub password: String,
+}
+
+// left out fields stay as they are, roles need a superuser
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AdminUserUpdateEvent {
+    #[schema(example = true)]
+    pub is_staff: Option<bool>,
+    #[schema(example = false)]
+    pub is_superuser: Option<bool>,
+}
+
+// no hours is until someone lifts it
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AdminSuspendEvent {
+    #[schema(example = "Spamming invites")]
+    pub reason: Option<String>,
+    #[schema(example = 72)]
+    pub hours: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ForgotPasswordEvent {
+    #[schema(example = "test@test.com")]
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ResetPasswordEvent {
+    #[schema(example = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08")]
+    pub token: String,
+    #[schema(example = "abcd1234")]
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct VerifyEvent {
+    pub code: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ClientEvent {
+    #[serde(flatten)]
+    pub data: MessageTypes,
+    pub client_id: usize,
+    pub room: String,
+}
+
+impl utoipa::ToSchema for ClientEvent {
+    fn schema() -> utoipa::openapi::schema::Schema {
+        utoipa::openapi::ObjectBuilder::new()
+            .property(
+                "client_id",
+                utoipa::openapi::ObjectBuilder::new()
+                    .schema_type(utoipa::openapi::SchemaType::Integer)
+                    .format(Some(utoipa::openapi::SchemaFormat::KnownFormat(
+                        utoipa::openapi::schema::KnownFormat::Int64,
+                    ))),
+            )
+            .required("client_id")
+            .property(
+                "room",
+                utoipa::openapi::Object::with_type(utoipa::openapi::SchemaType::String),
+            )
+            .required("room")
+            .property(
+                "type",
+                utoipa::openapi::Object::with_type(utoipa::openapi::SchemaType::String),
+            )
+            .required("room")
+            .property(
+                "data",
+                utoipa::openapi::Object::with_type(utoipa::openapi::SchemaType::Object),
+            )
+            .required("data")
+            .example(Some(serde_json::json!({
+                "type": "MessageCreate",
+                "data": {
+                    "content": "test123"
+                },
+                "room": "5fe9d2ab-2174-4a30-8245-cc5de2563dce",
+                "client_id": 1
+            })))
+            .into()
+    }
+}
+
+#[derive(Clone)]
+pub struct Chat {
+    // Sessions, online guild members and which guilds every session is in.
+    // Sharded so unrelated users never wait on each other.
+    pub registry: Arc<Registry>,
+    // This is useless
+    pub visitor_count: Arc<AtomicUsize>,
+    // hands out SessionHandle::conn_id
+    next_conn_id: Arc<AtomicUsize>,
+    // outbound queue size per session, clients that fill it up get disconnected
+    pub queue_limit: usize,
+    // reaches the sessions connected to other nodes
+    pub bus: Arc<dyn EventBus>,
+    pub node_id: Uuid,
+}
+
+impl Chat {
+    pub fn new(visitor_count: Arc<AtomicUsize>, queue_limit: usize, bus: Arc<dyn EventBus>) -> Self {
+        Chat {
+            registry: Arc::new(Registry::new()),
+            visitor_count,
+            next_conn_id: Arc::new(AtomicUsize::new(0)),
+            queue_limit,
+            bus,
+            node_id: Uuid::new_v4(),
+        }
+    }
+
+    // Apply events published by other nodes to the sessions connected here.
+    // Call once after creating the Chat.
+    pub fn listen(&self) {
+        let chat = self.clone();
+        let mut rx = self.bus.subscribe();
+        actix_web::rt::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(envelope) => {
+                        if envelope.origin != chat.node_id {
+                            chat.apply(envelope.event.clone()).await;
+                        }
+                    }
+                    Err(RecvError::Lagged(n)) => {
+                        log::warn!("Missed {} events from the bus", n)
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    // apply locally, then let the other nodes know
+    async fn emit(&self, event: Event) {
+        self.apply(event.clone()).await;
+        self.bus
+            .publish(&Envelope {
+                origin: self.node_id,
+                event,
+            })
+            .await;
+    }
+
+    async fn apply(&self, event: Event) {
+        match event {
+            Event::Dispatch { target, event } => {
+                let targets = match target {
+                    Target::All => self.registry.all_sessions(),
+                    Target::Guild(guild_id) => self.handles_for(&self.registry.members_of(&guild_id)),
+                    Target::Users(users) => self.handles_for(&users),
+                    Target::Audience { guilds, users } => {
+                        let mut everyone: HashSet<usize> = users.into_iter().collect();
+                        for guild_id in guilds {
+                            everyone.extend(self.registry.members_of(&guild_id));
+                        }
+                        self.handles_for(&everyone.into_iter().collect::<Vec<usize>>())
+                    }
+                };
+                if !targets.is_empty() {
+                    self.deliver(targets, event).await;
+                }
+            }
+            Event::Membership {
+                guild_id,
+                user_id,
+                joined,
+            } => {
+                if joined {
+                    for h in self.registry.sessions_of(user_id) {
+                        self.registry.join_guild(&guild_id, user_id, h.conn_id);
+                    }
+                } else {
+                    self.registry.leave_guild(&guild_id, user_id);
+                }
+            }
+            Event::GuildDelete { guild_id } => {
+                for user_id in self.registry.members_of(&guild_id) {
+                    self.registry.leave_guild(&guild_id, user_id);
+                }
+            }
+            Event::Profile { user } => {
+                for h in self.registry.sessions_of(user.id as usize) {
+                    *h.profile.write().unwrap() = user.clone();
+                }
+            }
+            Event::Disconnect {
+                user_id,
+                session_id,
+                code,
+                reason,
+            } => {
+                for h in self.registry.sessions_of(user_id) {
+                    if session_id.as_ref().is_none_or(|s| *s == h.session_id) {
+                        h.close(Some(CloseReason {
+                            code: CloseCode::Other(code),
+                            description: Some(reason.clone()),
+                        }));
+                    }
+                }
+            }
+        }
+    }
+
+    // Close a user's gateway sessions on every node, or just the one bound to session_id.
+    // The sessions clean up after themselves once their socket is gone.
+    pub async fn disconnect_user(
+        &self,
+        user_id: usize,
+        session_id: Option<String>,
+        code: u16,
+        reason: &str,
+    ) {
+        self.emit(Event::Disconnect {
+            user_id,
+            session_id,
+            code,
+            reason: reason.to_string(),
+        })
+        .await;
+    }
+
+    #[allow(dead_code)]
+    pub async fn get_sessions_by_user_id(&self, user_id: usize) -> Option<Vec<SessionHandle>> {
+        let sessions = self.registry.sessions_of(user_id);
+        if sessions.is_empty() {
+            None
+        } else {
+            Some(sessions)
+        }
+    }
+
+    pub fn next_conn_id(&self) -> usize {
+        self.next_conn_id.fetch_add(1, Ordering::SeqCst)
+    }
+
+    pub async fn insert_session(&self, user_id: usize, handle: SessionHandle) {
+        self.registry.insert_session(user_id, handle);
+    }
+
+    // Forget a session and leave every guild nobody else of that user is still in
+    pub async fn remove_session(&self, user_id: usize, conn_id: usize) {
+        for guild_id in self.registry.remove_session(user_id, conn_id) {
+            log::info!("{} id leaving guild_id {}", user_id, guild_id);
+            self.send_guild_message(
+                &guild_id,
+                MessageTypes::MessageCreate(Message::system(
+                    "Someone left".to_string(),
+                    &guild_id,
+                    0,
+                )),
+            )
+            .await;
+        }
+    }
+
+    pub async fn find_user_by_id(&self, user_id: usize) -> Option<User> {
+        self.registry
+            .sessions_of(user_id)
+            .first()
+            .map(|h| h.user.as_ref().clone())
+    }
+
+    #[allow(dead_code)]
+    pub async fn list_guilds(&self) -> Vec<String> {
+        // Now lists guilds
+        self.registry.guild_ids()
+    }
+
+    pub async fn guilds_of(&self, conn_id: usize) -> HashSet<String> {
+        self.registry.guilds_of(conn_id)
+    }
+
+    pub async fn new_visitor(&self) -> usize {
+        // Arc<AtomicUsize> does not need another layer bruh
+        self.visitor_count.fetch_add(1, Ordering::SeqCst)
+    }
+
+    // now rooms are guilds
+    #[allow(dead_code)]
+    pub async fn leave_guild(&self, guild_id: String, user_id: usize) {
+        log::info!("{} id leaving guild_id {}", user_id, guild_id);
+
+        if self.registry.leave_guild(&guild_id, user_id) {
+            self.send_guild_message(
+                &guild_id,
+                MessageTypes::MessageCreate(Message::system(
+                    "Someone left".to_string(),
+                    &guild_id,
+                    0,
+                )),
+            )
+            .await;
+        }
+    }
+
+    pub async fn join_guild(&self, guild_id: String, user_id: usize, conn_id: usize) {
+        log::info!("{} id joining guild_id {}", user_id, guild_id);
+
+        // a second session of someone already online is not news
+        if self.registry.join_guild(&guild_id, user_id, conn_id) {
+            self.send_guild_message(
+                &guild_id,
+                MessageTypes::MessageCreate(Message::system(
+                    "Someone joined".to_string(),
+                    &guild_id,
+                    0,
+                )),
+            )
+            .await;
+        }
+    }
+
+    pub async fn insert_id(&self, room: String, user_id: usize, conn_id: usize) {
+        // insert_id now works on rooms only, same as join_guild but quiet
+        self.registry.join_guild(&room, user_id, conn_id);
+    }
+
+    // Someone else put the user in the guild. Their sessions on every node join it,
+    // they get the guild and its channels, the guild gets a MemberCreate.
+    pub async fn add_member(&self, guild: Guild, user_id: usize, channels: Vec<Channel>) {
+        let guild_id = guild.id.to_string();
+        self.emit(Event::Membership {
+            guild_id: guild_id.clone(),
+            user_id,
+            joined: true,
+        })
+        .await;
+        self.send_to_id(
+            user_id,
+            MessageTypes::GuildCreate(GuildCreateType {
+                guild: guild.clone(),
+            }),
+        )
+        .await;
+        for channel in channels {
+            self.send_to_id(
+                user_id,
+                MessageTypes::ChannelCreate(ChannelCreateType { channel }),
+            )
+            .await;
+        }
+        self.send_guild_message(
+            &guild_id,
+            MessageTypes::MemberCreate(MemberCreateType { id: user_id, guild }),
+        )
+        .await;
+    }
+
+    // The guild gets a MemberRemove, the user's own sessions included so their client
+    // drops it too, then the user is out of the guild on every node
+    pub async fn remove_member(&self, guild_id: String, user_id: usize) {
+        self.send_guild_message(
+            &guild_id,
+            MessageTypes::MemberRemove(MemberRemoveType {
+                id: user_id,
+                room: guild_id.clone(),
+            }),
+        )
+        .await;
+        self.emit(Event::Membership {
+            guild_id,
+            user_id,
+            joined: false,
+        })
+        .await;
+    }
+
+    // Everyone in the guild gets a GuildDelete, then it's dropped on every node
+    pub async fn delete_guild(&self, id: Uuid) {
+        let guild_id = id.to_string();
+        self.send_guild_message(&guild_id, MessageTypes::GuildDelete(GuildDeleteType { id }))
+            .await;
+        self.emit(Event::GuildDelete { guild_id }).await;
+    }
+
+    // send global. Please try to not use this
+    pub async fn send(&self, msg: MessageTypes) {
+        self.emit(Event::Dispatch {
+            target: Target::All,
+            event: msg,
+        })
+        .await;
+    }
+
+    // send a message to a guild, on every node
+    pub async fn send_guild_message(&self, room: &str, message: MessageTypes) {
+        log::info!("SENDING TO GUILD: {}", room);
+        self.emit(Event::Dispatch {
+            target: Target::Guild(room.to_string()),
+            event: message,
+        })
+        .await;
+    }
+
+    // send a message to all the sessions active on user_id
+    pub async fn send_to_id(&self, id: usize, message: MessageTypes) {
+        self.emit(Event::Dispatch {
+            target: Target::Users(vec![id]),
+            event: message,
+        })
+        .await;
+    }
+
+    pub async fn send_dm(&self, id1: usize, id2: usize, message: MessageTypes) {
+        // one user might be offline, handles_for skips them
+        self.emit(Event::Dispatch {
+            target: Target::Users(vec![id1, id2]),
+            event: message,
+        })
+        .await;
+    }
+
+    // snapshot the handles of every session owned by the given users
+    fn handles_for(&self, users: &[usize]) -> Vec<(usize, SessionHandle)> {
+        users
+            .iter()
+            .flat_map(|user_id| {
+                self.registry
+                    .sessions_of(*user_id)
+                    .into_iter()
+                    .map(move |h| (*user_id, h))
+            })
+            .collect()
+    }
+
+    // encode once, then push the frame into every queue without waiting on slow clients
+    async fn deliver(&self, targets: Vec<(usize, SessionHandle)>, message: MessageTypes) {
+        let frame = Frame::new(message);
+        let mut gone = Vec::new();
+        for (user_id, handle) in targets {
+            match broadcast::deliver(&handle.outbound, &frame) {
+                Delivery::Sent => (),
+                Delivery::Lagging => {
+                    log::warn!("User {} fell behind, disconnecting", user_id);
+                    handle.close(Some(broadcast::slow_consumer()));
+                    gone.push((user_id, handle.conn_id));
+                }
+                Delivery::Closed => gone.push((user_id, handle.conn_id)),
+            }
+        }
+        for (user_id, conn_id) in gone {
+            // not remove_session, the "Someone left" would recurse into deliver
+            self.registry.remove_session(user_id, conn_id);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use sqlx::{types::Uuid, PgPool};
+
+use super::Chat;
+use crate::{
+    db::{self, guild_ban::GuildBan},
+    messages::{MessageDeleteType, MessageTypes, WsMemberBan},
+};
+
+// most a ban can wipe at once, a week
+pub const MAX_PURGE_HOURS: i32 = 24 * 7;
+
+const DB_ERROR: &str = "Something went wrong, try again";
+
+// What someone who's banned hears when they try to join again
+pub fn describe_ban(ban: &GuildBan) -> String {
+    let mut msg = match ban.expires_at {
+        Some(until) => format!(
+            "You are banned from this guild until {} UTC",
+            until.format("%Y-%m-%d %H:%M")
+        ),
+        None => "You are banned from this guild".to_string(),
+    };
+    if let Some(reason) = &ban.reason {
+        msg.push_str(": ");
+        msg.push_str(reason);
+    }
+    msg
+}
+
+fn check_ban(ban: &WsMemberBan) -> Result<(), &'static str> {
+    if ban.reason.as_ref().is_some_and(|r| r.chars().count() > 255) {
+        return Err("A ban reason can be 255 characters at most");
+    }
+    // hours become minutes in the db, keep them in an i32
+    if ban.hours.is_some_and(|h| h <= 0 || h > i32::MAX / 60) {
+        return Err("hours must be a positive number, leave it out for a permanent ban");
+    }
+    if ban
+        .purge_hours
+        .is_some_and(|h| h <= 0 || h > MAX_PURGE_HOURS)
+    {
+        return Err("purge_hours must be between 1 and 168");
+    }
+    Ok(())
+}
+
+// Only the creator moderates a guild, and not themselves
+async fn moderates(
+    moderator_id: i64,
+    guild_id: Uuid,
+    user_id: i64,
+    pool: &PgPool,
+) -> Result<(), &'static str> {
+    if user_id == moderator_id {
+        return Err("You can't do that to yourself");
+    }
+    match db::ws_session::get_guild_by_id(guild_id, pool).await {
+        Ok(guild) if guild.creator_id == moderator_id => Ok(()),
+        _ => Err("Only the guild's owner can do that"),
+    }
+}
+
+// The Err is what the moderator gets told
+impl Chat {
+    // Out of the member table and out of every node's guild, false if they weren't in it
+    async fn remove_from_guild(
+        &self,
+        guild_id: Uuid,
+        user_id: i64,
+        pool: &PgPool,
+    ) -> Result<bool, &'static str> {
+        match db::ws_session::leave_guild(user_id, guild_id, pool).await {
+            Ok(res) if res.rows_affected() > 0 => {
+                self.remove_member(guild_id.to_string(), user_id as usize)
+                    .await;
+                Ok(true)
+            }
+            Ok(_) => Ok(false),
+            Err(err) => {
+                log::error!("{:?}", err);
+                Err(DB_ERROR)
+            }
+        }
+    }
+
+    pub async fn kick(
+        &self,
+        moderator_id: i64,
+        guild_id: Uuid,
+        user_id: i64,
+        pool: &PgPool,
+    ) -> Result<(), &'static str> {
+        moderates(moderator_id, guild_id, user_id, pool).await?;
+        match self.remove_from_guild(guild_id, user_id, pool).await? {
+            true => Ok(()),
+            false => Err("They aren't in this guild"),
+        }
+    }
+
+    // Banning someone who already left still keeps them out
+    pub async fn ban(
+        &self,
+        moderator_id: i64,
+        ban: &WsMemberBan,
+        pool: &PgPool,
+    ) -> Result<(), &'static str> {
+        check_ban(ban)?;
+        moderates(moderator_id, ban.guild_id, ban.user_id, pool).await?;
+        if let Err(err) = db::guild_ban::ban(
+            ban.guild_id,
+            ban.user_id,
+            moderator_id,
+            ban.reason.to_owned(),
+            ban.hours.map(|h| h * 60),
+            pool,
+        )
+        .await
+        {
+            log::error!("{:?}", err);
+            return Err(DB_ERROR);
+        }
+        self.remove_from_guild(ban.guild_id, ban.user_id, pool)
+            .await?;
+        let Some(purge_hours) = ban.purge_hours else {
+            return Ok(());
+        };
+        match db::guild_ban::purge_messages(ban.guild_id, ban.user_id, purge_hours * 60, pool).await
+        {
+            Ok(purged) => {
+                for m in purged {
+                    self.send_guild_message(
+                        &ban.guild_id.to_string(),
+                        MessageTypes::MessageDelete(MessageDeleteType {
+                            id: m.id,
+                            channel_id: m.channel_id,
+                        }),
+                    )
+                    .await;
+                }
+                Ok(())
+            }
+            Err(err) => {
+                log::error!("{:?}", err);
+                Err("They're banned, but their messages couldn't be deleted")
+            }
+        }
+    }
+
+    pub async fn unban(
+        &self,
+        moderator_id: i64,
+        guild_id: Uuid,
+        user_id: i64,
+        pool: &PgPool,
+    ) -> Result<(), &'static str> {
+        moderates(moderator_id, guild_id, user_id, pool).await?;
+        match db::guild_ban::unban(guild_id, user_id, pool).await {
+            Ok(res) if res.rows_affected() == 0 => Err("They aren't banned"),
+            Ok(_) => Ok(()),
+            Err(err) => {
+                log::error!("{:?}", err);
+                Err(DB_ERROR)
+            }
+        }
+    }
+}
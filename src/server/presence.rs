@@ -0,0 +1,233 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+
+use super::{
+    bus::{Event, Target},
+    Chat,
+};
+use crate::{
+    db::{self, models::User},
+    messages::{MessageTypes, PresenceUpdateType, Status},
+    PLACEHOLDER_UUID,
+};
+
+// how often a node touches its gateway_session rows, and how long until
+// rows nobody touched are taken for a dead node's
+pub const HEARTBEAT_SECS: f64 = 30.0;
+pub const STALE_SECS: f64 = 90.0;
+
+// What a user picked for themselves, and what everyone was last told
+#[derive(Clone, Debug)]
+pub struct Presence {
+    // online, dnd or invisible
+    pub status: Status,
+    pub custom_status: Option<String>,
+    pub announced: Status,
+}
+
+// Idle only counts once every session is idle, invisible looks offline to everyone else
+pub fn effective(chosen: Status, idle: &[bool]) -> Status {
+    if idle.is_empty() {
+        return Status::Offline;
+    }
+    match chosen {
+        Status::Invisible | Status::Offline => Status::Offline,
+        Status::Dnd => Status::Dnd,
+        _ if idle.iter().all(|i| *i) => Status::Idle,
+        _ => Status::Online,
+    }
+}
+
+impl Chat {
+    // The live presence if the user is connected to this node, otherwise whatever
+    // the database had (which is how other nodes' users show up)
+    pub fn presence(&self, fallback: PresenceUpdateType) -> PresenceUpdateType {
+        let user_id = fallback.user_id as usize;
+        match self.registry.presence_of(user_id) {
+            Some(p) => {
+                let status = self.registry.effective_status(user_id);
+                PresenceUpdateType {
+                    user_id: fallback.user_id,
+                    status,
+                    custom_status: if status == Status::Offline {
+                        None
+                    } else {
+                        p.custom_status
+                    },
+                }
+            }
+            None => fallback,
+        }
+    }
+
+    // The first session of a user on this node seeds what they picked from the database
+    pub async fn connected(&self, user: &User, conn_id: usize, pool: &PgPool) {
+        if let Err(err) =
+            db::presence::add_session(self.node_id, conn_id as i64, user.id, pool).await
+        {
+            log::error!("{:?}", err);
+        }
+        self.registry.init_presence(
+            user.id as usize,
+            Presence {
+                status: Status::from_db(&user.status),
+                custom_status: user.custom_status.clone(),
+                announced: Status::Offline,
+            },
+        );
+        self.refresh_presence(user.id, pool, false).await;
+    }
+
+    // After remove_session. Offline only once there's no session left on any node.
+    pub async fn disconnected(&self, user_id: i64, conn_id: usize, pool: &PgPool) {
+        if let Err(err) = db::presence::remove_session(self.node_id, conn_id as i64, pool).await {
+            log::error!("{:?}", err);
+        }
+        match db::presence::live(user_id, STALE_SECS, pool).await {
+            Ok(live) if live.idle.is_empty() => {
+                if let Err(err) = db::ws_session::toggle_user_status(user_id, false, pool).await {
+                    log::error!("{:?}", err);
+                }
+            }
+            Ok(_) => {}
+            Err(err) => log::error!("{:?}", err),
+        }
+        self.refresh_presence(user_id, pool, false).await;
+    }
+
+    pub async fn set_presence(
+        &self,
+        user_id: i64,
+        status: Status,
+        custom_status: Option<String>,
+        pool: &PgPool,
+    ) {
+        self.registry.update_presence(user_id as usize, |p| {
+            p.status = status;
+            p.custom_status = custom_status;
+        });
+        self.refresh_presence(user_id, pool, true).await;
+    }
+
+    // Tell guild members and friends if the user's presence changed since last time.
+    // force is for changes the status alone doesn't show, like a new custom status.
+    // The sessions on every node count, and what they picked could have been set on another node.
+    pub async fn refresh_presence(&self, user_id: i64, pool: &PgPool, force: bool) {
+        let uid = user_id as usize;
+        let Some(presence) = self.registry.presence_of(uid) else {
+            return;
+        };
+        let (chosen, custom_status, status) =
+            match db::presence::live(user_id, STALE_SECS, pool).await {
+                Ok(live) => {
+                    let chosen = Status::from_db(&live.status);
+                    (chosen, live.custom_status, effective(chosen, &live.idle))
+                }
+                Err(err) => {
+                    log::error!("{:?}", err);
+                    (
+                        presence.status,
+                        presence.custom_status.clone(),
+                        self.registry.effective_status(uid),
+                    )
+                }
+            };
+        let here = !self.registry.sessions_of(uid).is_empty();
+        if here {
+            self.registry.update_presence(uid, |p| {
+                p.status = chosen;
+                p.custom_status = custom_status.clone();
+                p.announced = status;
+            });
+        } else {
+            self.registry.remove_presence(uid);
+        }
+        if status == presence.announced && !force {
+            return;
+        }
+        // still connected to another node, that one speaks for them now
+        if !here && status != Status::Offline {
+            return;
+        }
+        self.announce(user_id, status, custom_status, pool).await;
+    }
+
+    async fn announce(
+        &self,
+        user_id: i64,
+        status: Status,
+        custom_status: Option<String>,
+        pool: &PgPool,
+    ) {
+        let guilds = match db::ws_session::get_guilds_by_user_id(user_id, pool).await {
+            Ok(glds) => glds
+                .into_iter()
+                .map(|g| g.id.to_string())
+                // everyone online is in Main, that's nobody's business
+                .filter(|g| g != PLACEHOLDER_UUID)
+                .collect(),
+            Err(err) => {
+                log::error!("{:?}", err);
+                vec![]
+            }
+        };
+        let friends = match db::ws_session::get_friend_ids(user_id, pool).await {
+            Ok(ids) => ids.into_iter().map(|id| id as usize).collect(),
+            Err(err) => {
+                log::error!("{:?}", err);
+                vec![]
+            }
+        };
+        self.emit(Event::Dispatch {
+            target: Target::Audience {
+                guilds,
+                users: friends,
+            },
+            event: MessageTypes::PresenceUpdate(PresenceUpdateType {
+                user_id,
+                status,
+                custom_status: if status == Status::Offline {
+                    None
+                } else {
+                    custom_status
+                },
+            }),
+        })
+        .await;
+    }
+
+    // Keeps this node's sessions alive in the database and takes the ones of dead nodes
+    // offline. Call once after creating the Chat.
+    pub fn watch_nodes(&self, pool: PgPool) {
+        let chat = self.clone();
+        actix_web::rt::spawn(async move {
+            let mut interval =
+                actix_web::rt::time::interval(Duration::from_secs_f64(HEARTBEAT_SECS));
+            loop {
+                interval.tick().await;
+                chat.heartbeat(&pool).await;
+            }
+        });
+    }
+
+    pub async fn heartbeat(&self, pool: &PgPool) {
+        if let Err(err) = db::presence::touch(self.node_id, pool).await {
+            log::error!("{:?}", err);
+        }
+        let gone = match db::presence::sweep(STALE_SECS, pool).await {
+            Ok(gone) => gone,
+            Err(err) => {
+                log::error!("{:?}", err);
+                return;
+            }
+        };
+        for user_id in gone {
+            log::info!("User {} went offline with their node", user_id);
+            if let Err(err) = db::ws_session::toggle_user_status(user_id, false, pool).await {
+                log::error!("{:?}", err);
+            }
+            self.announce(user_id, Status::Offline, None, pool).await;
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use sqlx::PgPool;
+
+use super::{
+    bus::{Event, Target},
+    Chat,
+};
+use crate::{
+    db,
+    messages::{MessageTypes, UserFetchType},
+    PLACEHOLDER_UUID,
+};
+
+impl Chat {
+    // New username, avatar or description. Their own sessions pick it up for the
+    // messages they send, everyone sharing a guild with them gets a UserUpdate.
+    pub async fn update_profile(&self, user: UserFetchType, pool: &PgPool) {
+        self.emit(Event::Profile { user: user.clone() }).await;
+        let guilds = match db::ws_session::get_guilds_by_user_id(user.id, pool).await {
+            Ok(glds) => glds
+                .into_iter()
+                .map(|g| g.id.to_string())
+                .filter(|g| g != PLACEHOLDER_UUID)
+                .collect(),
+            Err(err) => {
+                log::error!("{:?}", err);
+                vec![]
+            }
+        };
+        self.emit(Event::Dispatch {
+            target: Target::Audience {
+                guilds,
+                users: vec![user.id as usize],
+            },
+            event: MessageTypes::UserUpdate(user),
+        })
+        .await;
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap, HashSet},
+    hash::{BuildHasher, Hash},
+    sync::{atomic::Ordering, RwLock},
+};
+
+use super::{
+    broadcast::SessionHandle,
+    presence::{self, Presence},
+};
+use crate::messages::Status;
+
+// Power of two so picking a shard is a mask
+pub const SHARDS: usize = 64;
+
+// A HashMap split into independently locked shards. The locks are std locks on purpose,
+// every method finishes synchronously so a guard can never be held across an await.
+pub struct Sharded<K, V> {
+    hasher: RandomState,
+    shards: Box<[RwLock<HashMap<K, V>>]>,
+}
+
+impl<K: Hash + Eq, V> Sharded<K, V> {
+    pub fn new() -> Self {
+        Self {
+            hasher: RandomState::new(),
+            shards: (0..SHARDS).map(|_| RwLock::new(HashMap::new())).collect(),
+        }
+    }
+
+    fn shard(&self, key: &K) -> &RwLock<HashMap<K, V>> {
+        &self.shards[self.hasher.hash_one(key) as usize & (SHARDS - 1)]
+    }
+
+    pub fn read<R>(&self, key: &K, f: impl FnOnce(Option<&V>) -> R) -> R {
+        f(self.shard(key).read().unwrap().get(key))
+    }
+
+    // runs f on the entry, removing it afterwards if it is left empty
+    pub fn update<R>(&self, key: K, f: impl FnOnce(&mut V) -> R) -> R
+    where
+        K: Clone,
+        V: Default + Empty,
+    {
+        let mut shard = self.shard(&key).write().unwrap();
+        let value = shard.entry(key.clone()).or_default();
+        let res = f(value);
+        if value.is_empty() {
+            shard.remove(&key);
+        }
+        res
+    }
+
+    pub fn get(&self, key: &K) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.read(key, |v| v.cloned())
+    }
+
+    pub fn write<R>(&self, key: &K, f: impl FnOnce(Option<&mut V>) -> R) -> R {
+        f(self.shard(key).write().unwrap().get_mut(key))
+    }
+
+    pub fn insert_if_absent(&self, key: K, value: V) {
+        self.shard(&key)
+            .write()
+            .unwrap()
+            .entry(key)
+            .or_insert(value);
+    }
+
+    pub fn remove(&self, key: &K) -> Option<V> {
+        self.shard(key).write().unwrap().remove(key)
+    }
+
+    pub fn keys(&self) -> Vec<K>
+    where
+        K: Clone,
+    {
+        self.shards
+            .iter()
+            .flat_map(|s| s.read().unwrap().keys().cloned().collect::<Vec<K>>())
+            .collect()
+    }
+
+    pub fn for_each(&self, mut f: impl FnMut(&K, &V)) {
+        for shard in self.shards.iter() {
+            for (k, v) in shard.read().unwrap().iter() {
+                f(k, v)
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq, V> Default for Sharded<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub trait Empty {
+    fn is_empty(&self) -> bool;
+}
+
+impl<T> Empty for Vec<T> {
+    fn is_empty(&self) -> bool {
+        Vec::is_empty(self)
+    }
+}
+
+impl<T> Empty for HashSet<T> {
+    fn is_empty(&self) -> bool {
+        HashSet::is_empty(self)
+    }
+}
+
+// Who is connected and where.
+// user -> sessions, guild -> online members and the reverse conn -> guilds
+#[derive(Default)]
+pub struct Registry {
+    sessions: Sharded<usize, Vec<SessionHandle>>,
+    guilds: Sharded<String, HashSet<usize>>,
+    rooms: Sharded<usize, HashSet<String>>,
+    presence: Sharded<usize, Presence>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert_session(&self, user_id: usize, handle: SessionHandle) {
+        self.sessions.update(user_id, |s| s.push(handle));
+    }
+
+    // Returns the guilds the user is no longer online in, which is all of the
+    // session's guilds unless another session of theirs is still around.
+    pub fn remove_session(&self, user_id: usize, conn_id: usize) -> Vec<String> {
+        let others: Vec<usize> = self.sessions.update(user_id, |s| {
+            s.retain(|h| h.conn_id != conn_id);
+            s.iter().map(|h| h.conn_id).collect()
+        });
+        let rooms = self.rooms.update(conn_id, std::mem::take);
+        let mut left = Vec::new();
+        for guild_id in rooms {
+            let still_there = others
+                .iter()
+                .any(|c| self.rooms.read(c, |r| r.is_some_and(|r| r.contains(&guild_id))));
+            if !still_there {
+                self.guilds.update(guild_id.clone(), |g| g.remove(&user_id));
+                left.push(guild_id);
+            }
+        }
+        left
+    }
+
+    pub fn sessions_of(&self, user_id: usize) -> Vec<SessionHandle> {
+        self.sessions
+            .read(&user_id, |s| s.cloned().unwrap_or_default())
+    }
+
+    pub fn all_sessions(&self) -> Vec<(usize, SessionHandle)> {
+        let mut all = Vec::new();
+        self.sessions.for_each(|user_id, s| {
+            all.extend(s.iter().map(|h| (*user_id, h.clone())));
+        });
+        all
+    }
+
+    // Returns true if the user was not online in the guild before
+    pub fn join_guild(&self, guild_id: &str, user_id: usize, conn_id: usize) -> bool {
+        self.rooms
+            .update(conn_id, |r| r.insert(guild_id.to_string()));
+        self.guilds
+            .update(guild_id.to_string(), |g| g.insert(user_id))
+    }
+
+    // Takes the user out of the guild on every one of their sessions.
+    // Returns false if they weren't online in it.
+    pub fn leave_guild(&self, guild_id: &str, user_id: usize) -> bool {
+        for h in self.sessions_of(user_id) {
+            self.rooms.update(h.conn_id, |r| r.remove(guild_id));
+        }
+        self.guilds
+            .update(guild_id.to_string(), |g| g.remove(&user_id))
+    }
+
+    pub fn members_of(&self, guild_id: &str) -> Vec<usize> {
+        self.guilds.read(&guild_id.to_string(), |g| {
+            g.map(|g| g.iter().copied().collect()).unwrap_or_default()
+        })
+    }
+
+    pub fn guilds_of(&self, conn_id: usize) -> HashSet<String> {
+        self.rooms
+            .read(&conn_id, |r| r.cloned().unwrap_or_default())
+    }
+
+    pub fn guild_ids(&self) -> Vec<String> {
+        self.guilds.keys()
+    }
+
+    pub fn init_presence(&self, user_id: usize, presence: Presence) {
+        self.presence.insert_if_absent(user_id, presence);
+    }
+
+    pub fn presence_of(&self, user_id: usize) -> Option<Presence> {
+        self.presence.get(&user_id)
+    }
+
+    pub fn update_presence(&self, user_id: usize, f: impl FnOnce(&mut Presence)) {
+        self.presence.write(&user_id, |p| p.map(f));
+    }
+
+    pub fn remove_presence(&self, user_id: usize) {
+        self.presence.remove(&user_id);
+    }
+
+    // what everyone else should see, across all of the user's sessions here
+    pub fn effective_status(&self, user_id: usize) -> Status {
+        let idle: Vec<bool> = self
+            .sessions_of(user_id)
+            .iter()
+            .map(|h| h.idle.load(Ordering::Relaxed))
+            .collect();
+        let chosen = self
+            .presence_of(user_id)
+            .map_or(Status::Online, |p| p.status);
+        presence::effective(chosen, &idle)
+    }
+}
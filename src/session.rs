@@ -1,15 +1,17 @@
 use std::{
-    collections::HashSet,
-    sync::Arc,
+    sync::{atomic::AtomicBool, Arc, RwLock},
     time::{Duration, Instant},
 };
 
 use crate::db::{self, models};
-use crate::messages::{Handler, Message as Msg, MessageTypes, ReadyEventType, WsReceiveTypes};
+use crate::messages::{
+    Handler, Message as Msg, MessageTypes, ReadyEventType, Status, UserFetchType, WsReceiveTypes,
+};
+use crate::server::broadcast::{self, Control, Delivery, Frame, Outbound, SessionHandle};
 use crate::{controllers::ws::WsMsgType, server, PLACEHOLDER_UUID};
 use actix_ws::{CloseReason, Message, MessageStream, Session};
 // use serde_json;
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, Mutex};
 
 use futures::StreamExt;
 use std::fmt;
@@ -42,6 +44,7 @@ impl fmt::Display for WsReceiveTypes {
                 write!(f, "Fetching member from guild_id {}", m.guild_id)
             }
             WsReceiveTypes::UserFetch(u) => write!(f, "Fetching user {}", u.id),
+            WsReceiveTypes::PresenceUpdate(p) => write!(f, "Presence {:?}", p.status),
             _ => write!(f, "Unimplemented"),
         }
     }
@@ -52,7 +55,8 @@ pub struct WsChatSession {
     // name and id fields are replaced by user model from the database.
     pub user: models::User,
 
-    pub rooms: Arc<Mutex<HashSet<String>>>,
+    // username, avatar etc as they are now, user above is how they were at connect time
+    pub profile: Arc<RwLock<UserFetchType>>,
 
     pub alive: Arc<Mutex<Instant>>,
 
@@ -69,9 +73,109 @@ pub struct WsChatSession {
     pub session: Session,
 
     pub recv_type: WsMsgType,
+
+    // identifies this socket in Chat.sessions, a user can have several
+    pub conn_id: usize,
+
+    // every write to the socket goes through here, drained by the Writer
+    pub outbound: Outbound,
+
+    pub control: mpsc::UnboundedSender<Control>,
+
+    // whether the client said it's away, see server::presence
+    pub idle: Arc<AtomicBool>,
+}
+
+// The only task allowed to write to the socket. Owns the receiving ends of the
+// session's queues and runs until either is closed or the socket errors.
+pub struct Writer {
+    rx: mpsc::Receiver<Arc<Frame>>,
+    control: mpsc::UnboundedReceiver<Control>,
+    session: Session,
+    recv_type: WsMsgType,
+}
+
+impl Writer {
+    pub async fn run(mut self) {
+        loop {
+            tokio::select! {
+                // a pending close should not wait behind a full queue
+                biased;
+                ctrl = self.control.recv() => {
+                    if let Some(Control::Close(reason)) = ctrl {
+                        let _ = self.session.close(reason).await;
+                    }
+                    break;
+                }
+                frame = self.rx.recv() => {
+                    let Some(frame) = frame else { break };
+                    let res = match self.recv_type {
+                        WsMsgType::Json => self.session.text(frame.json()).await,
+                        WsMsgType::Cbor => self.session.binary(frame.cbor()).await,
+                    };
+                    if res.is_err() {
+                        log::info!("Dropping session");
+                        break;
+                    }
+                }
+            }
+        }
+    }
 }
 
 impl WsChatSession {
+    pub fn new(
+        user: models::User,
+        srv: server::Chat,
+        pool: PgPool,
+        session: Session,
+        session_id: String,
+        recv_type: WsMsgType,
+    ) -> (Self, Writer) {
+        let (outbound, rx) = mpsc::channel(srv.queue_limit);
+        let (control, control_rx) = mpsc::unbounded_channel();
+        let writer = Writer {
+            rx,
+            control: control_rx,
+            session: session.clone(),
+            recv_type: recv_type.clone(),
+        };
+        (
+            WsChatSession {
+                profile: Arc::new(RwLock::new(user.clone().into())),
+                user,
+                alive: Arc::new(Mutex::new(Instant::now())),
+                conn_id: srv.next_conn_id(),
+                srv,
+                pool,
+                session_id,
+                session,
+                recv_type,
+                outbound,
+                control,
+                idle: Arc::new(AtomicBool::new(false)),
+            },
+            writer,
+        )
+    }
+
+    // what the Chat gets to keep, no access to the socket itself
+    pub fn handle(&self) -> SessionHandle {
+        SessionHandle {
+            conn_id: self.conn_id,
+            session_id: self.session_id.clone(),
+            user: Arc::new(self.user.clone()),
+            profile: self.profile.clone(),
+            outbound: self.outbound.clone(),
+            control: self.control.clone(),
+            idle: self.idle.clone(),
+        }
+    }
+
+    pub fn author(&self) -> UserFetchType {
+        self.profile.read().unwrap().clone()
+    }
+
     pub fn decode_json(&self, s: &str) -> serde_json::Result<WsReceiveTypes> {
         serde_json::from_str(s)
     }
@@ -87,20 +191,14 @@ impl WsChatSession {
     // }
 
     pub async fn send_event(&self, msg: MessageTypes) {
-        // println!("{}", serde_json::to_string(&msg).unwrap_or("Something failed idk".to_string()));
-        match self.recv_type {
-            WsMsgType::Json => self
-                .session
-                .clone()
-                .text(serde_json::to_string(&msg).unwrap())
-                .await
-                .unwrap(),
-            WsMsgType::Cbor => self
-                .session
-                .clone()
-                .binary(serde_cbor::to_vec(&msg).unwrap())
-                .await
-                .unwrap(),
+        // same rules as broadcasts, a client that stops reading gets kicked
+        match broadcast::deliver(&self.outbound, &Frame::new(msg)) {
+            Delivery::Sent => (),
+            Delivery::Lagging => {
+                let _ = self.control.send(Control::Close(Some(broadcast::slow_consumer())));
+            }
+            // the writer is gone, the read loop will notice soon enough
+            Delivery::Closed => (),
         }
     }
 
@@ -123,36 +221,38 @@ impl WsChatSession {
     }
 
     pub async fn disconnect(&self, reason: Option<CloseReason>) {
-        let session = self.session.clone();
-        // idk if closing session here is a good idea but eh
-        let _ = session.close(reason).await;
-        db::ws_session::toggle_user_status(self.user.id, false, &self.pool)
-            .await
-            .unwrap();
+        // the writer closes the socket, it might be in the middle of a write
+        let _ = self.control.send(Control::Close(reason));
+        // also leaves every guild this session was in
+        self.srv
+            .remove_session(self.user.id as usize, self.conn_id)
+            .await;
+        self.srv
+            .disconnected(self.user.id, self.conn_id, &self.pool)
+            .await;
         // self.send_to_all_rooms(Msg::system(
         //     format!("User {} disconneced", self.user.id),
         //     PLACEHOLDER_UUID,
         //     self.user.id,
         // ))
         // .await;
-        for room in &*self.rooms.lock().await {
-            self.srv
-                .leave_guild(room.to_string(), self.user.id as usize)
-                .await;
-        }
     }
 
     pub async fn start(&self, mut stream: MessageStream) {
         // connect
         // join user to guild Main
         self.srv
-            .insert_id(PLACEHOLDER_UUID.to_string(), self.user.id as usize)
+            .insert_id(
+                PLACEHOLDER_UUID.to_string(),
+                self.user.id as usize,
+                self.conn_id,
+            )
             .await;
         // add visitor count, very useless so removing soon!
         let count = self.srv.new_visitor().await;
         // let mut stream = self.stream.lock().await;
         let mut session = self.session.clone();
-        println!("Session_id: {}", self.session_id.clone());
+        log::debug!("Gateway session started for user {}", self.user.id);
         // let user: models::User = match db::ws_session::get_user_by_session_id(self.session_id.clone(), &self.pool).await {
         //     Ok(usr) => usr,
         //     Err(_err) => {
@@ -170,31 +270,26 @@ impl WsChatSession {
         )
         .await
         .unwrap();
-        println!("CODE: {:?}", self.user.code);
         if self.user.code.is_some() {
-            self.send_event(MessageTypes::MessageCreate(Msg::system("WARNING: Your account is not verified. Please check your email and verify at /verify".to_string(), PLACEHOLDER_UUID, 0))).await;
+            self.send_event(MessageTypes::MessageCreate(Msg::system("WARNING: Your account is not verified. Please check your email and verify at /verify, until then you can't create guilds or start DMs".to_string(), PLACEHOLDER_UUID, 0))).await;
         }
         let guilds: Vec<models::Guild> =
             match db::ws_session::get_guilds_by_user_id(self.user.id, &self.pool).await {
                 Ok(glds) => glds,
                 Err(err) => {
-                    println!("{:?}", err);
+                    log::error!("{:?}", err);
                     vec![]
                 }
             };
 
         let mut guildchannels: Vec<models::GuildChannels> = vec![];
 
-        // do smth about each guild the user is in, should occupy rooms for the entire time because
-        // we should not be reading the rooms while it is being initialized
-        let mut rooms = self.rooms.lock().await;
-
+        // do smth about each guild the user is in
         for guild in guilds.clone() {
             // about the permissions part...
             self.srv
-                .join_guild(guild.id.to_string(), self.user.id as usize)
+                .join_guild(guild.id.to_string(), self.user.id as usize, self.conn_id)
                 .await;
-            rooms.insert(guild.id.to_string());
             let channels = db::ws_session::get_channels_by_guild_id(guild.id, &self.pool)
                 .await
                 .unwrap();
@@ -216,13 +311,26 @@ impl WsChatSession {
             // }
         }
 
-        // Drop mutex guard after
-        drop(rooms);
+        self.srv
+            .connected(&self.user, self.conn_id, &self.pool)
+            .await;
+        let presences = match db::ws_session::get_presences(self.user.id, &self.pool).await {
+            Ok(p) => p
+                .into_iter()
+                .map(|p| self.srv.presence(p))
+                .filter(|p| p.status != Status::Offline)
+                .collect(),
+            Err(err) => {
+                log::error!("{:?}", err);
+                vec![]
+            }
+        };
 
         // ready event
         self.send_event(MessageTypes::ReadyEvent(ReadyEventType {
             user: self.user.clone().into(),
             guilds: guildchannels,
+            presences,
         }))
         .await;
         self.send_event(MessageTypes::MessageCreate(Msg::system(
@@ -258,21 +366,19 @@ impl WsChatSession {
                     *self.alive.lock().await = Instant::now();
                 }
                 Message::Text(s) => {
-                    log::info!("Relaying text, {}", s);
+                    log::debug!("Relaying text");
                     let s: &str = s.as_ref();
                     let val: Option<WsReceiveTypes> = self.decode_json(s.trim()).ok();
                     /* Starting from binary update, text events will be deprecated */
                     if let Some(val) = val {
-                        println!("{}", val);
+                        log::debug!("{}", val);
                         val.handle(self.to_owned()).await;
                     }
                 }
                 Message::Binary(b) => {
-                    // println!("{}", serde_cbor::from_slice(b.as_ref()).unwrap());
-                    println!("{:?}", serde_cbor::from_slice::<WsReceiveTypes>(b.as_ref()));
                     let val: Option<WsReceiveTypes> = serde_cbor::from_slice(b.as_ref()).ok();
                     if let Some(val) = val {
-                        println!("{}", val);
+                        log::debug!("{}", val);
                         val.handle(self.to_owned()).await;
                     }
                 }
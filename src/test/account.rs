@@ -0,0 +1,117 @@
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use crate::db;
+    use crate::messages::WsGuildCreate;
+    use crate::test::{db_user, dm};
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_delete_account_keeps_other_side_of_dms(pool: PgPool) {
+        let earlier = db_user("earlier", &pool).await;
+        let user = db_user("leaving", &pool).await;
+        let friend = db_user("friend", &pool).await;
+        let app = db::applications::create_application(user, "leavingbot".to_string(), None, &pool)
+            .await
+            .unwrap();
+        let with_earlier = dm(earlier, friend, &pool).await;
+        let with_user = dm(user, friend, &pool).await;
+        let with_bot = dm(friend, app.bot_id, &pool).await;
+        // and one nobody will be left in
+        dm(user, app.bot_id, &pool).await;
+        for (author, channel) in [
+            (friend, with_earlier),
+            (earlier, with_earlier),
+            (friend, with_user),
+            (user, with_user),
+            (friend, with_bot),
+            (app.bot_id, with_bot),
+        ] {
+            db::ws_session::create_message("hi".to_string(), author, channel, &pool)
+                .await
+                .unwrap();
+        }
+        let ghost = db::account::ghost(&mut pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        // an earlier deletion already left friend a DM with the ghost
+        db::account::delete_account(earlier, &pool).await.unwrap();
+        let messages = db::ws_session::fetch_message(with_earlier, &pool)
+            .await
+            .unwrap();
+        assert_eq!(messages.iter().filter(|m| m.author_id == ghost).count(), 1);
+
+        let deleted = db::account::delete_account(user, &pool).await.unwrap();
+        assert!(deleted.guilds.is_empty());
+
+        // everything ends up in the one DM friend has with the ghost
+        let dms = db::account::dm_channels(friend, &pool).await.unwrap();
+        assert_eq!(dms.len(), 1);
+        assert_eq!(dms[0].id, with_earlier);
+        let messages = db::ws_session::fetch_message(with_earlier, &pool)
+            .await
+            .unwrap();
+        assert_eq!(messages.len(), 6);
+        assert_eq!(messages.iter().filter(|m| m.author_id == friend).count(), 3);
+        assert_eq!(messages.iter().filter(|m| m.author_id == ghost).count(), 3);
+        assert!(db::ws_session::get_user_by_id(app.bot_id, &pool)
+            .await
+            .is_err());
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_ghost_made_when_name_was_taken(pool: PgPool) {
+        // a deployment where someone had the name before migration 14 ran
+        sqlx::query("DELETE FROM users WHERE email = $1")
+            .bind(db::account::GHOST_EMAIL)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let squatter = db_user("squatter", &pool).await;
+        sqlx::query("UPDATE users SET username = $1 WHERE id = $2")
+            .bind(db::account::GHOST_USERNAME)
+            .bind(squatter)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let user = db_user("leaving", &pool).await;
+
+        db::account::delete_account(user, &pool).await.unwrap();
+
+        let ghost = db::account::ghost(&mut pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        assert_ne!(ghost, squatter);
+        assert!(db::account::reserved_username(" [Deleted] "));
+        assert!(db::oidc::username_taken("[deleted]", &pool).await.unwrap());
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_delete_account_hands_over_or_deletes_guilds(pool: PgPool) {
+        let user = db_user("leaving", &pool).await;
+        let member = db_user("member", &pool).await;
+        let guild = |name: &str| WsGuildCreate {
+            name: name.to_string(),
+            desc: None,
+            icon: None,
+        };
+        let shared = db::ws_session::create_guild(user, guild("shared"), &pool)
+            .await
+            .unwrap();
+        let alone = db::ws_session::create_guild(user, guild("alone"), &pool)
+            .await
+            .unwrap();
+        db::ws_session::join_guild(member, shared.id, &pool)
+            .await
+            .unwrap();
+
+        let deleted = db::account::delete_account(user, &pool).await.unwrap();
+
+        assert_eq!(deleted.guilds, vec![alone.id]);
+        assert_eq!(deleted.left, vec![(shared.id, user)]);
+        let shared = db::ws_session::get_guild_by_id(shared.id, &pool)
+            .await
+            .unwrap();
+        assert_eq!(shared.creator_id, member);
+    }
+}
@@ -0,0 +1,241 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicUsize, Arc};
+
+    use actix_identity::IdentityMiddleware;
+    use actix_session::{storage::CookieSessionStore, SessionMiddleware};
+    use actix_web::{
+        cookie::Key,
+        dev::{Service, ServiceResponse},
+        http::{header, StatusCode},
+        test::{call_service, init_service, read_body_json, TestRequest},
+        web, App,
+    };
+    use serde_json::{json, Value};
+    use sqlx::PgPool;
+
+    use crate::controllers::admin;
+    use crate::db::{self, signup::create_password};
+    use crate::server::{
+        bus::{EventBus, InMemoryBus},
+        Chat,
+    };
+    use crate::test::test_agent;
+    use crate::token;
+
+    async fn staff_user(name: &str, is_staff: bool, is_superuser: bool, pool: &PgPool) -> i64 {
+        db::admin::create_user(
+            name.to_string(),
+            format!("{}@test.com", name),
+            create_password("hunter22".to_string()).unwrap(),
+            is_staff,
+            is_superuser,
+            pool,
+        )
+        .await
+        .unwrap()
+    }
+
+    // an API token for user, as /auth/token would give out
+    async fn bearer(user_id: i64, pool: &PgPool) -> String {
+        let session = db::login::create_session(user_id, pool, test_agent())
+            .await
+            .unwrap();
+        let t = token::generate();
+        db::auth::create_token(session, token::hash(&t), pool)
+            .await
+            .unwrap();
+        format!("Bearer {}", t)
+    }
+
+    async fn app(
+        pool: &PgPool,
+    ) -> impl Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error>
+    {
+        let bus: Arc<dyn EventBus> = Arc::new(InMemoryBus::new());
+        init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(Chat::new(
+                    Arc::new(AtomicUsize::new(0)),
+                    8,
+                    bus,
+                )))
+                .wrap(IdentityMiddleware::default())
+                .wrap(SessionMiddleware::new(
+                    CookieSessionStore::default(),
+                    Key::generate(),
+                ))
+                .route("/admin/api/users", web::get().to(admin::users))
+                .route("/admin/api/users/{id}", web::get().to(admin::user))
+                .route("/admin/api/users/{id}", web::patch().to(admin::update_user))
+                .route(
+                    "/admin/api/users/{id}/suspension",
+                    web::post().to(admin::suspend),
+                )
+                .route(
+                    "/admin/api/users/{id}/suspension",
+                    web::delete().to(admin::unsuspend),
+                ),
+        )
+        .await
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_non_staff_are_refused(pool: PgPool) {
+        let app = app(&pool).await;
+        let user = staff_user("user", false, false, &pool).await;
+        let staff = staff_user("staff", true, false, &pool).await;
+        let uri = format!("/admin/api/users/{}", user);
+
+        let res = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let as_user = bearer(user, &pool).await;
+        for req in [
+            TestRequest::get().uri(&uri),
+            TestRequest::get().uri("/admin/api/users"),
+            TestRequest::post()
+                .uri(&format!("{}/suspension", uri))
+                .set_json(json!({})),
+            TestRequest::patch()
+                .uri(&format!("/admin/api/users/{}", staff))
+                .set_json(json!({ "is_staff": false })),
+        ] {
+            let res = call_service(
+                &app,
+                req.insert_header((header::AUTHORIZATION, as_user.clone()))
+                    .to_request(),
+            )
+            .await;
+            assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        }
+        assert!(db::suspension::active(user, &pool).await.unwrap().is_none());
+
+        let res = call_service(
+            &app,
+            TestRequest::get()
+                .uri(&uri)
+                .insert_header((header::AUTHORIZATION, bearer(staff, &pool).await))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_only_a_superuser_acts_on_superusers(pool: PgPool) {
+        let app = app(&pool).await;
+        let user = staff_user("user", false, false, &pool).await;
+        let staff = staff_user("staff", true, false, &pool).await;
+        let root = staff_user("root", false, true, &pool).await;
+        let other_root = staff_user("other", false, true, &pool).await;
+        let (as_staff, as_root) = (bearer(staff, &pool).await, bearer(root, &pool).await);
+        let suspend = |id: i64, who: &str| {
+            TestRequest::post()
+                .uri(&format!("/admin/api/users/{}/suspension", id))
+                .insert_header((header::AUTHORIZATION, who.to_string()))
+                .set_json(json!({ "hours": 1 }))
+                .to_request()
+        };
+        let lift = |id: i64, who: &str| {
+            TestRequest::delete()
+                .uri(&format!("/admin/api/users/{}/suspension", id))
+                .insert_header((header::AUTHORIZATION, who.to_string()))
+                .to_request()
+        };
+
+        assert_eq!(
+            call_service(&app, suspend(other_root, &as_staff))
+                .await
+                .status(),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            call_service(&app, lift(other_root, &as_staff))
+                .await
+                .status(),
+            StatusCode::FORBIDDEN
+        );
+        let res = call_service(
+            &app,
+            TestRequest::patch()
+                .uri(&format!("/admin/api/users/{}", user))
+                .insert_header((header::AUTHORIZATION, as_staff.clone()))
+                .set_json(json!({ "is_staff": true }))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        // nor on themselves
+        assert_eq!(
+            call_service(&app, suspend(staff, &as_staff)).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+
+        // staff still handle everyone else
+        assert_eq!(
+            call_service(&app, suspend(user, &as_staff)).await.status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            call_service(&app, lift(user, &as_staff)).await.status(),
+            StatusCode::OK
+        );
+        // and a superuser handles superusers
+        assert_eq!(
+            call_service(&app, suspend(other_root, &as_root))
+                .await
+                .status(),
+            StatusCode::OK
+        );
+        assert!(db::suspension::active(other_root, &pool)
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_admin_user_leaves_out_the_password(pool: PgPool) {
+        let app = app(&pool).await;
+        let user = staff_user("user", false, false, &pool).await;
+        let staff = staff_user("staff", true, false, &pool).await;
+        let as_staff = bearer(staff, &pool).await;
+        let hash: String = sqlx::query_scalar("SELECT password FROM users WHERE id = $1")
+            .bind(user)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        let res = call_service(
+            &app,
+            TestRequest::get()
+                .uri(&format!("/admin/api/users/{}", user))
+                .insert_header((header::AUTHORIZATION, as_staff.clone()))
+                .to_request(),
+        )
+        .await;
+        let one: Value = read_body_json(res).await;
+        let res = call_service(
+            &app,
+            TestRequest::get()
+                .uri("/admin/api/users")
+                .insert_header((header::AUTHORIZATION, as_staff))
+                .to_request(),
+        )
+        .await;
+        let listed: Value = read_body_json(res).await;
+
+        assert_eq!(one["id"], user);
+        assert!(listed["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|u| u["id"] == user));
+        for user in std::iter::once(&one).chain(listed["items"].as_array().unwrap()) {
+            let user = user.as_object().unwrap();
+            assert!(!user.contains_key("password"));
+            assert!(!user.contains_key("code"));
+            assert!(!user.values().any(|v| v.as_str() == Some(hash.as_str())));
+        }
+    }
+}
@@ -0,0 +1,72 @@
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, web};
+    use sqlx::PgPool;
+
+    use crate::controllers::alerts::{confirm_not_me, fingerprint};
+    use crate::db::{self, signup::UserAgent};
+    use crate::test::{db_user, test_agent};
+    use crate::token;
+
+    fn uag(os: &str, browser: &str, device: &str) -> UserAgent {
+        UserAgent {
+            os: Some(os.to_string()),
+            browser: Some(browser.to_string()),
+            device: Some(device.to_string()),
+            original: "".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_versions() {
+        assert_eq!(
+            fingerprint(&uag("Windows 10 0", "Chrome 118 0", "Other  ")),
+            fingerprint(&uag("Windows 10 0", "Chrome 119 0", "Other  "))
+        );
+        assert_ne!(
+            fingerprint(&uag("Windows 10 0", "Chrome 118 0", "Other  ")),
+            fingerprint(&uag("Windows 10 0", "Firefox 118 0", "Other  "))
+        );
+        assert_eq!(
+            fingerprint(&uag("Mac OS X 10 15", "Mobile Safari 17 0", "iPhone  Apple")),
+            "Mac OS X|Mobile Safari|iPhone Apple"
+        );
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_opening_the_link_changes_nothing(pool: PgPool) {
+        let user = db_user("owner", &pool).await;
+        db::password::set_password(user, "hash".to_string(), &pool)
+            .await
+            .unwrap();
+        let session_id = db::login::create_session(user, &pool, test_agent())
+            .await
+            .unwrap();
+        let alert = token::generate();
+        db::alerts::create_alert(user, session_id, token::hash(&alert), 60, &pool)
+            .await
+            .unwrap();
+        let open =
+            |t: &str| confirm_not_me(web::Path::from(t.to_string()), web::Data::new(pool.clone()));
+
+        assert_eq!(open("nope").await.status(), StatusCode::BAD_REQUEST);
+        // a mail scanner following it twice
+        for _ in 0..2 {
+            assert_eq!(open(&alert).await.status(), StatusCode::OK);
+        }
+        assert_eq!(db::login::get_password(user, &pool).await.unwrap(), "hash");
+        assert!(db::auth::session_exists(user, session_id, &pool)
+            .await
+            .unwrap());
+
+        // it's still there for the POST
+        assert_eq!(
+            db::alerts::use_alert(token::hash(&alert), &pool)
+                .await
+                .unwrap()
+                .map(|(id, _)| id),
+            Some(user)
+        );
+        assert_eq!(open(&alert).await.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,71 @@
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use crate::db;
+    use crate::messages::WsGuildCreate;
+    use crate::test::{db_user, dm};
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_delete_application_with_dms(pool: PgPool) {
+        let owner = db_user("owner", &pool).await;
+        let friend = db_user("friend", &pool).await;
+        let app = db::applications::create_application(owner, "bot".to_string(), None, &pool)
+            .await
+            .unwrap();
+        let guild = db::ws_session::create_guild(
+            owner,
+            WsGuildCreate {
+                name: "g".to_string(),
+                desc: None,
+                icon: None,
+            },
+            &pool,
+        )
+        .await
+        .unwrap();
+        db::ws_session::join_guild(app.bot_id, guild.id, &pool)
+            .await
+            .unwrap();
+        let channel = dm(app.bot_id, friend, &pool).await;
+        db::ws_session::create_message("hi bot".to_string(), friend, channel, &pool)
+            .await
+            .unwrap();
+        db::ws_session::create_message("beep".to_string(), app.bot_id, channel, &pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "INSERT INTO user_relations (user1, user2, relationship) VALUES ($1, $2, 'friend')",
+        )
+        .bind(friend)
+        .bind(app.bot_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        // someone else's application is left alone
+        assert_eq!(
+            db::applications::delete_application(app.id, friend, &pool)
+                .await
+                .unwrap(),
+            None
+        );
+        let left = db::applications::delete_application(app.id, owner, &pool)
+            .await
+            .unwrap();
+        assert_eq!(left, Some(vec![guild.id]));
+
+        // the friend keeps the DM, the bot's side of it is the ghost's now
+        let ghost = db::account::ghost(&mut pool.acquire().await.unwrap())
+            .await
+            .unwrap();
+        let messages = db::ws_session::fetch_message(channel, &pool).await.unwrap();
+        assert_eq!(messages.len(), 2);
+        assert!(messages
+            .iter()
+            .all(|m| m.author_id == friend || m.author_id == ghost));
+        assert!(db::ws_session::get_user_by_id(app.bot_id, &pool)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,91 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use actix_web::{
+        http::{header, StatusCode},
+        test::TestRequest,
+        web, HttpResponse,
+    };
+    use sqlx::PgPool;
+    use user_agent_parser::UserAgentParser;
+
+    use crate::controllers::auth::{bearer, token};
+    use crate::server::TokenEvent;
+    use crate::test::{mfa_user, totp_code};
+    use crate::throttle::ACCOUNT_FREE;
+
+    async fn ask(code: Option<String>, pool: &PgPool) -> HttpResponse {
+        token(
+            web::Json(TokenEvent {
+                email: "bot@test.com".to_string(),
+                password: "hunter22".to_string(),
+                code,
+            }),
+            TestRequest::default().to_http_request(),
+            web::Data::new(pool.clone()),
+            web::Data::new(Arc::new(
+                UserAgentParser::from_path("./regexes.yaml").unwrap(),
+            )),
+        )
+        .await
+    }
+
+    #[test]
+    fn test_bearer_header() {
+        let req = TestRequest::default()
+            .insert_header((header::AUTHORIZATION, "Bearer abc123"))
+            .to_http_request();
+        assert_eq!(bearer(&req), Some("abc123".to_string()));
+
+        let req = TestRequest::default()
+            .insert_header((header::AUTHORIZATION, "Basic abc123"))
+            .to_http_request();
+        assert_eq!(bearer(&req), None);
+        assert_eq!(bearer(&TestRequest::default().to_http_request()), None);
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_token_is_throttled_after_bad_codes(pool: PgPool) {
+        let (_, secret) = mfa_user("bot", "hunter22", &pool).await;
+        // the password alone isn't enough, and asking like that isn't a failure
+        for _ in 0..ACCOUNT_FREE + 1 {
+            assert_eq!(ask(None, &pool).await.status(), StatusCode::BAD_REQUEST);
+        }
+        for _ in 0..ACCOUNT_FREE {
+            assert_eq!(
+                ask(Some("000000".to_string()), &pool).await.status(),
+                StatusCode::BAD_REQUEST
+            );
+        }
+        // the one over locks it
+        assert_eq!(
+            ask(Some("000000".to_string()), &pool).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+        let res = ask(Some(totp_code(&secret)), &pool).await;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(res.headers().contains_key(header::RETRY_AFTER));
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_right_code_clears_the_failures(pool: PgPool) {
+        let (_, secret) = mfa_user("bot", "hunter22", &pool).await;
+        for _ in 0..ACCOUNT_FREE {
+            ask(Some("000000".to_string()), &pool).await;
+        }
+        assert_eq!(
+            ask(Some(totp_code(&secret)), &pool).await.status(),
+            StatusCode::OK
+        );
+        // a fresh count, this one is free again
+        assert_eq!(
+            ask(Some("000000".to_string()), &pool).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            ask(Some("000000".to_string()), &pool).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
@@ -0,0 +1,32 @@
+#[cfg(test)]
+mod tests {
+    use crate::messages::{Message, MessageTypes};
+    use crate::server::broadcast::{deliver, Delivery, Frame};
+    use crate::PLACEHOLDER_UUID;
+    use tokio::sync::mpsc;
+
+    fn event() -> MessageTypes {
+        MessageTypes::MessageCreate(Message::system("hi".to_string(), PLACEHOLDER_UUID, 0))
+    }
+
+    #[test]
+    fn test_frame_encodes_once() {
+        let frame = Frame::new(event());
+        let first = frame.json();
+        let second = frame.json();
+        assert_eq!(first, serde_json::to_string(&frame.event).unwrap());
+        // both calls share the same buffer
+        assert_eq!(first.as_ptr(), second.as_ptr());
+        assert_eq!(frame.cbor().as_ptr(), frame.cbor().as_ptr());
+    }
+
+    #[test]
+    fn test_deliver_reports_lagging() {
+        let (tx, rx) = mpsc::channel(1);
+        let frame = Frame::new(event());
+        assert!(matches!(deliver(&tx, &frame), Delivery::Sent));
+        assert!(matches!(deliver(&tx, &frame), Delivery::Lagging));
+        drop(rx);
+        assert!(matches!(deliver(&tx, &frame), Delivery::Closed));
+    }
+}
@@ -0,0 +1,76 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{atomic::AtomicUsize, Arc},
+        time::Duration,
+    };
+
+    use actix_web::rt::time::timeout;
+
+    use sqlx::types::Uuid;
+
+    use crate::messages::{Message, MessageTypes};
+    use crate::server::{
+        bus::{EventBus, InMemoryBus},
+        Chat,
+    };
+    use crate::test::handle;
+    use crate::PLACEHOLDER_UUID;
+
+    #[actix_web::test]
+    async fn test_guild_message_reaches_other_node() {
+        let bus: Arc<dyn EventBus> = Arc::new(InMemoryBus::new());
+        let a = Chat::new(Arc::new(AtomicUsize::new(0)), 8, bus.clone());
+        let b = Chat::new(Arc::new(AtomicUsize::new(0)), 8, bus);
+        a.listen();
+        b.listen();
+
+        // user 2 is only connected to node b
+        let (h, mut rx) = handle(2, 0, 8);
+        b.insert_session(2, h).await;
+        b.insert_id("g".to_string(), 2, 0).await;
+
+        a.send_guild_message(
+            "g",
+            MessageTypes::MessageCreate(Message::system("hi".to_string(), PLACEHOLDER_UUID, 0)),
+        )
+        .await;
+
+        let frame = timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .expect("event never arrived")
+            .unwrap();
+        assert!(matches!(frame.event, MessageTypes::MessageCreate(_)));
+        // and it only arrives once
+        assert!(timeout(Duration::from_millis(50), rx.recv()).await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_guild_delete_reaches_other_node() {
+        let bus: Arc<dyn EventBus> = Arc::new(InMemoryBus::new());
+        let a = Chat::new(Arc::new(AtomicUsize::new(0)), 8, bus.clone());
+        let b = Chat::new(Arc::new(AtomicUsize::new(0)), 8, bus);
+        a.listen();
+        b.listen();
+
+        let guild = Uuid::new_v4();
+        let (h, mut rx) = handle(2, 0, 8);
+        b.insert_session(2, h).await;
+        b.insert_id(guild.to_string(), 2, 0).await;
+
+        a.delete_guild(guild).await;
+
+        let frame = timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .expect("event never arrived")
+            .unwrap();
+        assert!(matches!(frame.event, MessageTypes::GuildDelete(ref g) if g.id == guild));
+        timeout(Duration::from_secs(1), async {
+            while !b.registry.members_of(&guild.to_string()).is_empty() {
+                actix_web::rt::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("guild still has members");
+    }
+}
@@ -0,0 +1,42 @@
+#[cfg(test)]
+mod tests {
+    use crate::captcha::{
+        image::ImageCaptcha,
+        none::NoCaptcha,
+        pow::{leading_zero_bits, PowCaptcha},
+        CaptchaProvider,
+    };
+
+    #[test]
+    fn test_pow_answer_checks_out() {
+        let pow = PowCaptcha::new(8);
+        let challenge = pow.challenge();
+        assert!(challenge.json);
+        let body: serde_json::Value = serde_json::from_str(&challenge.body).unwrap();
+        let nonce = body["nonce"].as_str().unwrap();
+        let answer = (0u64..)
+            .map(|n| n.to_string())
+            .find(|a| pow.check(&challenge.expected, a))
+            .unwrap();
+        assert!(crate::captcha::pow::solves(nonce, &answer, 8));
+        assert!(!pow.check("8:othernonce", "definitely not"));
+        assert!(!pow.check("garbage", &answer));
+    }
+
+    #[test]
+    fn test_leading_zero_bits() {
+        assert_eq!(leading_zero_bits(&[0, 0, 0xff]), 16);
+        assert_eq!(leading_zero_bits(&[0, 0x10]), 11);
+        assert_eq!(leading_zero_bits(&[0x80]), 0);
+    }
+
+    #[test]
+    fn test_image_and_none() {
+        let image = ImageCaptcha;
+        let challenge = image.challenge();
+        assert!(image.check(&challenge.expected, &challenge.expected));
+        assert!(!image.check(&challenge.expected, "nope!"));
+        assert!(image.required());
+        assert!(!NoCaptcha.required());
+    }
+}
@@ -0,0 +1,119 @@
+#[cfg(test)]
+mod tests {
+    use crate::config::{Config, ConfigError, OidcConfig};
+    use crate::oidc::Providers;
+
+    const KEY: &str =
+        "1,2,3,4,5,6,7,8,9,10,11,12,13,14,15,16,17,18,19,20,21,22,23,24,25,26,27,28,29,30,31,32";
+
+    #[test]
+    fn test_file_overrides_defaults() {
+        let config = Config::from_toml(&format!(
+            r#"
+[server]
+port = 9000
+cors_origins = ["https://example.com"]
+
+[session]
+secret_key = "{}"
+
+[mail]
+mailer = "smtp"
+smtp_host = "smtp.example.com"
+from = "Flettex <no-reply@example.com>"
+"#,
+            KEY
+        ))
+        .unwrap();
+        config.validate().unwrap();
+        // dev has to be asked for
+        assert!(!config.server.dev);
+        assert!(config.database.migrate);
+        assert_eq!(config.server.port, 9000);
+        assert_eq!(config.server.workers, 2);
+        assert_eq!(config.bind(), "0.0.0.0");
+        assert!(config.allows_origin(b"https://example.com"));
+        assert!(!config.allows_origin(b"https://example.com.evil.com"));
+        assert!(!config.allows_origin(b"https://example.co"));
+        assert!(!config.allows_origin(b"http://localhost:3000"));
+        // mirrored up to 64 bytes
+        assert_eq!(config.secret_key().unwrap().len(), 64);
+    }
+
+    #[test]
+    fn test_bad_values_are_rejected() {
+        assert!(matches!(
+            Config::from_toml("[server]\nprot = 9000"),
+            Err(ConfigError::Parse(_))
+        ));
+        let mut config =
+            Config::from_toml(&format!("[session]\nsecret_key = \"{}\"", KEY)).unwrap();
+        // not in dev unless it says so, and the outbox is dev only
+        config.mail.mailer = "file".to_string();
+        assert!(config.validate().is_err());
+        config.server.dev = true;
+        config.validate().unwrap();
+        config.server.workers = 0;
+        assert!(config.validate().is_err());
+        config.server.workers = 1;
+        config.session.secret_key = "1,2,3".to_string();
+        assert!(config.secret_key().is_err());
+        config.gateway.event_bus = "redis".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_service_settings_are_checked() {
+        let mut config = Config::from_toml(&format!(
+            "[server]\ndev = true\n[session]\nsecret_key = \"{}\"\n[mail]\nmailer = \"file\"",
+            KEY
+        ))
+        .unwrap();
+        config.validate().unwrap();
+
+        // nothing is guessed for mail
+        config.mail.mailer = String::new();
+        assert!(config.validate().is_err());
+        config.mail.mailer = "file".to_string();
+        config.server.dev = false;
+        assert!(config.validate().is_err());
+        config.mail.mailer = "smtp".to_string();
+        config.mail.smtp_host = "smtp.example.com".to_string();
+        config.mail.from = "not an address".to_string();
+        assert!(config.validate().is_err());
+        config.mail.from = "no-reply@example.com".to_string();
+        config.validate().unwrap();
+        config.mail.smtp_username = Some("flettex".to_string());
+        assert!(config.validate().is_err());
+        config.mail.smtp_password = Some("hunter2".to_string());
+        config.validate().unwrap();
+
+        config.passkey.origin = "https://example.com".to_string();
+        assert!(config.validate().is_err());
+        config.passkey.rp_id = "example.com".to_string();
+        config.validate().unwrap();
+
+        config.captcha.kind = "recaptcha".to_string();
+        assert!(config.validate().is_err());
+        config.captcha.kind = "pow".to_string();
+        config.captcha.pow_bits = 40;
+        assert!(config.validate().is_err());
+        config.captcha.pow_bits = 16;
+        config.validate().unwrap();
+
+        config.oidc.insert(
+            "corp".to_string(),
+            OidcConfig {
+                issuer: "https://login.example.com".to_string(),
+                ..Default::default()
+            },
+        );
+        assert!(config.validate().is_err());
+        config.oidc.get_mut("corp").unwrap().client_id = "flettex".to_string();
+        config.validate().unwrap();
+        assert_eq!(
+            Providers::from_config(&config).names(),
+            vec!["corp".to_string()]
+        );
+    }
+}
@@ -0,0 +1,38 @@
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::mailer::{file::FileMailer, templates, Mailer};
+
+    #[test]
+    fn test_templates_fill_and_escape() {
+        let mail = templates::verification("a@test.com", 123456, 15, None);
+        assert!(mail.text.contains("123456"));
+        assert!(!mail.text.contains("{{"));
+        assert!(!mail.html.contains("{{"));
+        assert!(!mail.html.contains("href"));
+
+        let mail = templates::verification("a@test.com", 123456, 15, Some("http://x/verify/link/t".to_string()));
+        assert!(mail.text.contains("http://x/verify/link/t"));
+        assert!(mail.html.contains("href=\"http://x/verify/link/t\""));
+
+        let mail = templates::notification("a@test.com", "hi", "<script>");
+        assert!(mail.text.contains("<script>"));
+        assert!(mail.html.contains("&lt;script&gt;"));
+    }
+
+    #[actix_web::test]
+    async fn test_file_mailer_writes_outbox() {
+        let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        FileMailer::new(&dir)
+            .send(&templates::password_reset("a@test.com", "abc", 30))
+            .await
+            .unwrap();
+        let files: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(files.len(), 1);
+        let content = std::fs::read_to_string(files[0].as_ref().unwrap().path()).unwrap();
+        assert!(content.contains("To: a@test.com"));
+        assert!(content.contains("abc"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
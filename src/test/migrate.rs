@@ -0,0 +1,20 @@
+#[cfg(test)]
+mod tests {
+    use crate::migrate::{up_migrations, MIGRATOR};
+
+    #[test]
+    fn test_every_migration_can_be_reverted() {
+        let mut last = 0;
+        for m in up_migrations() {
+            assert!(m.version > last);
+            last = m.version;
+            assert!(
+                MIGRATOR
+                    .iter()
+                    .any(|d| d.version == m.version && d.migration_type.is_down_migration()),
+                "migration {} has no down script",
+                m.version
+            );
+        }
+    }
+}
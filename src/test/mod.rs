@@ -1 +1,150 @@
+#![cfg(test)]
+
+mod account;
+mod admin;
+mod alerts;
+mod applications;
+mod auth;
+mod broadcast;
+mod bus;
+mod captcha;
+mod config;
 mod index;
+mod mailer;
+mod migrate;
+mod moderation;
+mod oidc;
+mod passkey;
+mod password;
+mod presence;
+mod profile;
+mod registry;
+mod sessions;
+mod suspension;
+mod throttle;
+mod totp;
+mod verify;
+
+use std::sync::{atomic::AtomicBool, Arc, RwLock};
+
+use chrono::Utc;
+use sqlx::{types::Uuid, PgPool};
+use tokio::sync::mpsc;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::db::{
+    self,
+    models::User,
+    signup::{create_password, UserAgent},
+};
+use crate::server::broadcast::{Frame, SessionHandle};
+use crate::PLACEHOLDER_UUID;
+
+pub fn user(id: i64) -> User {
+    User {
+        id,
+        username: format!("user{}", id),
+        email: format!("user{}@test.com", id),
+        password: "".to_string(),
+        profile: None,
+        created_at: Utc::now().naive_utc(),
+        description: None,
+        allow_login: true,
+        is_online: true,
+        is_staff: false,
+        is_superuser: false,
+        code: None,
+        status: "online".to_string(),
+        custom_status: None,
+        code_expires_at: None,
+        code_sent_at: None,
+        code_attempts: 0,
+        verify_token: None,
+        is_bot: false,
+        username_changed_at: None,
+        suspended_until: None,
+        suspension_reason: None,
+    }
+}
+
+pub fn handle(user_id: i64, conn_id: usize, size: usize) -> (SessionHandle, mpsc::Receiver<Arc<Frame>>) {
+    let (outbound, rx) = mpsc::channel(size);
+    let (control, _) = mpsc::unbounded_channel();
+    (
+        SessionHandle {
+            conn_id,
+            session_id: PLACEHOLDER_UUID.to_string(),
+            user: Arc::new(user(user_id)),
+            profile: Arc::new(RwLock::new(user(user_id).into())),
+            outbound,
+            control,
+            idle: Arc::new(AtomicBool::new(false)),
+        },
+        rx,
+    )
+}
+
+// For the #[sqlx::test] ones, they get a fresh database with the migrations applied
+
+pub async fn db_user(name: &str, pool: &PgPool) -> i64 {
+    db::admin::create_user(
+        name.to_string(),
+        format!("{}@test.com", name),
+        "".to_string(),
+        false,
+        false,
+        pool,
+    )
+    .await
+    .unwrap()
+}
+
+// can log in with name@test.com and password, then needs totp_code(secret)
+pub async fn mfa_user(name: &str, password: &str, pool: &PgPool) -> (i64, String) {
+    let user = db_user(name, pool).await;
+    db::password::set_password(user, create_password(password.to_string()).unwrap(), pool)
+        .await
+        .unwrap();
+    let secret = crate::totp::new_secret();
+    db::mfa::set_pending_secret(user, secret.clone(), pool)
+        .await
+        .unwrap();
+    db::mfa::enable(user, vec![], pool).await.unwrap();
+    (user, secret)
+}
+
+pub fn totp_code(secret: &str) -> String {
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        Secret::Encoded(secret.to_string()).to_bytes().unwrap(),
+        None,
+        "".to_string(),
+    )
+    .unwrap()
+    .generate_current()
+    .unwrap()
+}
+
+// what create_dm_channel would make, without its quirks
+pub async fn dm(user1: i64, user2: i64, pool: &PgPool) -> Uuid {
+    sqlx::query_scalar(
+        "INSERT INTO channel (name, position, channel_type, user1, user2) VALUES ('', 0, 1, $1, $2) RETURNING id",
+    )
+    .bind(user1)
+    .bind(user2)
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+pub fn test_agent() -> UserAgent {
+    UserAgent {
+        os: None,
+        device: None,
+        browser: None,
+        original: "test".to_string(),
+    }
+}
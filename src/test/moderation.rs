@@ -0,0 +1,305 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{atomic::AtomicUsize, Arc},
+        time::Duration,
+    };
+
+    use actix_web::rt::time::timeout;
+    use sqlx::{types::Uuid, PgPool};
+    use tokio::sync::mpsc::Receiver;
+
+    use crate::db;
+    use crate::messages::{
+        MessageTypes, WsChannelCreate, WsGuildCreate, WsMemberBan, WsReceiveTypes,
+    };
+    use crate::server::{
+        broadcast::Frame,
+        bus::{EventBus, InMemoryBus},
+        moderation::describe_ban,
+        Chat,
+    };
+    use crate::test::{db_user, handle};
+
+    // One node, so no listen(): emit applies locally and that's all these need. It would
+    // also want an actix runtime, #[sqlx::test] only gives a plain tokio one.
+    fn chat() -> Chat {
+        let bus: Arc<dyn EventBus> = Arc::new(InMemoryBus::new());
+        Chat::new(Arc::new(AtomicUsize::new(0)), 8, bus)
+    }
+
+    // a guild with one text channel, made by owner
+    async fn guild(owner: i64, name: &str, pool: &PgPool) -> (Uuid, Uuid) {
+        let guild = db::ws_session::create_guild(
+            owner,
+            WsGuildCreate {
+                name: name.to_string(),
+                desc: None,
+                icon: None,
+            },
+            pool,
+        )
+        .await
+        .unwrap();
+        let channel = db::ws_session::create_channel(
+            WsChannelCreate {
+                name: "general".to_string(),
+                desc: None,
+                position: 0,
+                guild_id: guild.id,
+                channel_type: 0,
+            },
+            pool,
+        )
+        .await
+        .unwrap();
+        (guild.id, channel.id)
+    }
+
+    fn ban(guild_id: Uuid, user_id: i64) -> WsMemberBan {
+        WsMemberBan {
+            guild_id,
+            user_id,
+            reason: None,
+            hours: None,
+            purge_hours: None,
+        }
+    }
+
+    async fn is_member(user_id: i64, guild_id: Uuid, pool: &PgPool) -> bool {
+        sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM member WHERE user_id = $1 AND guild_id = $2)",
+        )
+        .bind(user_id)
+        .bind(guild_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    // the owner watching their guild on the gateway
+    async fn watch(chat: &Chat, owner: i64, guild_id: Uuid) -> Receiver<Arc<Frame>> {
+        let (h, rx) = handle(owner, 0, 16);
+        chat.insert_session(owner as usize, h).await;
+        chat.insert_id(guild_id.to_string(), owner as usize, 0)
+            .await;
+        rx
+    }
+
+    async fn next(rx: &mut Receiver<Arc<Frame>>) -> Arc<Frame> {
+        timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .expect("event never arrived")
+            .unwrap()
+    }
+
+    #[test]
+    fn test_ban_frame_parses() {
+        let frame = r#"{"type": "MemberBan", "data":{"guild_id": "5fe9d2ab-2174-4a30-8245-cc5de2563dce", "user_id": 2, "reason": "spam", "purge_hours": 1}}"#;
+        let WsReceiveTypes::MemberBan(ban) = serde_json::from_str(frame).unwrap() else {
+            panic!("not a ban");
+        };
+        assert_eq!(ban.user_id, 2);
+        assert_eq!(ban.reason.as_deref(), Some("spam"));
+        // no hours is a permanent ban
+        assert_eq!(ban.hours, None);
+        assert_eq!(ban.purge_hours, Some(1));
+
+        let frame = r#"{"type": "MemberKick", "data":{"guild_id": "5fe9d2ab-2174-4a30-8245-cc5de2563dce"}}"#;
+        assert!(serde_json::from_str::<WsReceiveTypes>(frame).is_err());
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_only_the_owner_kicks_and_bans(pool: PgPool) {
+        let owner = db_user("owner", &pool).await;
+        let member = db_user("member", &pool).await;
+        let other = db_user("other", &pool).await;
+        let (guild_id, _) = guild(owner, "g", &pool).await;
+        for user in [member, other] {
+            db::ws_session::join_guild(user, guild_id, &pool)
+                .await
+                .unwrap();
+        }
+        let chat = chat();
+        let mut rx = watch(&chat, owner, guild_id).await;
+
+        assert_eq!(
+            chat.kick(member, guild_id, other, &pool).await,
+            Err("Only the guild's owner can do that")
+        );
+        assert!(chat
+            .ban(member, &ban(guild_id, other), &pool)
+            .await
+            .is_err());
+        assert!(chat.unban(member, guild_id, other, &pool).await.is_err());
+        assert_eq!(
+            chat.kick(owner, guild_id, owner, &pool).await,
+            Err("You can't do that to yourself")
+        );
+        assert!(is_member(other, guild_id, &pool).await);
+        assert!(db::guild_ban::active(other, guild_id, &pool)
+            .await
+            .unwrap()
+            .is_none());
+
+        chat.kick(owner, guild_id, other, &pool).await.unwrap();
+        assert!(!is_member(other, guild_id, &pool).await);
+        let frame = next(&mut rx).await;
+        assert!(matches!(frame.event, MessageTypes::MemberRemove(_)));
+        assert_eq!(
+            chat.kick(owner, guild_id, other, &pool).await,
+            Err("They aren't in this guild")
+        );
+        // a kick isn't a ban
+        db::ws_session::join_guild(other, guild_id, &pool)
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_ban_blocks_rejoining_until_it_runs_out(pool: PgPool) {
+        let owner = db_user("owner", &pool).await;
+        let member = db_user("member", &pool).await;
+        let (guild_id, _) = guild(owner, "g", &pool).await;
+        db::ws_session::join_guild(member, guild_id, &pool)
+            .await
+            .unwrap();
+        let chat = chat();
+
+        let mut timed = ban(guild_id, member);
+        timed.hours = Some(1);
+        timed.reason = Some("spam".to_string());
+        chat.ban(owner, &timed, &pool).await.unwrap();
+        assert!(!is_member(member, guild_id, &pool).await);
+        assert!(matches!(
+            db::ws_session::join_guild(member, guild_id, &pool).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+        let active = db::guild_ban::active(member, guild_id, &pool)
+            .await
+            .unwrap()
+            .unwrap();
+        let msg = describe_ban(&active);
+        assert!(msg.starts_with("You are banned from this guild until "));
+        assert!(msg.ends_with(" UTC: spam"));
+
+        sqlx::query(
+            "UPDATE guild_ban SET expires_at = NOW() - INTERVAL '1 minute' WHERE user_id = $1",
+        )
+        .bind(member)
+        .execute(&pool)
+        .await
+        .unwrap();
+        assert!(db::guild_ban::active(member, guild_id, &pool)
+            .await
+            .unwrap()
+            .is_none());
+        db::ws_session::join_guild(member, guild_id, &pool)
+            .await
+            .unwrap();
+
+        // a permanent one lasts until the owner lifts it
+        chat.ban(owner, &ban(guild_id, member), &pool)
+            .await
+            .unwrap();
+        assert!(db::ws_session::join_guild(member, guild_id, &pool)
+            .await
+            .is_err());
+        chat.unban(owner, guild_id, member, &pool).await.unwrap();
+        assert_eq!(
+            chat.unban(owner, guild_id, member, &pool).await,
+            Err("They aren't banned")
+        );
+        db::ws_session::join_guild(member, guild_id, &pool)
+            .await
+            .unwrap();
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_purge_only_takes_their_recent_messages_in_that_guild(pool: PgPool) {
+        let owner = db_user("owner", &pool).await;
+        let member = db_user("member", &pool).await;
+        let (guild_id, channel) = guild(owner, "g", &pool).await;
+        let (elsewhere, other_channel) = guild(owner, "elsewhere", &pool).await;
+        for g in [guild_id, elsewhere] {
+            db::ws_session::join_guild(member, g, &pool).await.unwrap();
+        }
+        let say = |author: i64, channel: Uuid| {
+            let pool = pool.clone();
+            async move {
+                db::ws_session::create_message("hi".to_string(), author, channel, &pool)
+                    .await
+                    .unwrap()
+                    .id
+            }
+        };
+        let recent = say(member, channel).await;
+        let old = say(member, channel).await;
+        sqlx::query("UPDATE message SET created_at = NOW() - INTERVAL '3 hours' WHERE id = $1")
+            .bind(old)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let owners = say(owner, channel).await;
+        let other_guild = say(member, other_channel).await;
+        let chat = chat();
+        let mut rx = watch(&chat, owner, guild_id).await;
+
+        let mut purge = ban(guild_id, member);
+        purge.purge_hours = Some(1);
+        chat.ban(owner, &purge, &pool).await.unwrap();
+
+        let left: Vec<Uuid> = db::ws_session::fetch_message(channel, &pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| m.id)
+            .collect();
+        assert!(!left.contains(&recent));
+        assert!(left.contains(&old));
+        assert!(left.contains(&owners));
+        assert_eq!(
+            db::ws_session::fetch_message(other_channel, &pool)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|m| m.id)
+                .collect::<Vec<_>>(),
+            vec![other_guild]
+        );
+
+        assert!(matches!(
+            next(&mut rx).await.event,
+            MessageTypes::MemberRemove(_)
+        ));
+        assert!(matches!(
+            next(&mut rx).await.event,
+            MessageTypes::MessageDelete(ref d) if d.id == recent && d.channel_id == channel
+        ));
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_bad_ban_is_refused(pool: PgPool) {
+        let owner = db_user("owner", &pool).await;
+        let member = db_user("member", &pool).await;
+        let (guild_id, _) = guild(owner, "g", &pool).await;
+        let chat = chat();
+
+        let mut bad = ban(guild_id, member);
+        bad.reason = Some("a".repeat(256));
+        assert!(chat.ban(owner, &bad, &pool).await.is_err());
+        let mut bad = ban(guild_id, member);
+        bad.hours = Some(0);
+        assert!(chat.ban(owner, &bad, &pool).await.is_err());
+        let mut bad = ban(guild_id, member);
+        bad.purge_hours = Some(24 * 7 + 1);
+        assert_eq!(
+            chat.ban(owner, &bad, &pool).await,
+            Err("purge_hours must be between 1 and 168")
+        );
+        assert!(db::guild_ban::active(member, guild_id, &pool)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
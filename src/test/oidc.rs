@@ -0,0 +1,339 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        net::TcpListener,
+        sync::{Arc, Mutex},
+    };
+
+    use actix_identity::IdentityMiddleware;
+    use actix_session::{storage::CookieSessionStore, SessionMiddleware};
+    use actix_web::{
+        cookie::Key,
+        dev::{Service, ServiceResponse},
+        http::{header, StatusCode},
+        test::{call_service, init_service, TestRequest},
+        web, App, HttpResponse, HttpServer,
+    };
+    use chrono::{Duration, Utc};
+    use openidconnect::core::{
+        CoreIdToken, CoreIdTokenClaims, CoreJsonWebKeySet, CoreJwsSigningAlgorithm,
+        CoreProviderMetadata, CoreResponseType, CoreRsaPrivateSigningKey,
+        CoreSubjectIdentifierType,
+    };
+    use openidconnect::{
+        url::Url, Audience, AuthUrl, EmptyAdditionalClaims, EmptyAdditionalProviderMetadata,
+        EndUserEmail, IssuerUrl, JsonWebKeyId, JsonWebKeySetUrl, Nonce, PrivateSigningKey,
+        ResponseTypes, StandardClaims, SubjectIdentifier, TokenUrl,
+    };
+    use sqlx::PgPool;
+    use tokio::task::LocalSet;
+    use user_agent_parser::UserAgentParser;
+
+    use crate::config::Config;
+    use crate::controllers::oidc;
+    use crate::db;
+    use crate::mailer::{file::FileMailer, MailQueue};
+    use crate::oidc::{ProviderConfig, Providers};
+    use crate::test::{db_user, mfa_user};
+    use crate::token;
+
+    fn signing_key() -> CoreRsaPrivateSigningKey {
+        CoreRsaPrivateSigningKey::from_pem(
+            include_str!("oidc_key.pem"),
+            Some(JsonWebKeyId::new("test".to_string())),
+        )
+        .unwrap()
+    }
+
+    // An issuer on its own thread and actix system: discovery, keys, and a token
+    // endpoint that hands back whatever ID token the test put in id_token
+    struct Issuer {
+        url: String,
+        id_token: Arc<Mutex<String>>,
+    }
+
+    fn issuer() -> Issuer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let id_token = Arc::new(Mutex::new(String::new()));
+        let metadata = CoreProviderMetadata::new(
+            IssuerUrl::new(url.clone()).unwrap(),
+            AuthUrl::new(format!("{}/authorize", url)).unwrap(),
+            JsonWebKeySetUrl::new(format!("{}/jwks", url)).unwrap(),
+            vec![ResponseTypes::new(vec![CoreResponseType::Code])],
+            vec![CoreSubjectIdentifierType::Public],
+            vec![CoreJwsSigningAlgorithm::RsaSsaPkcs1V15Sha256],
+            EmptyAdditionalProviderMetadata {},
+        )
+        .set_token_endpoint(Some(TokenUrl::new(format!("{}/token", url)).unwrap()));
+        let metadata = serde_json::to_string(&metadata).unwrap();
+        let jwks = serde_json::to_string(&CoreJsonWebKeySet::new(vec![
+            signing_key().as_verification_key()
+        ]))
+        .unwrap();
+        let served = id_token.clone();
+        std::thread::spawn(move || {
+            actix_web::rt::System::new().block_on(
+                HttpServer::new(move || {
+                    let (metadata, jwks, served) = (metadata.clone(), jwks.clone(), served.clone());
+                    App::new()
+                        .route(
+                            "/.well-known/openid-configuration",
+                            web::get().to(move || {
+                                let metadata = metadata.clone();
+                                async move {
+                                    HttpResponse::Ok()
+                                        .content_type("application/json")
+                                        .body(metadata)
+                                }
+                            }),
+                        )
+                        .route(
+                            "/jwks",
+                            web::get().to(move || {
+                                let jwks = jwks.clone();
+                                async move {
+                                    HttpResponse::Ok()
+                                        .content_type("application/json")
+                                        .body(jwks)
+                                }
+                            }),
+                        )
+                        .route(
+                            "/token",
+                            web::post().to(move || {
+                                let id_token = served.lock().unwrap().clone();
+                                async move {
+                                    HttpResponse::Ok().json(serde_json::json!({
+                                        "access_token": "access",
+                                        "token_type": "bearer",
+                                        "id_token": id_token,
+                                    }))
+                                }
+                            }),
+                        )
+                })
+                .workers(1)
+                .listen(listener)
+                .unwrap()
+                .run(),
+            )
+        });
+        Issuer { url, id_token }
+    }
+
+    impl Issuer {
+        // what the provider says about whoever just logged in there
+        fn will_say(&self, nonce: &str, subject: &str, email: &str, verified: bool) {
+            let claims = CoreIdTokenClaims::new(
+                IssuerUrl::new(self.url.clone()).unwrap(),
+                vec![Audience::new("client".to_string())],
+                Utc::now() + Duration::minutes(5),
+                Utc::now(),
+                StandardClaims::new(SubjectIdentifier::new(subject.to_string()))
+                    .set_email(Some(EndUserEmail::new(email.to_string())))
+                    .set_email_verified(Some(verified)),
+                EmptyAdditionalClaims {},
+            )
+            .set_nonce(Some(Nonce::new(nonce.to_string())));
+            let id_token = CoreIdToken::new(
+                claims,
+                &signing_key(),
+                CoreJwsSigningAlgorithm::RsaSsaPkcs1V15Sha256,
+                None,
+                None,
+            )
+            .unwrap();
+            *self.id_token.lock().unwrap() = serde_json::to_value(&id_token)
+                .unwrap()
+                .as_str()
+                .unwrap()
+                .to_string();
+        }
+    }
+
+    // /oidc/mock/login, the provider agreeing, and the callback with the same cookie
+    async fn sign_in<S>(
+        app: &S,
+        issuer: &Issuer,
+        subject: &str,
+        email: &str,
+        verified: bool,
+    ) -> ServiceResponse
+    where
+        S: Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error>,
+    {
+        let res = call_service(app, TestRequest::get().uri("/oidc/mock/login").to_request()).await;
+        assert_eq!(res.status(), StatusCode::FOUND);
+        let cookies: Vec<_> = res.response().cookies().map(|c| c.into_owned()).collect();
+        let to = Url::parse(
+            res.headers()
+                .get(header::LOCATION)
+                .unwrap()
+                .to_str()
+                .unwrap(),
+        )
+        .unwrap();
+        let query: HashMap<_, _> = to.query_pairs().into_owned().collect();
+        issuer.will_say(&query["nonce"], subject, email, verified);
+
+        let mut req = TestRequest::get().uri(&format!(
+            "/oidc/mock/callback?code=code&state={}",
+            query["state"]
+        ));
+        for cookie in cookies {
+            req = req.cookie(cookie);
+        }
+        call_service(app, req.to_request()).await
+    }
+
+    fn location(res: &ServiceResponse) -> &str {
+        res.headers()
+            .get(header::LOCATION)
+            .unwrap()
+            .to_str()
+            .unwrap()
+    }
+
+    async fn sessions(user_id: i64, pool: &PgPool) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM user_sessions WHERE userid = $1")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn app(
+        pool: &PgPool,
+        issuer: &Issuer,
+    ) -> impl Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error>
+    {
+        let providers = Providers::new(HashMap::from([(
+            "mock".to_string(),
+            ProviderConfig {
+                issuer: issuer.url.clone(),
+                client_id: "client".to_string(),
+                client_secret: Some("secret".to_string()),
+                redirect_url: "http://localhost:8080/oidc/mock/callback".to_string(),
+            },
+        )]));
+        let outbox = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(providers))
+                .app_data(web::Data::new(MailQueue::start(Arc::new(FileMailer::new(
+                    outbox,
+                )))))
+                .app_data(web::Data::new(Arc::new(
+                    UserAgentParser::from_path("./regexes.yaml").unwrap(),
+                )))
+                .app_data(web::Data::new(Config::default()))
+                .wrap(IdentityMiddleware::default())
+                .wrap(SessionMiddleware::new(
+                    CookieSessionStore::default(),
+                    Key::generate(),
+                ))
+                .route("/oidc/{provider}/login", web::get().to(oidc::login))
+                .route("/oidc/{provider}/callback", web::get().to(oidc::callback)),
+        )
+        .await
+    }
+
+    // The mail queue wants spawn_local, #[sqlx::test] only gives a plain tokio runtime.
+    // These go in a LocalSet for that.
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_new_account_from_provider(pool: PgPool) {
+        LocalSet::new()
+            .run_until(async {
+                let issuer = issuer();
+                let app = app(&pool, &issuer).await;
+                let res = sign_in(&app, &issuer, "sub-1", "new@test.com", true).await;
+                assert_eq!(res.status(), StatusCode::FOUND);
+                assert_eq!(location(&res), "http://localhost:3000");
+
+                let user = db::password::get_user_id_by_email("new@test.com".to_string(), &pool)
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    db::oidc::get_linked_user("mock", "sub-1", &pool)
+                        .await
+                        .unwrap(),
+                    Some(user)
+                );
+                assert_eq!(sessions(user, &pool).await, 1);
+                // the provider checked the email, no code needed
+                assert_eq!(db::verify::code(user, &pool).await.unwrap(), None);
+
+                // and the same subject logs in to it again
+                let res = sign_in(&app, &issuer, "sub-1", "new@test.com", true).await;
+                assert_eq!(location(&res), "http://localhost:3000");
+                assert_eq!(sessions(user, &pool).await, 2);
+            })
+            .await
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_links_only_by_verified_email(pool: PgPool) {
+        LocalSet::new()
+            .run_until(async {
+                let issuer = issuer();
+                let app = app(&pool, &issuer).await;
+                let owner = db_user("owner", &pool).await;
+
+                let res = sign_in(&app, &issuer, "sub-1", "owner@test.com", false).await;
+                assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+                assert_eq!(
+                    db::oidc::get_linked_user("mock", "sub-1", &pool)
+                        .await
+                        .unwrap(),
+                    None
+                );
+                assert_eq!(sessions(owner, &pool).await, 0);
+
+                let res = sign_in(&app, &issuer, "sub-1", "owner@test.com", true).await;
+                assert_eq!(location(&res), "http://localhost:3000");
+                assert_eq!(
+                    db::oidc::get_linked_user("mock", "sub-1", &pool)
+                        .await
+                        .unwrap(),
+                    Some(owner)
+                );
+                assert_eq!(sessions(owner, &pool).await, 1);
+            })
+            .await
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_two_factor_accounts_get_a_ticket(pool: PgPool) {
+        LocalSet::new()
+            .run_until(async {
+                let issuer = issuer();
+                let app = app(&pool, &issuer).await;
+                let (owner, _) = mfa_user("owner", "hunter22", &pool).await;
+
+                let res = sign_in(&app, &issuer, "sub-1", "owner@test.com", true).await;
+                assert_eq!(res.status(), StatusCode::FOUND);
+                let ticket = location(&res)
+                    .strip_prefix("http://localhost:3000/login/mfa#ticket=")
+                    .unwrap()
+                    .to_string();
+                // nothing until /login/mfa
+                assert_eq!(sessions(owner, &pool).await, 0);
+                assert_eq!(
+                    db::mfa::ticket_attempt(token::hash(&ticket), 5, &pool)
+                        .await
+                        .unwrap(),
+                    Some(owner)
+                );
+
+                // once linked it still asks
+                let res = sign_in(&app, &issuer, "sub-1", "owner@test.com", true).await;
+                assert!(location(&res).contains("/login/mfa#ticket="));
+                assert_eq!(sessions(owner, &pool).await, 0);
+            })
+            .await
+    }
+}
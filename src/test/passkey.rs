@@ -0,0 +1,43 @@
+#[cfg(test)]
+mod tests {
+    use webauthn_authenticator_rs::{softpasskey::SoftPasskey, WebauthnAuthenticator};
+    use webauthn_rs::prelude::Url;
+
+    use crate::passkey;
+
+    #[test]
+    fn test_passkey_register_then_login() {
+        let origin = Url::parse("http://localhost:3000").unwrap();
+        let webauthn = passkey::new("localhost", origin.as_str()).unwrap();
+        let mut authenticator = WebauthnAuthenticator::new(SoftPasskey::new(true));
+
+        let (challenge, reg_state) = webauthn
+            .start_passkey_registration(passkey::user_handle(1), "user1@test.com", "user1", None)
+            .unwrap();
+        let credential = authenticator
+            .do_registration(origin.clone(), challenge)
+            .unwrap();
+        let key = webauthn
+            .finish_passkey_registration(&credential, &reg_state)
+            .unwrap();
+
+        // what goes in and comes out of user_credential
+        let stored = serde_json::to_string(&key).unwrap();
+        let key: webauthn_rs::prelude::Passkey = serde_json::from_str(&stored).unwrap();
+
+        let (challenge, auth_state) = webauthn
+            .start_passkey_authentication(std::slice::from_ref(&key))
+            .unwrap();
+        let response = authenticator.do_authentication(origin, challenge).unwrap();
+        let result = webauthn
+            .finish_passkey_authentication(&response, &auth_state)
+            .unwrap();
+        assert_eq!(result.cred_id(), key.cred_id());
+    }
+
+    #[test]
+    fn test_user_handle_is_stable() {
+        assert_eq!(passkey::user_handle(7), passkey::user_handle(7));
+        assert_ne!(passkey::user_handle(7), passkey::user_handle(8));
+    }
+}
@@ -0,0 +1,169 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicUsize, Arc};
+
+    use actix_web::{http::StatusCode, test::TestRequest, web, HttpResponse};
+    use sqlx::PgPool;
+    use tokio::task::LocalSet;
+
+    use crate::controllers::login::verify_password;
+    use crate::controllers::password::{self, RESET_MINUTES};
+    use crate::db;
+    use crate::mailer::{file::FileMailer, MailQueue};
+    use crate::server::{
+        bus::{EventBus, InMemoryBus},
+        Chat, ForgotPasswordEvent, ResetPasswordEvent,
+    };
+    use crate::test::{db_user, test_agent};
+    use crate::throttle::RESET_FREE;
+    use crate::token;
+
+    // what the email would have carried
+    async fn reset_token(user_id: i64, pool: &PgPool) -> String {
+        let reset = token::generate();
+        db::password::create_reset(user_id, token::hash(&reset), RESET_MINUTES, pool)
+            .await
+            .unwrap();
+        reset
+    }
+
+    async fn reset(reset: &str, password: &str, pool: &PgPool) -> HttpResponse {
+        let bus: Arc<dyn EventBus> = Arc::new(InMemoryBus::new());
+        password::reset(
+            web::Json(ResetPasswordEvent {
+                token: reset.to_string(),
+                password: password.to_string(),
+            }),
+            web::Data::new(pool.clone()),
+            web::Data::new(Chat::new(Arc::new(AtomicUsize::new(0)), 8, bus)),
+        )
+        .await
+    }
+
+    async fn password_is(user_id: i64, password: &str, pool: &PgPool) -> bool {
+        let hash: String = sqlx::query_scalar("SELECT password FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        verify_password(password, &hash)
+    }
+
+    async fn sessions(user_id: i64, pool: &PgPool) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM user_sessions WHERE userid = $1")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_reset_logs_everyone_out(pool: PgPool) {
+        let user = db_user("user", &pool).await;
+        db::login::create_session(user, &pool, test_agent())
+            .await
+            .unwrap();
+        db::login::create_session(user, &pool, test_agent())
+            .await
+            .unwrap();
+        let t = reset_token(user, &pool).await;
+
+        assert_eq!(
+            reset(&t, "new password", &pool).await.status(),
+            StatusCode::OK
+        );
+        assert!(password_is(user, "new password", &pool).await);
+        assert_eq!(sessions(user, &pool).await, 0);
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_reset_token_works_once(pool: PgPool) {
+        let user = db_user("user", &pool).await;
+        let t = reset_token(user, &pool).await;
+        assert_eq!(reset(&t, "first", &pool).await.status(), StatusCode::OK);
+        assert_eq!(
+            reset(&t, "second", &pool).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+        assert!(password_is(user, "first", &pool).await);
+
+        // asking again throws out the one before
+        let old = reset_token(user, &pool).await;
+        let new = reset_token(user, &pool).await;
+        assert_eq!(
+            reset(&old, "third", &pool).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(reset(&new, "third", &pool).await.status(), StatusCode::OK);
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_expired_reset_token_is_refused(pool: PgPool) {
+        let user = db_user("user", &pool).await;
+        let t = reset_token(user, &pool).await;
+        sqlx::query("UPDATE password_reset SET expires_at = NOW() - INTERVAL '1 minute'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            reset(&t, "new password", &pool).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+        assert!(!password_is(user, "new password", &pool).await);
+    }
+
+    // The mail queue wants spawn_local, so a LocalSet
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_forgot_is_throttled_per_email(pool: PgPool) {
+        LocalSet::new()
+            .run_until(async {
+                let user = db_user("user", &pool).await;
+                let outbox = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+                let mail = web::Data::new(MailQueue::start(Arc::new(FileMailer::new(outbox))));
+                let forgot = |email: &str| {
+                    password::forgot(
+                        TestRequest::default().to_http_request(),
+                        web::Json(ForgotPasswordEvent {
+                            email: email.to_string(),
+                        }),
+                        web::Data::new(pool.clone()),
+                        mail.clone(),
+                    )
+                };
+
+                for _ in 0..RESET_FREE {
+                    assert_eq!(forgot("user@test.com").await.status(), StatusCode::OK);
+                }
+                // the next one still goes out, then the address is locked
+                assert_eq!(forgot("user@test.com").await.status(), StatusCode::OK);
+                let res = forgot("USER@test.com").await;
+                assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+                // unknown addresses get the same treatment
+                for _ in 0..=RESET_FREE {
+                    forgot("nobody@test.com").await;
+                }
+                assert_eq!(
+                    forgot("nobody@test.com").await.status(),
+                    StatusCode::TOO_MANY_REQUESTS
+                );
+
+                // logging in isn't held up by it
+                let login_locked: bool = sqlx::query_scalar(
+                    "SELECT EXISTS (SELECT 1 FROM login_throttle WHERE key LIKE 'email:%')",
+                )
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+                assert!(!login_locked);
+                let resets: i64 =
+                    sqlx::query_scalar("SELECT COUNT(*) FROM password_reset WHERE user_id = $1")
+                        .bind(user)
+                        .fetch_one(&pool)
+                        .await
+                        .unwrap();
+                // each new one replaces the last
+                assert_eq!(resets, 1);
+            })
+            .await
+    }
+}
@@ -0,0 +1,166 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{atomic::AtomicUsize, Arc},
+        time::Duration,
+    };
+
+    use actix_web::rt::time::timeout;
+    use sqlx::{types::Uuid, PgPool};
+    use tokio::sync::mpsc::Receiver;
+
+    use crate::db;
+    use crate::messages::{MessageTypes, Status, WsGuildCreate};
+    use crate::server::{
+        broadcast::Frame,
+        bus::{EventBus, InMemoryBus},
+        Chat,
+    };
+    use crate::test::{db_user, handle, user};
+
+    // two of these are two nodes, sharing the database but not a bus
+    fn node() -> Chat {
+        let bus: Arc<dyn EventBus> = Arc::new(InMemoryBus::new());
+        Chat::new(Arc::new(AtomicUsize::new(0)), 8, bus)
+    }
+
+    async fn is_online(user_id: i64, pool: &PgPool) -> bool {
+        sqlx::query_scalar("SELECT is_online FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    // user comes online on node, as the gateway would do it. Hold on to the receiver,
+    // a closed one gets the session dropped.
+    async fn connect(
+        node: &Chat,
+        user_id: i64,
+        conn_id: usize,
+        pool: &PgPool,
+    ) -> Receiver<Arc<Frame>> {
+        let (h, rx) = handle(user_id, conn_id, 16);
+        node.insert_session(user_id as usize, h).await;
+        db::ws_session::toggle_user_status(user_id, true, pool)
+            .await
+            .unwrap();
+        node.connected(&user(user_id), conn_id, pool).await;
+        rx
+    }
+
+    async fn disconnect(node: &Chat, user_id: i64, conn_id: usize, pool: &PgPool) {
+        node.remove_session(user_id as usize, conn_id).await;
+        node.disconnected(user_id, conn_id, pool).await;
+    }
+
+    // someone in a guild with user, watching from node
+    async fn watcher(node: &Chat, user_id: i64, pool: &PgPool) -> Receiver<Arc<Frame>> {
+        let watcher = db_user("watcher", pool).await;
+        let guild = db::ws_session::create_guild(
+            watcher,
+            WsGuildCreate {
+                name: "g".to_string(),
+                desc: None,
+                icon: None,
+            },
+            pool,
+        )
+        .await
+        .unwrap();
+        db::ws_session::join_guild(user_id, guild.id, pool)
+            .await
+            .unwrap();
+        let (h, rx) = handle(watcher, 100, 16);
+        node.insert_session(watcher as usize, h).await;
+        node.insert_id(guild.id.to_string(), watcher as usize, 100)
+            .await;
+        node.insert_id(guild.id.to_string(), user_id as usize, 0)
+            .await;
+        rx
+    }
+
+    async fn statuses(rx: &mut Receiver<Arc<Frame>>) -> Vec<Status> {
+        let mut seen = vec![];
+        while let Ok(Some(frame)) = timeout(Duration::from_millis(100), rx.recv()).await {
+            if let MessageTypes::PresenceUpdate(p) = &frame.event {
+                seen.push(p.status);
+            }
+        }
+        seen
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_leaving_one_node_keeps_the_other_online(pool: PgPool) {
+        let (a, b) = (node(), node());
+        let user = db_user("user", &pool).await;
+        let mut rx = watcher(&a, user, &pool).await;
+
+        let _a = connect(&a, user, 0, &pool).await;
+        let _b = connect(&b, user, 0, &pool).await;
+        assert_eq!(statuses(&mut rx).await, vec![Status::Online]);
+
+        disconnect(&a, user, 0, &pool).await;
+        // b still has them, a mustn't say otherwise
+        assert_eq!(statuses(&mut rx).await, vec![]);
+        assert!(is_online(user, &pool).await);
+        assert!(a.registry.presence_of(user as usize).is_none());
+
+        disconnect(&b, user, 0, &pool).await;
+        assert!(!is_online(user, &pool).await);
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_idle_counts_sessions_on_every_node(pool: PgPool) {
+        let (a, b) = (node(), node());
+        let user = db_user("user", &pool).await;
+        let mut rx = watcher(&a, user, &pool).await;
+        let _a = connect(&a, user, 0, &pool).await;
+        let _b = connect(&b, user, 0, &pool).await;
+        statuses(&mut rx).await;
+
+        db::presence::set_idle(a.node_id, 0, true, &pool)
+            .await
+            .unwrap();
+        a.refresh_presence(user, &pool, false).await;
+        // the session on b is still active
+        assert_eq!(statuses(&mut rx).await, vec![]);
+
+        db::presence::set_idle(b.node_id, 0, true, &pool)
+            .await
+            .unwrap();
+        a.refresh_presence(user, &pool, false).await;
+        assert_eq!(statuses(&mut rx).await, vec![Status::Idle]);
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_dead_node_takes_its_users_offline(pool: PgPool) {
+        let (a, b) = (node(), node());
+        let user = db_user("user", &pool).await;
+        let both = db_user("both", &pool).await;
+        let mut rx = watcher(&a, user, &pool).await;
+        let _b = connect(&b, user, 0, &pool).await;
+        let _both_a = connect(&a, both, 1, &pool).await;
+        let _both_b = connect(&b, both, 1, &pool).await;
+
+        // b stops touching its rows
+        sqlx::query(
+            "UPDATE gateway_session SET seen_at = NOW() - INTERVAL '5 minutes' WHERE node_id = $1",
+        )
+        .bind(b.node_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+        a.heartbeat(&pool).await;
+
+        assert_eq!(statuses(&mut rx).await, vec![Status::Offline]);
+        assert!(!is_online(user, &pool).await);
+        // still on a
+        assert!(is_online(both, &pool).await);
+        let left: Vec<Uuid> = sqlx::query_scalar("SELECT node_id FROM gateway_session")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(left, vec![a.node_id]);
+    }
+}
@@ -0,0 +1,156 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use actix_web::{http::StatusCode, web, HttpResponse};
+    use async_trait::async_trait;
+    use sqlx::PgPool;
+    use tokio::task::LocalSet;
+
+    use crate::config::Config;
+    use crate::controllers::{auth::Auth, me};
+    use crate::db::{self, signup::create_password};
+    use crate::mailer::{Mail, MailError, MailQueue, Mailer};
+    use crate::server::EmailChangeEvent;
+    use crate::test::db_user;
+    use crate::PLACEHOLDER_UUID;
+
+    // keeps what would have gone out
+    #[derive(Default)]
+    struct Outbox(Mutex<Vec<Mail>>);
+
+    #[async_trait]
+    impl Mailer for Outbox {
+        async fn send(&self, mail: &Mail) -> Result<(), MailError> {
+            self.0.lock().unwrap().push(Mail {
+                to: mail.to.clone(),
+                subject: mail.subject.clone(),
+                text: mail.text.clone(),
+                html: mail.html.clone(),
+            });
+            Ok(())
+        }
+    }
+
+    impl Outbox {
+        // the token in the last confirmation link sent to `to`
+        async fn link_for(&self, to: &str) -> String {
+            for _ in 0..50 {
+                let found = self.0.lock().unwrap().iter().rev().find_map(|mail| {
+                    let (_, rest) = mail.text.split_once("/me/email/confirm/")?;
+                    (mail.to == to).then(|| rest.split_whitespace().next().unwrap().to_string())
+                });
+                if let Some(token) = found {
+                    return token;
+                }
+                actix_web::rt::time::sleep(Duration::from_millis(20)).await;
+            }
+            panic!("no mail to {}", to);
+        }
+    }
+
+    async fn with_password(pool: &PgPool) -> i64 {
+        let user = db_user("user", pool).await;
+        db::password::set_password(user, create_password("hunter22".to_string()).unwrap(), pool)
+            .await
+            .unwrap();
+        user
+    }
+
+    async fn email(user_id: i64, pool: &PgPool) -> String {
+        sqlx::query_scalar("SELECT email FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn change(
+        user_id: i64,
+        to: &str,
+        mail: &web::Data<MailQueue>,
+        pool: &PgPool,
+    ) -> HttpResponse {
+        me::change_email(
+            web::Json(EmailChangeEvent {
+                email: to.to_string(),
+                password: "hunter22".to_string(),
+            }),
+            Auth {
+                user_id,
+                session_id: PLACEHOLDER_UUID.to_string(),
+            },
+            web::Data::new(pool.clone()),
+            mail.clone(),
+            web::Data::new(Config::default()),
+        )
+        .await
+    }
+
+    async fn confirm(token: &str, mail: &web::Data<MailQueue>, pool: &PgPool) -> StatusCode {
+        me::confirm_email(
+            web::Path::from(token.to_string()),
+            web::Data::new(pool.clone()),
+            mail.clone(),
+        )
+        .await
+        .status()
+    }
+
+    // The mail queue wants spawn_local, so a LocalSet
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_email_changes_only_once_confirmed(pool: PgPool) {
+        LocalSet::new()
+            .run_until(async {
+                let outbox = Arc::new(Outbox::default());
+                let mail = web::Data::new(MailQueue::start(outbox.clone()));
+                let user = with_password(&pool).await;
+
+                assert_eq!(
+                    change(user, "first@test.com", &mail, &pool).await.status(),
+                    StatusCode::OK
+                );
+                let first = outbox.link_for("first@test.com").await;
+                // asking isn't enough
+                assert_eq!(email(user, &pool).await, "user@test.com");
+
+                // a second request replaces the first, whose link stops working
+                change(user, "second@test.com", &mail, &pool).await;
+                let second = outbox.link_for("second@test.com").await;
+                assert_eq!(confirm(&first, &mail, &pool).await, StatusCode::BAD_REQUEST);
+                assert_eq!(email(user, &pool).await, "user@test.com");
+
+                assert_eq!(confirm(&second, &mail, &pool).await, StatusCode::OK);
+                assert_eq!(email(user, &pool).await, "second@test.com");
+                // and it's spent
+                assert_eq!(
+                    confirm(&second, &mail, &pool).await,
+                    StatusCode::BAD_REQUEST
+                );
+            })
+            .await
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_expired_email_link_is_refused(pool: PgPool) {
+        LocalSet::new()
+            .run_until(async {
+                let outbox = Arc::new(Outbox::default());
+                let mail = web::Data::new(MailQueue::start(outbox.clone()));
+                let user = with_password(&pool).await;
+
+                change(user, "new@test.com", &mail, &pool).await;
+                let link = outbox.link_for("new@test.com").await;
+                sqlx::query("UPDATE email_change SET expires_at = NOW() - INTERVAL '1 minute'")
+                    .execute(&pool)
+                    .await
+                    .unwrap();
+                assert_eq!(confirm(&link, &mail, &pool).await, StatusCode::BAD_REQUEST);
+                assert_eq!(email(user, &pool).await, "user@test.com");
+            })
+            .await
+    }
+}
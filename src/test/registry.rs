@@ -0,0 +1,104 @@
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Instant,
+    };
+
+    use crate::test::handle;
+    use crate::messages::{Message, MessageTypes, Status};
+    use crate::server::{bus::InMemoryBus, presence::effective, registry::Registry, Chat};
+    use crate::PLACEHOLDER_UUID;
+
+    #[test]
+    fn test_leave_keeps_other_sessions() {
+        let registry = Registry::new();
+        let (a, _ra) = handle(1, 1, 1);
+        let (b, _rb) = handle(1, 2, 1);
+        registry.insert_session(1, a);
+        registry.insert_session(1, b);
+        assert!(registry.join_guild("g", 1, 1));
+        assert!(!registry.join_guild("g", 1, 2));
+
+        assert!(registry.remove_session(1, 1).is_empty());
+        assert_eq!(registry.members_of("g"), vec![1]);
+
+        assert_eq!(registry.remove_session(1, 2), vec!["g".to_string()]);
+        assert!(registry.members_of("g").is_empty());
+        assert!(registry.sessions_of(1).is_empty());
+    }
+
+    #[test]
+    fn test_effective_status() {
+        assert_eq!(effective(Status::Online, &[]), Status::Offline);
+        assert_eq!(effective(Status::Online, &[true, false]), Status::Online);
+        assert_eq!(effective(Status::Online, &[true, true]), Status::Idle);
+        assert_eq!(effective(Status::Dnd, &[true]), Status::Dnd);
+        assert_eq!(effective(Status::Invisible, &[false]), Status::Offline);
+    }
+
+    // cargo test bench_guild_fan_out -- --ignored --nocapture
+    #[actix_web::test]
+    #[ignore]
+    async fn bench_guild_fan_out() {
+        const SESSIONS: usize = 5000;
+        const GUILDS: usize = 50;
+        const MESSAGES: usize = 200;
+
+        let chat = Chat::new(
+            Arc::new(AtomicUsize::new(0)),
+            MESSAGES,
+            Arc::new(InMemoryBus::new()),
+        );
+        let received = Arc::new(AtomicUsize::new(0));
+
+        let start = Instant::now();
+        let joins = (0..SESSIONS).map(|i| {
+            let chat = chat.clone();
+            let received = received.clone();
+            actix_web::rt::spawn(async move {
+                let (h, mut rx) = handle(i as i64, i, MESSAGES);
+                chat.insert_session(i, h).await;
+                chat.insert_id((i % GUILDS).to_string(), i, i).await;
+                actix_web::rt::spawn(async move {
+                    while rx.recv().await.is_some() {
+                        received.fetch_add(1, Ordering::Relaxed);
+                    }
+                });
+            })
+        });
+        futures::future::join_all(joins).await;
+        println!("{} sessions registered in {:?}", SESSIONS, start.elapsed());
+
+        let start = Instant::now();
+        let sends = (0..GUILDS).map(|g| {
+            let chat = chat.clone();
+            actix_web::rt::spawn(async move {
+                for _ in 0..MESSAGES {
+                    chat.send_guild_message(
+                        &g.to_string(),
+                        MessageTypes::MessageCreate(Message::system(
+                            "bench".to_string(),
+                            PLACEHOLDER_UUID,
+                            0,
+                        )),
+                    )
+                    .await;
+                }
+            })
+        });
+        futures::future::join_all(sends).await;
+        let elapsed = start.elapsed();
+        let total = GUILDS * MESSAGES * (SESSIONS / GUILDS);
+        println!(
+            "{} frames queued in {:?} ({:.0} frames/s), {} drained so far",
+            total,
+            elapsed,
+            total as f64 / elapsed.as_secs_f64(),
+            received.load(Ordering::Relaxed)
+        );
+    }
+}
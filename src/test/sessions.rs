@@ -0,0 +1,51 @@
+#[cfg(test)]
+mod tests {
+    use sqlx::{types::Uuid, PgPool};
+
+    use crate::db;
+    use crate::test::{db_user, test_agent};
+    use crate::token;
+
+    async fn age(session_id: Uuid, pool: &PgPool) {
+        sqlx::query(
+            "UPDATE user_sessions SET last_login = NOW() - INTERVAL '8 days' WHERE session_id = $1",
+        )
+        .bind(session_id)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_used_token_survives_wipe(pool: PgPool) {
+        let bot = db_user("bot", &pool).await;
+        let used = db::login::create_session(bot, &pool, test_agent())
+            .await
+            .unwrap();
+        let idle = db::login::create_session(bot, &pool, test_agent())
+            .await
+            .unwrap();
+        let t = token::generate();
+        db::auth::create_token(used, token::hash(&t), &pool)
+            .await
+            .unwrap();
+        age(used, &pool).await;
+        age(idle, &pool).await;
+
+        // the bot keeps using its token
+        assert_eq!(
+            db::auth::session_for_token(token::hash(&t), &pool)
+                .await
+                .unwrap(),
+            Some((bot, used))
+        );
+        db::sessions::wipe_stale(&pool).await.unwrap();
+
+        assert!(db::auth::session_exists(bot, used, &pool).await.unwrap());
+        assert!(!db::auth::session_exists(bot, idle, &pool).await.unwrap());
+        assert!(db::auth::session_for_token(token::hash(&t), &pool)
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
@@ -0,0 +1,102 @@
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        http::{header, StatusCode},
+        test::TestRequest,
+        web, FromRequest,
+    };
+    use actix_ws::CloseCode;
+    use chrono::NaiveDate;
+    use sqlx::PgPool;
+
+    use crate::controllers::auth::Auth;
+    use crate::controllers::login::{describe, suspended};
+    use crate::controllers::ws::{refusal, CLOSE_SUSPENDED};
+    use crate::db;
+    use crate::db::suspension::Suspension;
+    use crate::test::{db_user, test_agent};
+    use crate::token;
+
+    async fn bearer_auth(token: &str, pool: &PgPool) -> Result<Auth, actix_web::Error> {
+        let req = TestRequest::default()
+            .app_data(web::Data::new(pool.clone()))
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", token)))
+            .to_http_request();
+        Auth::extract(&req).await
+    }
+
+    async fn run_out(user_id: i64, pool: &PgPool) {
+        sqlx::query("UPDATE users SET suspended_until = NOW() - INTERVAL '1 minute' WHERE id = $1")
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_describe_suspension() {
+        let permanent = Suspension {
+            until: None,
+            reason: None,
+        };
+        assert_eq!(describe(&permanent), "Account suspended");
+
+        let until = NaiveDate::from_ymd_opt(2026, 10, 20)
+            .unwrap()
+            .and_hms_opt(18, 30, 0)
+            .unwrap();
+        let temporary = Suspension {
+            until: Some(until),
+            reason: Some("Spamming invites".to_string()),
+        };
+        assert_eq!(
+            describe(&temporary),
+            "Account suspended until 2026-10-20 18:30 UTC: Spamming invites"
+        );
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_suspended_user_is_refused_until_it_runs_out(pool: PgPool) {
+        let user = db_user("spammer", &pool).await;
+        let session_id = db::login::create_session(user, &pool, test_agent())
+            .await
+            .unwrap();
+        let t = token::generate();
+        db::auth::create_token(session_id, token::hash(&t), &pool)
+            .await
+            .unwrap();
+        assert!(bearer_auth(&t, &pool).await.is_ok());
+
+        // a session that was around before the suspension, nothing revoked it
+        db::suspension::suspend(user, Some(60), Some("Spamming invites".to_string()), &pool)
+            .await
+            .unwrap();
+
+        let res = suspended(user, &pool).await.unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        let err = bearer_auth(&t, &pool).await.unwrap_err();
+        assert_eq!(err.as_response_error().status_code(), StatusCode::FORBIDDEN);
+        assert!(err.to_string().contains("Spamming invites"));
+        let close = refusal(user, &pool).await.unwrap();
+        assert_eq!(close.code, CloseCode::Other(CLOSE_SUSPENDED));
+
+        run_out(user, &pool).await;
+        assert!(suspended(user, &pool).await.is_none());
+        assert_eq!(bearer_auth(&t, &pool).await.unwrap().user_id, user);
+        assert!(refusal(user, &pool).await.is_none());
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_lifted_suspension_lets_user_in(pool: PgPool) {
+        let user = db_user("forgiven", &pool).await;
+        db::suspension::suspend(user, None, None, &pool)
+            .await
+            .unwrap();
+        assert!(suspended(user, &pool).await.is_some());
+        assert!(refusal(user, &pool).await.is_some());
+
+        db::suspension::lift(user, &pool).await.unwrap();
+        assert!(suspended(user, &pool).await.is_none());
+        assert!(refusal(user, &pool).await.is_none());
+    }
+}
@@ -0,0 +1,123 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use actix_identity::IdentityMiddleware;
+    use actix_session::{storage::CookieSessionStore, SessionMiddleware};
+    use actix_web::{
+        cookie::Key,
+        http::StatusCode,
+        test::{call_service, init_service, TestRequest},
+        web, App,
+    };
+    use sqlx::PgPool;
+    use user_agent_parser::UserAgentParser;
+
+    use crate::controllers::login::mfa;
+    use crate::server::MfaLoginEvent;
+    use crate::test::{mfa_user, totp_code};
+    use crate::throttle::{account_key, lock_seconds, ACCOUNT_FREE};
+    use crate::{db, token};
+
+    // what /login hands out once the password checked out
+    async fn ticket(user_id: i64, pool: &PgPool) -> String {
+        let ticket = token::generate();
+        db::mfa::create_ticket(user_id, token::hash(&ticket), 5, pool)
+            .await
+            .unwrap();
+        ticket
+    }
+
+    // through the app, a good code logs in and that wants the cookie middleware
+    async fn second_factor(ticket: &str, code: &str, pool: &PgPool) -> StatusCode {
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(Arc::new(
+                    UserAgentParser::from_path("./regexes.yaml").unwrap(),
+                )))
+                .wrap(IdentityMiddleware::default())
+                .wrap(SessionMiddleware::new(
+                    CookieSessionStore::default(),
+                    Key::generate(),
+                ))
+                .route("/login/mfa", web::post().to(mfa)),
+        )
+        .await;
+        let req = TestRequest::post()
+            .uri("/login/mfa")
+            .set_json(MfaLoginEvent {
+                ticket: ticket.to_string(),
+                code: code.to_string(),
+            })
+            .to_request();
+        call_service(&app, req).await.status()
+    }
+
+    async fn failures(email: &str, pool: &PgPool) -> Option<i32> {
+        sqlx::query_scalar("SELECT failures FROM login_throttle WHERE key = $1")
+            .bind(account_key(email))
+            .fetch_optional(pool)
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_lock_doubles_after_free_attempts() {
+        assert_eq!(lock_seconds(ACCOUNT_FREE, ACCOUNT_FREE), None);
+        assert_eq!(lock_seconds(ACCOUNT_FREE + 1, ACCOUNT_FREE), Some(30.0));
+        assert_eq!(lock_seconds(ACCOUNT_FREE + 2, ACCOUNT_FREE), Some(60.0));
+        assert_eq!(lock_seconds(ACCOUNT_FREE + 3, ACCOUNT_FREE), Some(120.0));
+        // capped at an hour
+        assert_eq!(lock_seconds(1000, ACCOUNT_FREE), Some(3600.0));
+    }
+
+    #[test]
+    fn test_account_key_ignores_case() {
+        assert_eq!(account_key(" Test@Test.com"), account_key("test@test.com"));
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_wrong_codes_count_across_tickets(pool: PgPool) {
+        let (user, secret) = mfa_user("owner", "hunter22", &pool).await;
+        // a ticket runs out after its own few tries
+        let first = ticket(user, &pool).await;
+        for _ in 0..5 {
+            assert_eq!(
+                second_factor(&first, "000000", &pool).await,
+                StatusCode::BAD_REQUEST
+            );
+        }
+        assert_eq!(failures("owner@test.com", &pool).await, Some(ACCOUNT_FREE));
+
+        // but a new one doesn't start the account over
+        let second = ticket(user, &pool).await;
+        assert_eq!(
+            second_factor(&second, "000000", &pool).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            second_factor(&second, &totp_code(&secret), &pool).await,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_failures_stay_until_the_second_factor(pool: PgPool) {
+        let (user, secret) = mfa_user("owner", "hunter22", &pool).await;
+        db::throttle::record_failure(&account_key("owner@test.com"), 60, &pool)
+            .await
+            .unwrap();
+        let t = ticket(user, &pool).await;
+        assert_eq!(
+            second_factor(&t, "000000", &pool).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(failures("owner@test.com", &pool).await, Some(2));
+        assert_eq!(
+            second_factor(&t, &totp_code(&secret), &pool).await,
+            StatusCode::OK
+        );
+        assert_eq!(failures("owner@test.com", &pool).await, None);
+    }
+}
@@ -0,0 +1,40 @@
+#[cfg(test)]
+mod tests {
+    use totp_rs::{Algorithm, Secret, TOTP};
+
+    use crate::totp;
+
+    #[test]
+    fn test_totp_code_is_single_use() {
+        let secret = totp::new_secret();
+        let code = TOTP::new(
+            Algorithm::SHA1,
+            6,
+            1,
+            30,
+            Secret::Encoded(secret.clone()).to_bytes().unwrap(),
+            None,
+            "".to_string(),
+        )
+        .unwrap()
+        .generate_current()
+        .unwrap();
+
+        let step = totp::check(&secret, &code, 0).unwrap();
+        // same code once the step is spent
+        assert_eq!(totp::check(&secret, &code, step), None);
+        assert_eq!(totp::check(&secret, "000000x", 0), None);
+        assert!(totp::uri(&secret, "a@test.com")
+            .unwrap()
+            .starts_with("otpauth://totp/"));
+    }
+
+    #[test]
+    fn test_recovery_codes_are_unique() {
+        let mut codes = totp::recovery_codes();
+        assert_eq!(codes.len(), totp::RECOVERY_CODES);
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), totp::RECOVERY_CODES);
+    }
+}
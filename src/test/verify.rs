@@ -0,0 +1,194 @@
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, web, HttpResponse};
+    use sqlx::PgPool;
+
+    use crate::controllers::auth::Auth;
+    use crate::controllers::verify::{self, CODE_MINUTES, MAX_ATTEMPTS, RESEND_COOLDOWN};
+    use crate::db::{
+        self,
+        signup::{create_user, NewUser},
+    };
+    use crate::server::VerifyEvent;
+    use crate::test::test_agent;
+    use crate::{token, PLACEHOLDER_UUID};
+
+    const CODE: i64 = 123456;
+
+    // a fresh signup, still waiting on its code
+    async fn signed_up(name: &str, link_token: &str, pool: &PgPool) -> i64 {
+        let user = NewUser {
+            username: name.to_string(),
+            email: format!("{}@test.com", name),
+            password_hash: "".to_string(),
+            code: CODE,
+            token_hash: token::hash(link_token),
+            minutes: CODE_MINUTES,
+        };
+        create_user(user, test_agent(), pool).await.unwrap().1
+    }
+
+    async fn guess(user_id: i64, code: i64, pool: &PgPool) -> HttpResponse {
+        verify::post(
+            web::Json(VerifyEvent { code }),
+            web::Data::new(pool.clone()),
+            Some(Auth {
+                user_id,
+                session_id: PLACEHOLDER_UUID.to_string(),
+            }),
+        )
+        .await
+    }
+
+    async fn verified(user_id: i64, pool: &PgPool) -> bool {
+        db::verify::code(user_id, pool).await.unwrap().is_none()
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_code_is_burnt_after_max_attempts(pool: PgPool) {
+        let user = signed_up("new", &token::generate(), &pool).await;
+        for _ in 0..MAX_ATTEMPTS - 1 {
+            assert_eq!(
+                guess(user, 1, &pool).await.status(),
+                StatusCode::BAD_REQUEST
+            );
+        }
+        // the last try still counts
+        assert_eq!(guess(user, CODE, &pool).await.status(), StatusCode::OK);
+        assert!(verified(user, &pool).await);
+
+        let user = signed_up("again", &token::generate(), &pool).await;
+        for _ in 0..MAX_ATTEMPTS {
+            assert_eq!(
+                guess(user, 1, &pool).await.status(),
+                StatusCode::BAD_REQUEST
+            );
+        }
+        // even the right one is refused now
+        assert_eq!(
+            guess(user, CODE, &pool).await.status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert!(!verified(user, &pool).await);
+
+        // a new code starts the count over
+        sqlx::query("UPDATE users SET code_sent_at = NOW() - INTERVAL '2 minutes' WHERE id = $1")
+            .bind(user)
+            .execute(&pool)
+            .await
+            .unwrap();
+        db::verify::new_code(
+            user,
+            654321,
+            token::hash(&token::generate()),
+            CODE_MINUTES,
+            RESEND_COOLDOWN,
+            &pool,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(guess(user, 654321, &pool).await.status(), StatusCode::OK);
+        assert!(verified(user, &pool).await);
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_expired_code_is_refused(pool: PgPool) {
+        let user = signed_up("new", &token::generate(), &pool).await;
+        sqlx::query("UPDATE users SET code_expires_at = NOW() - INTERVAL '1 minute' WHERE id = $1")
+            .bind(user)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            guess(user, CODE, &pool).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+        assert!(!verified(user, &pool).await);
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_resend_waits_for_the_cooldown(pool: PgPool) {
+        let user = signed_up("new", &token::generate(), &pool).await;
+        let resend = |code: i64| {
+            let pool = pool.clone();
+            async move {
+                db::verify::new_code(
+                    user,
+                    code,
+                    token::hash(&token::generate()),
+                    CODE_MINUTES,
+                    RESEND_COOLDOWN,
+                    &pool,
+                )
+                .await
+                .unwrap()
+            }
+        };
+        // the signup mail just went out
+        assert_eq!(resend(111111).await, None);
+        assert_eq!(db::verify::code(user, &pool).await.unwrap(), Some(CODE));
+
+        sqlx::query("UPDATE users SET code_sent_at = NOW() - INTERVAL '61 seconds' WHERE id = $1")
+            .bind(user)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(resend(222222).await, Some("new@test.com".to_string()));
+        assert_eq!(db::verify::code(user, &pool).await.unwrap(), Some(222222));
+        // and the clock starts again
+        assert_eq!(resend(333333).await, None);
+
+        // nothing to resend once verified
+        db::verify::delete_code(user, &pool).await.unwrap();
+        sqlx::query("UPDATE users SET code_sent_at = NOW() - INTERVAL '61 seconds' WHERE id = $1")
+            .bind(user)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(resend(444444).await, None);
+    }
+
+    #[sqlx::test(migrator = "crate::migrate::MIGRATOR")]
+    async fn test_magic_link_verifies_once(pool: PgPool) {
+        let link_token = token::generate();
+        let user = signed_up("new", &link_token, &pool).await;
+        let click =
+            |t: &str| verify::link(web::Path::from(t.to_string()), web::Data::new(pool.clone()));
+
+        assert_eq!(click("nope").await.status(), StatusCode::BAD_REQUEST);
+        assert!(!verified(user, &pool).await);
+        assert_eq!(click(&link_token).await.status(), StatusCode::OK);
+        assert!(verified(user, &pool).await);
+        assert_eq!(click(&link_token).await.status(), StatusCode::BAD_REQUEST);
+
+        // a resend replaces the link, and links run out with the code
+        let old_link = token::generate();
+        let user = signed_up("again", &old_link, &pool).await;
+        sqlx::query("UPDATE users SET code_sent_at = NOW() - INTERVAL '61 seconds' WHERE id = $1")
+            .bind(user)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let new_link = token::generate();
+        db::verify::new_code(
+            user,
+            CODE,
+            token::hash(&new_link),
+            CODE_MINUTES,
+            RESEND_COOLDOWN,
+            &pool,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(click(&old_link).await.status(), StatusCode::BAD_REQUEST);
+        sqlx::query("UPDATE users SET code_expires_at = NOW() - INTERVAL '1 minute' WHERE id = $1")
+            .bind(user)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(click(&new_link).await.status(), StatusCode::BAD_REQUEST);
+        assert!(!verified(user, &pool).await);
+    }
+}
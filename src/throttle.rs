@@ -0,0 +1,36 @@
+// Failed logins are counted per account (by email, known or not) and per IP.
+// The first few are free, after that each failure locks the key for twice as long
+// as the last one, up to MAX_LOCK.
+
+pub const ACCOUNT_FREE: i32 = 5;
+pub const IP_FREE: i32 = 20;
+// reset mails per address, counted apart from logins so spamming them
+// can't lock someone out of their account
+pub const RESET_FREE: i32 = 3;
+
+const BASE_LOCK: f64 = 30.0;
+const MAX_LOCK: f64 = 60.0 * 60.0;
+
+// failures are forgotten after a quiet day
+pub const FORGET_MINUTES: i32 = 24 * 60;
+
+pub fn account_key(email: &str) -> String {
+    format!("email:{}", email.trim().to_lowercase())
+}
+
+pub fn reset_key(email: &str) -> String {
+    format!("reset:{}", email.trim().to_lowercase())
+}
+
+pub fn ip_key(ip: &str) -> String {
+    format!("ip:{}", ip)
+}
+
+// seconds the key is locked for after its nth failure
+pub fn lock_seconds(failures: i32, free: i32) -> Option<f64> {
+    if failures <= free {
+        return None;
+    }
+    let over = (failures - free - 1).min(16);
+    Some((BASE_LOCK * 2f64.powi(over)).min(MAX_LOCK))
+}
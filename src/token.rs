@@ -0,0 +1,14 @@
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+// Random secrets handed to users (reset links, tickets, ...). Only the hash gets stored.
+
+pub fn generate() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
@@ -0,0 +1,59 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::{distributions::Alphanumeric, Rng};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+// 30 second steps, 6 digits, one step of clock drift either way. What every authenticator app expects.
+const STEP: u64 = 30;
+const ISSUER: &str = "flettex";
+
+pub const RECOVERY_CODES: usize = 10;
+
+fn totp(secret: &str, account: &str) -> Option<TOTP> {
+    let bytes = Secret::Encoded(secret.to_string()).to_bytes().ok()?;
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        STEP,
+        bytes,
+        Some(ISSUER.to_string()),
+        account.to_string(),
+    )
+    .ok()
+}
+
+// base32, which is what goes in the db and what users type in by hand
+pub fn new_secret() -> String {
+    Secret::generate_secret().to_encoded().to_string()
+}
+
+// otpauth:// URI for the QR code
+pub fn uri(secret: &str, account: &str) -> Option<String> {
+    totp(secret, account).map(|t| t.get_url())
+}
+
+// Returns the step the code belongs to if it's valid and newer than last_step
+pub fn check(secret: &str, code: &str, last_step: i64) -> Option<i64> {
+    let t = totp(secret, "")?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    [now - STEP, now, now + STEP]
+        .into_iter()
+        .map(|time| ((time / STEP) as i64, time))
+        .find(|(step, time)| *step > last_step && t.generate(*time) == code)
+        .map(|(step, _)| step)
+}
+
+// xxxxx-xxxxx, only their hashes are stored
+pub fn recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODES)
+        .map(|_| {
+            let s: String = rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(10)
+                .map(|c| (c as char).to_ascii_lowercase())
+                .collect();
+            format!("{}-{}", &s[..5], &s[5..])
+        })
+        .collect()
+}